@@ -38,11 +38,21 @@ impl ServerOneConn {
             + Sync
             + 'static,
     {
-        ServerOneConn::new_fn_impl(port, service)
+        ServerOneConn::new_fn_impl(port, Default::default(), service)
+    }
+
+    pub fn new_fn_with_conf<S>(port: u16, conf: ServerConf, service: S) -> Self
+    where
+        S: Fn(ServerHandlerContext, ServerRequest, ServerResponse) -> httpbis::Result<()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        ServerOneConn::new_fn_impl(port, conf, service)
     }
 
     #[allow(dead_code)]
-    fn new_fn_impl<S>(port: u16, service: S) -> Self
+    fn new_fn_impl<S>(port: u16, conf: ServerConf, service: S) -> Self
     where
         S: Fn(ServerHandlerContext, ServerRequest, ServerResponse) -> httpbis::Result<()>
             + Send
@@ -85,7 +95,7 @@ impl ServerOneConn {
                         &handle,
                         conn,
                         peer_addr,
-                        Default::default(),
+                        conf,
                         service,
                     );
                     *conn_for_thread.lock().unwrap() = Some(conn);
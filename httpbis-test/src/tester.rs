@@ -22,6 +22,8 @@ use httpbis::for_test::solicit::frame::GoawayFrame;
 use httpbis::for_test::solicit::frame::HeadersFlag;
 use httpbis::for_test::solicit::frame::HeadersFrame;
 use httpbis::for_test::solicit::frame::HttpFrame;
+use httpbis::for_test::solicit::frame::PushPromiseFlag;
+use httpbis::for_test::solicit::frame::PushPromiseFrame;
 use httpbis::for_test::solicit::frame::RawFrame;
 use httpbis::for_test::solicit::frame::RstStreamFrame;
 use httpbis::for_test::solicit::frame::SettingsFrame;
@@ -132,6 +134,11 @@ impl HttpConnTester {
         Self::with_tcp(tcp)
     }
 
+    /// Client side address of the connection.
+    pub fn local_addr(&self) -> net::SocketAddr {
+        self.tcp.local_addr().unwrap()
+    }
+
     pub fn recv_preface(&mut self) {
         let mut preface = Vec::new();
         preface.resize(PREFACE.len(), 0);
@@ -391,6 +398,40 @@ impl HttpConnTester {
         (frame, headers, cont_count)
     }
 
+    pub fn recv_frame_push_promise_decode(&mut self) -> (PushPromiseFrame, Headers, u32) {
+        let mut frame = match self.recv_frame() {
+            HttpFrame::PushPromise(frame) => frame,
+            f => panic!("expecting PUSH_PROMISE, got: {:?}", f),
+        };
+
+        let mut cont_count = 0;
+
+        while !frame.flags.is_set(PushPromiseFlag::EndHeaders) {
+            let continuation = self.recv_frame_continuation();
+            cont_count += 1;
+
+            frame
+                .header_fragment
+                .extend_from_slice(&continuation.header_fragment);
+
+            if continuation.flags.is_set(ContinuationFlag::EndHeaders) {
+                frame.flags.set(PushPromiseFlag::EndHeaders);
+            }
+        }
+
+        let headers = self
+            .decoder
+            .decode(mem::take(&mut frame.header_fragment))
+            .expect("decode");
+        let headers = Headers::from_vec(
+            headers
+                .into_iter()
+                .map(|(n, v)| Header::new(n, v))
+                .collect(),
+        );
+        (frame, headers, cont_count)
+    }
+
     pub fn recv_frame_headers_check(&mut self, stream_id: StreamId, end: bool) -> Headers {
         let (frame, headers, _) = self.recv_frame_headers_decode();
         assert_eq!(stream_id, frame.stream_id);
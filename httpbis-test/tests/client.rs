@@ -14,6 +14,8 @@ use futures::stream::StreamExt;
 use futures::future;
 use futures::future::TryFutureExt;
 
+use httpbis::for_test::solicit::frame::HttpSetting;
+use httpbis::for_test::solicit::frame::SettingsFrame;
 use httpbis::for_test::solicit::DEFAULT_SETTINGS;
 use httpbis::for_test::*;
 use httpbis::ErrorCode;
@@ -449,3 +451,108 @@ fn sink_reset_by_peer() {
     assert_eq!(0, client.conn_state().out_window_size);
     assert_eq!(0, client.conn_state().pump_out_window_size);
 }
+
+#[test]
+fn queue_requests_over_max_concurrent_streams() {
+    init_logger();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(1),
+    ]));
+
+    let req1 = client.start_get("/aa", "localhost").collect();
+    server_tester.recv_message(1);
+
+    let req2 = client.start_get("/bb", "localhost").collect();
+
+    let state = client.conn_state();
+    assert_eq!(1, state.streams.len(), "{:?}", state);
+    assert_eq!(1, state.queued_streams, "{:?}", state);
+
+    server_tester.send_headers(1, Headers::ok_200(), true);
+
+    // second request is started after the first one is closed
+    let get = server_tester.recv_message(3);
+    assert_eq!("/bb", get.headers.path());
+    server_tester.send_headers(3, Headers::ok_200(), true);
+
+    let mut rt = Runtime::new().unwrap();
+
+    assert_eq!(200, rt.block_on(req1).expect("req1").headers.status());
+    assert_eq!(200, rt.block_on(req2).expect("req2").headers.status());
+
+    let state = client.conn_state();
+    assert_eq!(0, state.streams.len(), "{:?}", state);
+    assert_eq!(0, state.queued_streams, "{:?}", state);
+}
+
+#[test]
+fn queued_request_started_after_response_end() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let (mut server_tester, client) = HttpConnTester::new_server_with_client_xchg();
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(1),
+    ]));
+
+    let (_sender, _response) = rt
+        .block_on(client.start_post_sink("/aa", "localhost"))
+        .expect("start_post_sink");
+    server_tester.recv_frame_headers_check(1, false);
+
+    let req2 = client.start_get("/bb", "localhost").collect();
+    assert_eq!(1, client.conn_state().queued_streams);
+
+    // request body is still open, but server completed the response
+    server_tester.send_headers(1, Headers::ok_200(), true);
+
+    let get = server_tester.recv_message(3);
+    assert_eq!("/bb", get.headers.path());
+    server_tester.send_headers(3, Headers::ok_200(), true);
+    assert_eq!(200, rt.block_on(req2).expect("req2").headers.status());
+}
+
+#[test]
+fn queued_request_limit_and_timeout() {
+    init_logger();
+
+    let server = HttpServerTester::new();
+
+    let mut conf = ClientConf::new();
+    conf.max_queued_requests = Some(1);
+    conf.queued_request_timeout = Some(Duration::from_millis(100));
+    let client = Client::new_plain(BIND_HOST, server.port(), conf).expect("client");
+
+    let mut server_tester = server.accept_xchg();
+
+    server_tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(1),
+    ]));
+
+    let _req1 = client.start_get("/aa", "localhost").collect();
+    server_tester.recv_message(1);
+
+    let req2 = client.start_get("/bb", "localhost").collect();
+    let req3 = client.start_get("/cc", "localhost").collect();
+
+    let mut rt = Runtime::new().unwrap();
+
+    match rt.block_on(req3) {
+        Err(Error::RequestQueueFull) => {}
+        r => panic!("wrong result: {:?}", r.map(|_| ())),
+    }
+
+    match rt.block_on(req2) {
+        Err(Error::RequestQueueTimeout) => {}
+        r => panic!("wrong result: {:?}", r.map(|_| ())),
+    }
+
+    let state = client.conn_state();
+    assert_eq!(1, state.streams.len(), "{:?}", state);
+    assert_eq!(0, state.queued_streams, "{:?}", state);
+}
@@ -8,28 +8,37 @@ use httpbis_test::*;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use bytes::Bytes;
 
 use std::io::Read as _Read;
 use std::io::Write as _Write;
 use std::thread;
+use std::time::Duration;
 
+use futures::executor;
 use futures::stream;
 
 use futures::channel::oneshot;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 
 use std::task::Poll;
 
+use httpbis::for_test::solicit::frame::ContinuationFrame;
 use httpbis::for_test::solicit::frame::HeadersFlag;
+use httpbis::for_test::solicit::frame::HeadersFrame;
+use httpbis::for_test::solicit::frame::HttpFrame;
 use httpbis::for_test::solicit::frame::HttpSetting;
+use httpbis::for_test::solicit::frame::PingFrame;
 use httpbis::for_test::solicit::frame::SettingsFrame;
 use httpbis::for_test::solicit::DEFAULT_SETTINGS;
 use httpbis::*;
 
 use std::iter::FromIterator;
+use std::net::Shutdown;
 use std::net::TcpStream;
 use std::sync::mpsc;
 
@@ -524,6 +533,291 @@ pub fn http_1_1_unix() {
     );
 }
 
+fn http_1_conf() -> ServerConf {
+    let mut conf = ServerConf::new();
+    conf.http1 = Some(true);
+    conf
+}
+
+fn count_subslice(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|w| *w == needle)
+        .count()
+}
+
+#[test]
+pub fn http_1_1_keep_alive() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), |_, req, mut resp| {
+        resp.send_found_200_plain_text(req.headers.path())?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+
+    tcp_stream
+        .write_all(b"GET /first HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .expect("write");
+    tcp_stream
+        .write_all(b"GET /second HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert!(
+        read.starts_with(b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+    assert_eq!(
+        2,
+        count_subslice(&read, b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+    assert_eq!(1, count_subslice(&read, b"6\r\n/first\r\n0\r\n\r\n"));
+    assert_eq!(1, count_subslice(&read, b"7\r\n/second\r\n0\r\n\r\n"));
+    assert_eq!(1, count_subslice(&read, b"connection: close\r\n"));
+}
+
+#[test]
+pub fn http_1_1_post_body() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+
+    tcp_stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nabcd")
+        .expect("write");
+    tcp_stream
+        .write_all(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\
+              Connection: close\r\n\r\n3\r\nefg\r\n2\r\nhi\r\n0\r\n\r\n",
+        )
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert_eq!(
+        2,
+        count_subslice(&read, b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+    assert_eq!(1, count_subslice(&read, b"abcd"), "{:?}", BsDebug(&read));
+    assert_eq!(1, count_subslice(&read, b"efg"), "{:?}", BsDebug(&read));
+    assert_eq!(1, count_subslice(&read, b"hi"), "{:?}", BsDebug(&read));
+}
+
+struct CountingStreamHandler(Arc<AtomicUsize>);
+
+impl ServerRequestStreamHandler for CountingStreamHandler {
+    fn data_frame(&mut self, data: Bytes, _end_stream: bool) -> httpbis::Result<()> {
+        self.0.fetch_add(data.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn trailers(&mut self, _trailers: Headers) -> httpbis::Result<()> {
+        Ok(())
+    }
+
+    fn error(&mut self, _error: Error) -> httpbis::Result<()> {
+        Ok(())
+    }
+}
+
+fn wait_for_count(count: &AtomicUsize, expected: usize) {
+    for _ in 0..500 {
+        if count.load(Ordering::SeqCst) >= expected {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(expected, count.load(Ordering::SeqCst));
+}
+
+#[test]
+pub fn http_1_1_request_body_flow_control() {
+    init_logger();
+
+    const BODY_LEN: usize = 200_000;
+
+    let received = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let received_copy = received.clone();
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), move |_, req, resp| {
+        let received = received_copy.clone();
+        let increase = req
+            .register_stream_handler(move |increase| (CountingStreamHandler(received), increase));
+        tx.lock().unwrap().send((increase, resp)).unwrap();
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    tcp_stream
+        .write_all(
+            format!(
+                "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n",
+                BODY_LEN
+            )
+            .as_bytes(),
+        )
+        .expect("write");
+    let mut writer = tcp_stream.try_clone().expect("clone");
+    thread::spawn(move || writer.write_all(&[b'a'; BODY_LEN]).expect("write"));
+
+    let (mut increase, mut resp) = rx.recv().unwrap();
+
+    // handler gets no more than the initial window
+    let window = DEFAULT_SETTINGS.initial_window_size as usize;
+    wait_for_count(&received, window);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(window, received.load(Ordering::SeqCst));
+
+    increase
+        .increase_window((BODY_LEN - window) as u32)
+        .expect("increase");
+    wait_for_count(&received, BODY_LEN);
+
+    resp.send_found_200_plain_text("done").expect("send");
+    // client half-close is a disconnect while response is pending
+    let mut read = Vec::new();
+    while count_subslice(&read, b"done") == 0 {
+        let mut buf = [0; 1024];
+        let n = tcp_stream.read(&mut buf).expect("read");
+        assert!(n != 0, "{:?}", BsDebug(&read));
+        read.extend_from_slice(&buf[..n]);
+    }
+    tcp_stream.shutdown(Shutdown::Write).expect("shutdown");
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert_eq!(
+        1,
+        count_subslice(&read, b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+}
+
+#[test]
+pub fn http_1_1_bad_request() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), |_, _req, mut resp| {
+        resp.send_found_200_plain_text("unreachable")?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+
+    tcp_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nContent-Length: x\r\n\r\n")
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert!(
+        read.starts_with(b"HTTP/1.1 400 Bad Request\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+}
+
+#[test]
+pub fn http_1_1_content_length_mismatch() {
+    init_logger();
+
+    for body in &["abc", "abcdefgh"] {
+        let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), move |_, _req, mut resp| {
+            let mut headers = Headers::ok_200();
+            headers.add("content-length", "5");
+            resp.send_headers(headers)?;
+            resp.send_data_end_of_stream(Bytes::from(*body))?;
+            Ok(())
+        });
+
+        let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+
+        // connection is closed instead of serving the second request
+        tcp_stream
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"
+                    .repeat(2)
+                    .as_slice(),
+            )
+            .expect("write");
+
+        let mut read = Vec::new();
+        tcp_stream.read_to_end(&mut read).expect("read");
+        assert_eq!(
+            1,
+            count_subslice(&read, b"HTTP/1.1 200 OK\r\n"),
+            "{:?}",
+            BsDebug(&read)
+        );
+        assert_eq!(0, count_subslice(&read, b"abcdef"), "{:?}", BsDebug(&read));
+    }
+}
+
+#[test]
+pub fn h2c_upgrade() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), |_, req, mut resp| {
+        resp.send_found_200_plain_text(req.headers.path())?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+
+    // SETTINGS_INITIAL_WINDOW_SIZE = 4
+    tcp_stream
+        .write_all(
+            b"GET /upgrade HTTP/1.1\r\nHost: localhost\r\n\
+              Connection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\n\
+              HTTP2-Settings: AAQAAAAE\r\n\r\n",
+        )
+        .expect("write");
+
+    let mut read = Vec::new();
+    while !read.ends_with(b"\r\n\r\n") {
+        let mut c = [0];
+        tcp_stream.read_exact(&mut c).expect("read");
+        read.push(c[0]);
+    }
+    assert!(
+        read.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+
+    let mut tester = HttpConnTester::with_tcp(tcp_stream);
+    tester.send_preface();
+    tester.settings_xchg_but_ack();
+
+    let headers = tester.recv_frame_headers_check(1, false);
+    assert_eq!("200", headers.get(":status"));
+
+    // window is limited by HTTP2-Settings
+    assert_eq!(&b"/upg"[..], &tester.recv_frame_data_check(1, false)[..]);
+    tester.send_window_update_stream(1, 4);
+    assert_eq!(&b"rade"[..], &tester.recv_frame_data_check(1, true)[..]);
+
+    // connection continues as HTTP/2
+    let resp = tester.get(3, "/n");
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"/n"[..], &resp.body.get_bytes()[..]);
+}
+
 #[test]
 fn external_event_loop() {
     init_logger();
@@ -586,3 +880,2065 @@ fn external_event_loop() {
 
     info!("last line of test");
 }
+
+fn start_echo_stream(tester: &mut HttpConnTester, stream_id: StreamId) {
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    tester.send_headers(stream_id, headers, false);
+
+    assert_eq!(
+        200,
+        tester.recv_frame_headers_check(stream_id, false).status()
+    );
+}
+
+fn recv_ping(tester: &mut HttpConnTester) -> PingFrame {
+    match tester.recv_frame() {
+        HttpFrame::Ping(ping) => ping,
+        f => panic!("expecting PING, got: {:?}", f),
+    }
+}
+
+#[test]
+fn shutdown_graceful() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    start_echo_stream(&mut tester, 1);
+
+    let shutdown = server.server.shutdown_graceful(Duration::from_secs(10));
+
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(0x7fff_ffff, goaway.last_stream_id());
+
+    let ping = recv_ping(&mut tester);
+    assert!(!ping.is_ack());
+
+    // stream is accepted before PING ack
+    start_echo_stream(&mut tester, 3);
+
+    tester.send_frame(PingFrame::new_ack(ping.opaque_data()));
+
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(3, goaway.last_stream_id());
+
+    tester.send_data(1, b"abcd", true);
+    assert_eq!(&b"abcd"[..], &tester.recv_frame_data_tail(1)[..]);
+
+    tester.send_data(3, b"efgh", true);
+    assert_eq!(&b"efgh"[..], &tester.recv_frame_data_tail(3)[..]);
+
+    tester.recv_eof();
+
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(shutdown).expect("shutdown");
+}
+
+#[test]
+fn shutdown_graceful_ping_not_acked() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    start_echo_stream(&mut tester, 1);
+
+    let shutdown = server.server.shutdown_graceful(Duration::from_secs(10));
+
+    tester.recv_goaway_frame();
+    recv_ping(&mut tester);
+
+    // final GOAWAY is sent without PING ack
+    let goaway = tester.recv_goaway_frame();
+    assert_eq!(ErrorCode::NoError, goaway.error_code());
+    assert_eq!(1, goaway.last_stream_id());
+
+    tester.send_data(1, b"abcd", true);
+    assert_eq!(&b"abcd"[..], &tester.recv_frame_data_tail(1)[..]);
+
+    tester.recv_eof();
+
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(shutdown).expect("shutdown");
+}
+
+#[test]
+fn shutdown_graceful_drain_timeout() {
+    init_logger();
+
+    let server = ServerTest::new();
+
+    let mut tester = HttpConnTester::connect(server.port);
+    tester.send_preface();
+    tester.settings_xchg();
+
+    start_echo_stream(&mut tester, 1);
+
+    let shutdown = server.server.shutdown_graceful(Duration::from_millis(100));
+
+    tester.recv_goaway_frame();
+    recv_ping(&mut tester);
+
+    // neither PING ack nor stream end is sent, so connection is closed forcibly
+    let mut rt = Runtime::new().unwrap();
+    rt.block_on(shutdown).expect("shutdown");
+
+    tester.recv_eof();
+}
+
+#[test]
+fn max_concurrent_streams() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.max_concurrent_streams = Some(1);
+
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert_eq!(1, tester.peer_settings.max_concurrent_streams);
+
+    start_echo_stream(&mut tester, 1);
+
+    tester.send_get(3, "/aabb");
+    tester.recv_rst_frame_check(3, ErrorCode::RefusedStream);
+
+    // refused stream does not affect the open one
+    tester.send_data(1, b"abcd", true);
+    assert_eq!(&b"abcd"[..], &tester.recv_frame_data_tail(1)[..]);
+
+    // stream slot is free again
+    start_echo_stream(&mut tester, 5);
+    tester.send_data(5, b"efgh", true);
+    assert_eq!(&b"efgh"[..], &tester.recv_frame_data_tail(5)[..]);
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn max_concurrent_streams_ignores_pushed() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let mut conf = ServerConf::new();
+    conf.max_concurrent_streams = Some(1);
+
+    let server = ServerOneConn::new_fn_with_conf(0, conf, move |_, req, mut resp| {
+        if req.headers.path() == "/index.html" {
+            tx.lock().unwrap().send(resp).unwrap();
+        } else {
+            resp.send_found_200_plain_text("other")?;
+        }
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    // pushed response is kept open
+    let mut pushed = resp
+        .push_promise(push_request_headers("/style.css"))
+        .expect("push");
+    resp.send_found_200_plain_text("index").expect("send");
+
+    let (frame, _, _) = tester.recv_frame_push_promise_decode();
+    assert_eq!(2, frame.promised_stream_id);
+    tester.recv_message(1);
+
+    // pushed stream does not occupy a slot of client streams
+    let message = tester.get(3, "/other");
+    assert_eq!(200, message.headers.status());
+
+    pushed
+        .send_found_200_plain_text("body {}")
+        .expect("send pushed");
+    tester.recv_message(2);
+}
+
+#[test]
+fn max_concurrent_streams_default_unlimited() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    assert_eq!(
+        DEFAULT_SETTINGS.max_concurrent_streams,
+        tester.peer_settings.max_concurrent_streams
+    );
+
+    for stream_id in (1..20).step_by(2) {
+        start_echo_stream(&mut tester, stream_id);
+    }
+
+    assert_eq!(10, server.dump_state().streams.len());
+}
+
+fn push_request_headers(path: &str) -> Headers {
+    let mut headers = Headers::new_get(path);
+    headers.add(":scheme", "http");
+    headers
+}
+
+#[test]
+fn push_promise() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        tx.lock().unwrap().send(resp).unwrap();
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    let mut push_headers = push_request_headers("/style.css");
+    // does not fit into single frame
+    push_headers.add("x-large", "a".repeat(20000));
+    let mut pushed = resp.push_promise(push_headers).expect("push");
+
+    let (frame, headers, cont_count) = tester.recv_frame_push_promise_decode();
+    assert_eq!(1, frame.stream_id);
+    assert_eq!(2, frame.promised_stream_id);
+    assert!(cont_count > 0);
+    assert_eq!("GET", headers.method());
+    assert_eq!("/style.css", headers.path());
+
+    // pushed response cannot push
+    match pushed.push_promise(push_request_headers("/a.css")) {
+        Err(Error::PushPromiseOnPushedStream) => {}
+        r => panic!("expecting PushPromiseOnPushedStream, got: {:?}", r.err()),
+    }
+
+    pushed
+        .send_found_200_plain_text("body {}")
+        .expect("send pushed");
+    let message = tester.recv_message(2);
+    assert_eq!(200, message.headers.status());
+    assert_eq!(&b"body {}"[..], &message.body.get_bytes()[..]);
+
+    resp.send_found_200_plain_text("index").expect("send");
+    let message = tester.recv_message(1);
+    assert_eq!(&b"index"[..], &message.body.get_bytes()[..]);
+
+    // cannot push after response is finished
+    assert!(resp
+        .push_promise(push_request_headers("/late.css"))
+        .is_err());
+
+    assert_eq!(0, server.dump_state().streams.len());
+}
+
+#[test]
+fn push_promise_parent_closed() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        tx.lock().unwrap().send(resp).unwrap();
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    tester.send_rst(1, ErrorCode::Cancel);
+    // wait for the reset to be processed
+    tester.send_recv_settings(SettingsFrame::new());
+
+    match resp.push_promise(push_request_headers("/style.css")) {
+        Err(Error::PushPromiseOnClosedStream(1)) => {}
+        r => panic!("expecting PushPromiseOnClosedStream, got: {:?}", r.err()),
+    }
+}
+
+#[test]
+fn push_promise_after_goaway() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        tx.lock().unwrap().send(resp).unwrap();
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    tester.send_goaway(1);
+    tester.send_recv_settings(SettingsFrame::new());
+
+    match resp.push_promise(push_request_headers("/style.css")) {
+        Err(Error::PushPromiseAfterGoaway) => {}
+        r => panic!("expecting PushPromiseAfterGoaway, got: {:?}", r.err()),
+    }
+
+    // parent response can still be completed
+    resp.send_found_200_plain_text("index").expect("send");
+    assert_eq!(&b"index"[..], &tester.recv_message(1).body.get_bytes()[..]);
+}
+
+#[test]
+fn push_promise_disabled() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        tx.lock().unwrap().send(resp).unwrap();
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+    tester.send_recv_settings(SettingsFrame::from_settings(vec![HttpSetting::EnablePush(
+        false,
+    )]));
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    match resp.push_promise(push_request_headers("/style.css")) {
+        Err(Error::PushDisabled) => {}
+        r => panic!("expecting PushDisabled, got: {:?}", r.err()),
+    }
+
+    resp.send_found_200_plain_text("index").expect("send");
+    let message = tester.recv_message(1);
+    assert_eq!(&b"index"[..], &message.body.get_bytes()[..]);
+}
+
+#[test]
+fn push_promise_concurrency_limit() {
+    init_logger();
+
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let server = ServerOneConn::new_fn(0, move |_, _req, resp| {
+        tx.lock().unwrap().send(resp).unwrap();
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+    tester.send_recv_settings(SettingsFrame::from_settings(vec![
+        HttpSetting::MaxConcurrentStreams(1),
+    ]));
+
+    tester.send_get(1, "/index.html");
+    let mut resp = rx.recv().unwrap();
+
+    let mut pushed = resp
+        .push_promise(push_request_headers("/a.css"))
+        .expect("push");
+    match resp.push_promise(push_request_headers("/b.css")) {
+        Err(Error::PushConcurrencyLimit(1)) => {}
+        r => panic!("expecting PushConcurrencyLimit, got: {:?}", r.err()),
+    }
+
+    let (frame, headers, _) = tester.recv_frame_push_promise_decode();
+    assert_eq!(2, frame.promised_stream_id);
+    assert_eq!("/a.css", headers.path());
+
+    pushed.send_found_200_plain_text("a").expect("send pushed");
+    assert_eq!(&b"a"[..], &tester.recv_message(2).body.get_bytes()[..]);
+
+    // only parent stream is left
+    assert_eq!(1, server.dump_state().streams.len());
+
+    // slot is free after pushed stream completes
+    let mut pushed = resp
+        .push_promise(push_request_headers("/b.css"))
+        .expect("push");
+    let (frame, _, _) = tester.recv_frame_push_promise_decode();
+    assert_eq!(4, frame.promised_stream_id);
+
+    pushed.send_found_200_plain_text("b").expect("send pushed");
+    assert_eq!(&b"b"[..], &tester.recv_message(4).body.get_bytes()[..]);
+
+    resp.send_found_200_plain_text("index").expect("send");
+    tester.recv_message(1);
+}
+
+#[test]
+fn push_promise_http_1() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn_with_conf(0, http_1_conf(), |_, _req, mut resp| {
+        match resp.push_promise(push_request_headers("/style.css")) {
+            Err(Error::PushNotSupported) => resp.send_found_200_plain_text("not pushed")?,
+            r => panic!("expecting PushNotSupported, got: {:?}", r.err()),
+        }
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    tcp_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert!(
+        read.starts_with(b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+    assert_eq!(1, count_subslice(&read, b"not pushed"));
+}
+
+fn async_server<H: AsyncServerHandler>(handler: H) -> ServerOneConn {
+    let handler = AsyncServerHandlerAdapter::new(handler);
+    ServerOneConn::new_fn(0, move |context, req, resp| {
+        handler.start_request(context, req, resp)
+    })
+}
+
+#[test]
+fn async_handler() {
+    init_logger();
+
+    let server = async_server(|_, headers: Headers, body: HttpStreamAfterHeaders| {
+        Response::new(async move {
+            let body: Vec<Bytes> = body.filter_data().try_collect().await?;
+            let mut content = headers.path().as_bytes().to_vec();
+            for b in body {
+                content.extend_from_slice(&b);
+            }
+            Ok((
+                Headers::ok_200(),
+                HttpStreamAfterHeaders::once_bytes(content),
+            ))
+        })
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/echo");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
+    tester.send_data(1, b"abcd", true);
+
+    let message = tester.recv_message(1);
+    assert_eq!(200, message.headers.status());
+    assert_eq!(&b"/echoabcd"[..], &message.body.get_bytes()[..]);
+}
+
+#[test]
+fn async_handler_panic() {
+    init_logger();
+
+    let server = async_server(|_, _, _| {
+        Response::new(async {
+            if true {
+                panic!("told to panic");
+            }
+            Ok((Headers::ok_200(), HttpStreamAfterHeaders::empty()))
+        })
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let message = tester.get(1, "/panic");
+    assert_eq!(500, message.headers.status());
+}
+
+#[test]
+fn async_handler_error() {
+    init_logger();
+
+    let server = async_server(|_, _, _| {
+        Response::new(async { Err(httpbis::Error::CodeError(ErrorCode::RefusedStream)) })
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/error");
+    tester.recv_rst_frame_check(1, ErrorCode::RefusedStream);
+}
+
+#[test]
+fn async_handler_cancelled_on_reset() {
+    init_logger();
+
+    struct DropGuard(Mutex<mpsc::Sender<()>>);
+
+    impl Drop for DropGuard {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().send(()).unwrap();
+        }
+    }
+
+    let (dropped_tx, dropped_rx) = mpsc::channel();
+    let dropped_tx = Mutex::new(dropped_tx);
+
+    let server = async_server(move |_, _, _| {
+        let guard = DropGuard(Mutex::new(dropped_tx.lock().unwrap().clone()));
+        Response::new(async move {
+            let _guard = guard;
+            futures::future::pending().await
+        })
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_get(1, "/forever");
+    tester.send_rst(1, ErrorCode::Cancel);
+
+    dropped_rx
+        .recv_timeout(Duration::from_secs(10))
+        .expect("handler future dropped");
+}
+
+struct TagLayer(&'static str);
+
+struct TagHandler {
+    tag: &'static str,
+    inner: Arc<dyn ServerHandler>,
+}
+
+impl ServerHandler for TagHandler {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        mut req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> Result<()> {
+        let tag = self.tag;
+        req.headers.add("x-request-tag", tag);
+        resp.on_send_headers(move |headers| headers.add("x-response-tag", tag));
+        self.inner.start_request(context, req, resp)
+    }
+}
+
+impl ServerHandlerLayer for TagLayer {
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> {
+        Arc::new(TagHandler { tag: self.0, inner })
+    }
+}
+
+#[test]
+fn layers() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.service.set_service_fn("/", |_, req, mut resp| {
+        let tags: Vec<&str> = req
+            .headers
+            .iter()
+            .filter(|h| h.name() == "x-request-tag")
+            .map(|h| std::str::from_utf8(h.value()).unwrap())
+            .collect();
+        resp.send_found_200_plain_text(&tags.join(","))?;
+        Ok(())
+    });
+    server.layer(TagLayer("outer"));
+    server.layer(TagLayer("inner"));
+    server.layer(ServerHandlerLoggingLayer);
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let r = tester.get(1, "/");
+    assert_eq!(200, r.headers.status());
+    let response_tags: Vec<&[u8]> = r
+        .headers
+        .iter()
+        .filter(|h| h.name() == "x-response-tag")
+        .map(|h| h.value())
+        .collect();
+    assert_eq!(vec![&b"outer"[..], &b"inner"[..]], response_tags);
+    assert_eq!(&b"outer,inner"[..], &r.body.get_bytes()[..]);
+}
+
+#[test]
+fn timeout_layer() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    // keep responses alive, dropped response is replied with an error
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    server
+        .service
+        .set_service_fn("/slow", move |_, _req, resp| {
+            responses.lock().unwrap().push(resp);
+            Ok(())
+        });
+    server.service.set_service_fn("/fast", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("fast")?;
+        Ok(())
+    });
+    server.layer(ServerHandlerTimeoutLayer::new(Duration::from_millis(100)));
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let r = tester.get(1, "/fast");
+    assert_eq!(&b"fast"[..], &r.body.get_bytes()[..]);
+
+    tester.send_get(3, "/slow");
+    tester.recv_rst_frame_check(3, ErrorCode::Cancel);
+}
+
+#[test]
+fn paths_params_and_methods() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server
+        .service
+        .set_method_service_fn("GET", "/users/{id}", |_, req, mut resp| {
+            let params = req.extensions.get::<PathParams>().unwrap();
+            resp.send_found_200_plain_text(&format!("user {}", params.get("id").unwrap()))?;
+            Ok(())
+        });
+    server
+        .service
+        .set_method_service_fn("DELETE", "/users/{id}", |_, _req, mut resp| {
+            resp.send_found_200_plain_text("deleted")?;
+            Ok(())
+        });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let r = tester.get(1, "/users/17?verbose=1");
+    assert_eq!(200, r.headers.status());
+    assert_eq!(&b"user 17"[..], &r.body.get_bytes()[..]);
+
+    let mut headers = Headers::new_post("/users/17");
+    headers.add(":scheme", "http");
+    tester.send_headers(3, headers, true);
+    let r = tester.recv_message(3);
+    assert_eq!(405, r.headers.status());
+    assert_eq!("DELETE, GET", r.headers.get("allow"));
+
+    let r = tester.get(5, "/other");
+    assert_eq!(404, r.headers.status());
+}
+
+fn body_limit_server() -> Server {
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.conf.http1 = Some(true);
+    server.conf.max_request_body_size = Some(10);
+    // keep responses alive, dropped response is replied with an error
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    server
+        .service
+        .set_service_fn("/slow", move |_, _req, resp| {
+            responses.lock().unwrap().push(resp);
+            Ok(())
+        });
+    server.service.set_service_fn("/echo", |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+    server
+        .service
+        .set_service_fn("/upload", |_, req, mut resp| {
+            resp.send_headers(Headers::ok_200())?;
+            resp.pull_from_stream(req.make_stream())?;
+            Ok(())
+        });
+    server.service.set_max_request_body_size("/upload", 100);
+    server.build().expect("server")
+}
+
+fn post_headers(path: &str, content_length: Option<u64>) -> Headers {
+    let mut headers = Headers::new_post(path);
+    headers.add(":scheme", "http");
+    if let Some(content_length) = content_length {
+        headers.add("content-length", format!("{}", content_length));
+    }
+    headers
+}
+
+#[test]
+fn request_body_limit_content_length() {
+    init_logger();
+
+    let server = body_limit_server();
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, post_headers("/echo", Some(20)), false);
+    let headers = tester.recv_frame_headers_check(1, true);
+    assert_eq!(413, headers.status());
+    tester.recv_rst_frame_check(1, ErrorCode::NoError);
+
+    // per-route override
+    tester.send_headers(3, post_headers("/upload", Some(20)), false);
+    tester.send_data(3, &[1; 20], true);
+    let r = tester.recv_message(3);
+    assert_eq!(200, r.headers.status());
+    assert_eq!(&[1; 20][..], &r.body.get_bytes()[..]);
+}
+
+#[test]
+fn request_body_limit_data() {
+    init_logger();
+
+    let server = body_limit_server();
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // response headers are not sent yet
+    tester.send_headers(1, post_headers("/slow", None), false);
+    tester.send_data(1, &[1; 8], false);
+    tester.send_data(1, &[2; 8], false);
+    let headers = tester.recv_frame_headers_check(1, true);
+    assert_eq!(413, headers.status());
+    tester.recv_rst_frame_check(1, ErrorCode::NoError);
+
+    // response headers are sent
+    tester.send_headers(3, post_headers("/echo", None), false);
+    tester.send_data(3, &[1; 8], false);
+    let headers = tester.recv_frame_headers_check(3, false);
+    assert_eq!(200, headers.status());
+    assert_eq!(&[1; 8][..], &tester.recv_frame_data_check(3, false)[..]);
+    tester.send_data(3, &[2; 8], false);
+    tester.recv_rst_frame_check(3, ErrorCode::Cancel);
+}
+
+#[test]
+fn request_body_limit_conn_window() {
+    init_logger();
+
+    let server = body_limit_server();
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_headers(1, post_headers("/slow", None), false);
+    tester.send_data(1, &[1; 16], false);
+    assert_eq!(413, tester.recv_frame_headers_check(1, true).status());
+    tester.recv_rst_frame_check(1, ErrorCode::NoError);
+
+    // data for the reset stream still counts against connection window
+    for _ in 0..3 {
+        tester.send_data(1, &[2; 16384], false);
+    }
+    tester.send_frame(PingFrame::new());
+
+    let mut conn_window_updates = 0;
+    loop {
+        match tester.fn_recv_frame_no_check_ack() {
+            HttpFrame::WindowUpdate(ref f) if f.stream_id == 0 => conn_window_updates += 1,
+            HttpFrame::Ping(ref ping) if ping.is_ack() => break,
+            HttpFrame::RstStream(..) => {}
+            f => panic!("unexpected frame: {:?}", f),
+        }
+    }
+    assert_eq!(1, conn_window_updates);
+}
+
+#[test]
+fn request_body_limit_http_1() {
+    init_logger();
+
+    let server = body_limit_server();
+
+    let mut tcp_stream =
+        TcpStream::connect((BIND_HOST, server.local_addr().port().unwrap())).expect("connect");
+    tcp_stream
+        .write_all(
+            b"POST /slow HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\
+              8\r\n12345678\r\n8\r\n12345678\r\n0\r\n\r\n",
+        )
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert_eq!(
+        1,
+        count_subslice(&read, b"HTTP/1.1 413 Payload Too Large\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+}
+
+#[test]
+fn handshake_timeout() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.handshake_timeout = Some(Duration::from_millis(100));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("unreachable")?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    // partial preface
+    tcp_stream.write_all(b"PRI * HTTP/2.0\r\n").expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert!(read.is_empty(), "{:?}", BsDebug(&read));
+}
+
+#[test]
+fn handshake_timeout_no_settings() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.handshake_timeout = Some(Duration::from_millis(100));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("unreachable")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.recv_frame_settings_set();
+    tester.recv_eof();
+}
+
+#[test]
+fn header_block_timeout() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.header_block_timeout = Some(Duration::from_millis(100));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("unreachable")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // HEADERS without END_HEADERS, CONTINUATION never comes
+    let fragment = tester
+        .encoder
+        .encode_for_test(vec![(&b":method"[..], &b"GET"[..])].into_iter());
+    tester.send_frame(HeadersFrame::new_conv(fragment, 1));
+
+    tester.recv_eof();
+}
+
+#[test]
+fn idle_timeout() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.idle_timeout = Some(Duration::from_millis(200));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let r = tester.get(1, "/");
+    assert_eq!(&b"ok"[..], &r.body.get_bytes()[..]);
+
+    tester.recv_goaway_frame_check(ErrorCode::NoError);
+    tester.recv_eof();
+}
+
+#[test]
+fn idle_timeout_http_1() {
+    init_logger();
+
+    let mut conf = http_1_conf();
+    conf.idle_timeout = Some(Duration::from_millis(200));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    tcp_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .expect("write");
+
+    // connection is closed after keep-alive response
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert_eq!(
+        1,
+        count_subslice(&read, b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+}
+
+#[test]
+fn header_block_timeout_http_1() {
+    init_logger();
+
+    let mut conf = http_1_conf();
+    conf.header_block_timeout = Some(Duration::from_millis(100));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tcp_stream = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    // second request head is never completed
+    tcp_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\n")
+        .expect("write");
+
+    let mut read = Vec::new();
+    tcp_stream.read_to_end(&mut read).expect("read");
+    assert!(
+        read.starts_with(b"HTTP/1.1 200 OK\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+    assert_eq!(
+        1,
+        count_subslice(&read, b"HTTP/1.1 408 Request Timeout\r\n"),
+        "{:?}",
+        BsDebug(&read)
+    );
+}
+
+#[test]
+fn ping_timeout() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.ping_interval = Some(Duration::from_millis(100));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    // acknowledged ping keeps connection alive
+    let ping = recv_ping(&mut tester);
+    assert!(!ping.is_ack());
+    tester.send_frame(PingFrame::new_ack(ping.opaque_data()));
+
+    let ping = recv_ping(&mut tester);
+    assert!(!ping.is_ack());
+
+    // second ping is not acknowledged
+    tester.recv_eof();
+}
+
+#[test]
+fn static_files() {
+    init_logger();
+
+    let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+    let root = tempdir.path().join("root");
+    std::fs::create_dir(&root).unwrap();
+    let content: Vec<u8> = (0..100u8).collect();
+    std::fs::write(root.join("data.bin"), &content).unwrap();
+    std::fs::write(root.join("page.html"), "<p>plain</p>").unwrap();
+    std::fs::write(root.join("page.html.gz"), "gzipped").unwrap();
+    std::fs::write(tempdir.path().join("secret"), "secret").unwrap();
+
+    let mut files = StaticFilesHandler::new(root);
+    files.set_path_param("path");
+    files.set_precompressed(true);
+    files.set_chunk_size(30);
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server
+        .service
+        .set_service("/static/{*path}", Arc::new(files));
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let get = |tester: &mut HttpConnTester, stream_id, path: &str, extra: &[(&str, &str)]| {
+        let mut headers = Headers::new_get(path.to_owned());
+        headers.add(":scheme", "http");
+        for &(n, v) in extra {
+            headers.add(n.to_owned(), v.to_owned());
+        }
+        tester.send_headers(stream_id, headers, true);
+        tester.recv_message(stream_id)
+    };
+
+    let r = get(&mut tester, 1, "/static/data.bin", &[]);
+    assert_eq!(200, r.headers.status());
+    assert_eq!("100", r.headers.get("content-length"));
+    assert_eq!("application/octet-stream", r.headers.get("content-type"));
+    assert_eq!(&content[..], &r.body.get_bytes()[..]);
+    let etag = r.headers.get("etag").to_owned();
+    let last_modified = r.headers.get("last-modified").to_owned();
+
+    let r = get(
+        &mut tester,
+        3,
+        "/static/data.bin",
+        &[("if-none-match", &etag)],
+    );
+    assert_eq!(304, r.headers.status());
+    assert!(r.body.get_bytes().is_empty());
+
+    let r = get(
+        &mut tester,
+        5,
+        "/static/data.bin",
+        &[("if-modified-since", &last_modified)],
+    );
+    assert_eq!(304, r.headers.status());
+
+    let r = get(
+        &mut tester,
+        7,
+        "/static/data.bin",
+        &[("range", "bytes=10-19")],
+    );
+    assert_eq!(206, r.headers.status());
+    assert_eq!("bytes 10-19/100", r.headers.get("content-range"));
+    assert_eq!(&content[10..20], &r.body.get_bytes()[..]);
+
+    let r = get(
+        &mut tester,
+        9,
+        "/static/data.bin",
+        &[("range", "bytes=0-1, -2")],
+    );
+    assert_eq!(206, r.headers.status());
+    let content_type = r.headers.get("content-type");
+    assert!(content_type.starts_with("multipart/byteranges; boundary="));
+    let body = r.body.get_bytes();
+    assert_eq!(
+        r.headers.get("content-length"),
+        body.len().to_string().as_str()
+    );
+    assert_eq!(
+        1,
+        count_subslice(&body, b"content-range: bytes 0-1/100\r\n\r\n\x00\x01")
+    );
+    assert_eq!(
+        1,
+        count_subslice(&body, b"content-range: bytes 98-99/100\r\n\r\n\x62\x63")
+    );
+
+    let r = get(
+        &mut tester,
+        11,
+        "/static/data.bin",
+        &[("range", "bytes=100-")],
+    );
+    assert_eq!(416, r.headers.status());
+    assert_eq!("bytes */100", r.headers.get("content-range"));
+
+    let mut headers = Headers::new();
+    headers.add(":method", "HEAD");
+    headers.add(":path", "/static/data.bin");
+    headers.add(":scheme", "http");
+    tester.send_headers(13, headers, true);
+    let r = tester.recv_message(13);
+    assert_eq!(200, r.headers.status());
+    assert_eq!("100", r.headers.get("content-length"));
+    assert!(r.body.get_bytes().is_empty());
+
+    let r = get(
+        &mut tester,
+        15,
+        "/static/page.html",
+        &[("accept-encoding", "gzip")],
+    );
+    assert_eq!(200, r.headers.status());
+    assert_eq!("gzip", r.headers.get("content-encoding"));
+    assert_eq!("text/html; charset=utf-8", r.headers.get("content-type"));
+    assert_eq!(&b"gzipped"[..], &r.body.get_bytes()[..]);
+
+    let r = get(&mut tester, 17, "/static/page.html", &[]);
+    assert_eq!(&b"<p>plain</p>"[..], &r.body.get_bytes()[..]);
+
+    let r = get(&mut tester, 19, "/static/%2e%2e/secret", &[]);
+    assert_eq!(404, r.headers.status());
+
+    let r = get(&mut tester, 21, "/static/missing", &[]);
+    assert_eq!(404, r.headers.status());
+}
+
+#[cfg(unix)]
+#[test]
+fn multiple_listeners() {
+    init_logger();
+
+    let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+    let socket_path = tempdir.path().join("test_socket");
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server
+        .add_addrs((BIND_HOST, 0), ServerTlsOption::Plain)
+        .unwrap();
+    server.add_listener(
+        SocketAddrUnix::from(socket_path.as_path()),
+        ServerTlsOption::Plain,
+    );
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    assert_eq!(3, server.local_addrs().len());
+    assert_eq!(server.local_addr(), &server.local_addrs()[0]);
+    assert_eq!(
+        AnySocketAddr::Unix(SocketAddrUnix::from(socket_path.as_path())),
+        server.local_addrs()[2]
+    );
+
+    let mut clients = Vec::new();
+    for addr in &server.local_addrs()[..2] {
+        let port = addr.port().unwrap();
+        assert_ne!(0, port);
+        clients.push(Client::new_plain(BIND_HOST, port, Default::default()).expect("client"));
+    }
+    clients.push(
+        Client::new_plain_unix(socket_path.to_str().unwrap(), Default::default()).expect("client"),
+    );
+
+    let mut rt = Runtime::new().unwrap();
+    for client in &clients {
+        let r = rt
+            .block_on(client.start_get("/", "localhost").collect())
+            .expect("get");
+        assert_eq!(200, r.headers.status());
+        assert_eq!(&b"ok"[..], &r.body.get_bytes()[..]);
+    }
+
+    let state = rt.block_on(server.dump_state()).expect("state");
+    assert_eq!(3, state.conns.len());
+}
+
+#[cfg(unix)]
+#[test]
+fn inherited_listeners() {
+    init_logger();
+
+    let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+    let socket_path = tempdir.path().join("test_socket");
+
+    let tcp = std::net::TcpListener::bind((BIND_HOST, 0)).unwrap();
+    let port = tcp.local_addr().unwrap().port();
+    let unix = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+
+    let mut server = ServerBuilder::new_plain();
+    server.add_listener(tcp, ServerTlsOption::Plain);
+    server.add_listener(unix, ServerTlsOption::Plain);
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    assert_eq!(port, server.local_addr().port().unwrap());
+
+    let clients = vec![
+        Client::new_plain(BIND_HOST, port, Default::default()).expect("client"),
+        Client::new_plain_unix(socket_path.to_str().unwrap(), Default::default()).expect("client"),
+    ];
+
+    let mut rt = Runtime::new().unwrap();
+    for client in &clients {
+        let r = rt
+            .block_on(client.start_get("/", "localhost").collect())
+            .expect("get");
+        assert_eq!(&b"ok"[..], &r.body.get_bytes()[..]);
+    }
+
+    drop(server);
+    // socket file is owned by whoever bound the socket
+    assert!(socket_path.exists());
+}
+
+#[test]
+fn conn_event_loops() {
+    init_logger();
+
+    let runtimes: Vec<Runtime> = (0..3).map(|_| Runtime::new().unwrap()).collect();
+
+    for &selection in &[
+        ServerConnLoopSelection::RoundRobin,
+        ServerConnLoopSelection::LeastConnections,
+        ServerConnLoopSelection::Random,
+    ] {
+        let (hold_tx, hold_rx) = mpsc::channel::<oneshot::Sender<()>>();
+        let hold_tx = Mutex::new(hold_tx);
+
+        let mut server = ServerBuilder::new_plain();
+        server.set_addr((BIND_HOST, 0)).unwrap();
+        server.conf.conn_loop_selection = Some(selection);
+        server.conn_event_loops = runtimes.iter().map(|rt| rt.handle().clone()).collect();
+        server.service.set_service_fn("/", move |_, req, mut resp| {
+            if req.headers.path() == "/hold" {
+                // keep the stream open until the test drops the sender
+                let (tx, rx) = oneshot::channel();
+                hold_tx.lock().unwrap().send(tx).unwrap();
+                resp.send_headers(Headers::ok_200())?;
+                resp.pull_bytes_from_stream(stream::once(async move {
+                    rx.await.ok();
+                    Ok(Bytes::from_static(b"done"))
+                }))?;
+                return Ok(());
+            }
+            resp.send_found_200_plain_text("ok")?;
+            Ok(())
+        });
+        let server = server.build().expect("server");
+        let port = server.local_addr().port().unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+        let clients: Vec<Client> = (0..6)
+            .map(|_| {
+                let client = Client::new_plain(BIND_HOST, port, Default::default()).unwrap();
+                let r = rt
+                    .block_on(client.start_get("/", "localhost").collect())
+                    .expect("get");
+                assert_eq!(200, r.headers.status());
+                client
+            })
+            .collect();
+
+        let state = rt.block_on(server.dump_state()).expect("state");
+        assert_eq!(6, state.conns.len());
+        let conns: Vec<usize> = state.conn_loops.iter().map(|l| l.conns).collect();
+        assert_eq!(6, conns.iter().sum::<usize>(), "{:?}", selection);
+        if selection != ServerConnLoopSelection::Random {
+            assert_eq!(vec![2, 2, 2], conns, "{:?}", selection);
+        }
+
+        let held = clients[0].start_get("/hold", "localhost").collect();
+        let done_tx = hold_rx.recv().unwrap();
+        let state = rt.block_on(server.dump_state()).expect("state");
+        assert_eq!(1, state.conn_loops.iter().map(|l| l.streams).sum::<usize>());
+
+        drop(done_tx);
+        let r = rt.block_on(held).expect("get");
+        assert_eq!(&b"done"[..], &r.body.get_bytes()[..]);
+
+        drop(clients);
+        // connections are closed asynchronously
+        for _ in 0..500 {
+            let state = rt.block_on(server.dump_state()).expect("state");
+            if state
+                .conn_loops
+                .iter()
+                .all(|l| l.conns == 0 && l.streams == 0)
+            {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let state = rt.block_on(server.dump_state()).expect("state");
+        assert!(
+            state
+                .conn_loops
+                .iter()
+                .all(|l| l.conns == 0 && l.streams == 0),
+            "{:?}",
+            state.conn_loops
+        );
+    }
+}
+
+#[test]
+fn proxy_protocol() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.proxy_protocol = Some(true);
+    conf.handshake_timeout = Some(Duration::from_millis(300));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |context, _req, mut resp| {
+        let header = context.conn_info().proxy_header().expect("header");
+        let authority = header
+            .tlv(ProxyTlv::AUTHORITY)
+            .map(|v| String::from_utf8(v.to_vec()).unwrap());
+        resp.send_found_200_plain_text(&format!("{} {:?}", header.source.unwrap(), authority))?;
+        Ok(())
+    });
+
+    let connect = |header: &[u8]| {
+        let mut tcp = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+        tcp.write_all(header).unwrap();
+        HttpConnTester::with_tcp(tcp)
+    };
+
+    let mut tester = connect(b"PROXY TCP4 192.0.2.1 192.0.2.2 1111 443\r\n");
+    tester.send_preface();
+    tester.settings_xchg();
+    let r = tester.get(1, "/");
+    assert_eq!(&b"192.0.2.1:1111 None"[..], &r.body.get_bytes()[..]);
+    assert_eq!(
+        AnySocketAddr::Inet("192.0.2.1:1111".parse().unwrap()),
+        server.dump_state().peer_addr
+    );
+}
+
+#[test]
+fn proxy_protocol_v2() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.proxy_protocol = Some(true);
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |context, _req, mut resp| {
+        let header = context.conn_info().proxy_header().expect("header");
+        let authority = header
+            .tlv(ProxyTlv::AUTHORITY)
+            .map(|v| String::from_utf8(v.to_vec()).unwrap());
+        resp.send_found_200_plain_text(&format!("{} {:?}", header.source.unwrap(), authority))?;
+        Ok(())
+    });
+
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x13".to_vec();
+    header.extend_from_slice(&[192, 0, 2, 1, 192, 0, 2, 2, 0x04, 0x57, 0x01, 0xbb]);
+    header.extend_from_slice(&[0x02, 0x00, 0x04]);
+    header.extend_from_slice(b"a.ex");
+
+    let mut tcp = TcpStream::connect((BIND_HOST, server.port())).expect("connect");
+    // header and preface in one write
+    header.extend_from_slice(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n");
+    tcp.write_all(&header).unwrap();
+    let mut tester = HttpConnTester::with_tcp(tcp);
+    tester.settings_xchg();
+    let r = tester.get(1, "/");
+    assert_eq!(
+        &b"192.0.2.1:1111 Some(\"a.ex\")"[..],
+        &r.body.get_bytes()[..]
+    );
+}
+
+#[test]
+fn proxy_protocol_invalid() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server.conf.proxy_protocol = Some(true);
+    server.conf.handshake_timeout = Some(Duration::from_millis(300));
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let port = server.local_addr().port().unwrap();
+
+    // preface without header
+    let mut tester = HttpConnTester::connect(port);
+    tester.send_preface();
+    tester.recv_eof();
+
+    // incomplete header is rejected by timeout
+    let mut tcp = TcpStream::connect((BIND_HOST, port)).expect("connect");
+    tcp.write_all(b"PROXY TCP4 192.0.2.1").unwrap();
+    HttpConnTester::with_tcp(tcp).recv_eof();
+}
+
+#[test]
+fn conn_info() {
+    init_logger();
+
+    let (infos_tx, infos_rx) = mpsc::channel();
+    let infos_tx = Mutex::new(infos_tx);
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server
+        .service
+        .set_service_fn("/", move |context, req, mut resp| {
+            let info = context.conn_info();
+            infos_tx
+                .lock()
+                .unwrap()
+                .send((
+                    info.id(),
+                    info.peer_addr().clone(),
+                    info.is_tls(),
+                    info.tls_sni().map(str::to_owned),
+                    info.alpn_protocol().map(<[u8]>::to_vec),
+                    context.stream_id(),
+                    req.stream_id(),
+                ))
+                .unwrap();
+            resp.send_found_200_plain_text("ok")?;
+            Ok(())
+        });
+    let server = server.build().expect("server");
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+    tester.get(1, "/");
+    tester.get(3, "/");
+
+    let (id, peer_addr, tls, sni, alpn, context_stream_id, req_stream_id) =
+        infos_rx.recv().unwrap();
+    assert_eq!(AnySocketAddr::Inet(tester.local_addr()), peer_addr);
+    assert_eq!((false, None, None), (tls, sni, alpn));
+    assert_eq!((1, 1), (context_stream_id, req_stream_id));
+
+    let (id3, _, _, _, _, context_stream_id, _) = infos_rx.recv().unwrap();
+    assert_eq!(id, id3);
+    assert_eq!(3, context_stream_id);
+
+    let mut rt = Runtime::new().unwrap();
+    let state = rt.block_on(server.dump_state()).expect("state");
+    assert_eq!(id, state.single_conn().0);
+}
+
+#[test]
+fn accept_filter() {
+    init_logger();
+
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let accepted_copy = accepted.clone();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    // refuse every other connection
+    server.set_accept_filter(move |addr| {
+        assert!(addr.port().is_ok());
+        accepted_copy.fetch_add(1, Ordering::SeqCst) % 2 == 1
+    });
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let port = server.local_addr().port().unwrap();
+
+    HttpConnTester::connect(port).recv_eof();
+
+    let mut tester = HttpConnTester::connect(port);
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(&b"ok"[..], &tester.get(1, "/").body.get_bytes()[..]);
+
+    let mut rt = Runtime::new().unwrap();
+    let state = rt.block_on(server.dump_state()).expect("state");
+    assert_eq!(1, state.conns.len());
+    assert_eq!(1, state.refused.filter);
+    assert_eq!(2, accepted.load(Ordering::SeqCst));
+}
+
+#[test]
+fn max_conns() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server.conf.max_conns_per_ip = Some(1);
+    server.conf.max_conns = Some(1);
+    server.conf.refuse_with_goaway = Some(true);
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let port = server.local_addr().port().unwrap();
+
+    let mut tester = HttpConnTester::connect(port);
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(&b"ok"[..], &tester.get(1, "/").body.get_bytes()[..]);
+
+    let mut refused = HttpConnTester::connect(port);
+    refused.send_preface();
+    refused.recv_frame_settings_set();
+    let goaway = refused.recv_goaway_frame();
+    assert_eq!(ErrorCode::EnhanceYourCalm, goaway.error_code());
+    assert_eq!(0, goaway.last_stream_id());
+    refused.recv_eof();
+
+    let mut rt = Runtime::new().unwrap();
+    let state = rt.block_on(server.dump_state()).expect("state");
+    assert_eq!(1, state.conns.len());
+    assert_eq!(1, state.refused.max_conns);
+
+    // connection is accepted after the first one is closed
+    drop(tester);
+    for _ in 0..500 {
+        if rt
+            .block_on(server.dump_state())
+            .expect("state")
+            .conns
+            .is_empty()
+        {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let mut tester = HttpConnTester::connect(port);
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(&b"ok"[..], &tester.get(1, "/").body.get_bytes()[..]);
+}
+
+#[test]
+fn max_conns_per_ip() {
+    init_logger();
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server.conf.max_conns_per_ip = Some(2);
+    server.service.set_service_fn("/", |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let port = server.local_addr().port().unwrap();
+
+    let testers: Vec<HttpConnTester> = (0..2)
+        .map(|_| {
+            let mut tester = HttpConnTester::connect(port);
+            tester.send_preface();
+            tester.settings_xchg();
+            tester
+        })
+        .collect();
+
+    HttpConnTester::connect(port).recv_eof();
+
+    let mut rt = Runtime::new().unwrap();
+    let state = rt.block_on(server.dump_state()).expect("state");
+    assert_eq!(2, state.conns.len());
+    assert_eq!(1, state.refused.max_conns_per_ip);
+    assert_eq!(0, state.refused.max_conns);
+    drop(testers);
+}
+
+#[test]
+fn max_conns_per_ip_proxy_protocol() {
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server.conf.max_conns_per_ip = Some(2);
+    server.conf.proxy_protocol = Some(true);
+    match server.build() {
+        Err(httpbis::Error::MaxConnsPerIpWithProxyProtocol) => {}
+        r => panic!("{:?}", r.map(|_| ())),
+    }
+}
+
+fn frame_rate_limit(count: u32) -> ServerFrameRateLimit {
+    ServerFrameRateLimit {
+        count,
+        period: Duration::from_secs(60),
+    }
+}
+
+/// Skip responses and acks until `GOAWAY`.
+fn recv_goaway_skip_other(tester: &mut HttpConnTester) -> ErrorCode {
+    loop {
+        match tester.recv_frame() {
+            HttpFrame::Goaway(goaway) => return goaway.error_code(),
+            f => debug!("skipping frame: {:?}", f),
+        }
+    }
+}
+
+#[test]
+fn rapid_reset() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.max_reset_rate = Some(frame_rate_limit(10));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    for i in 0..11 {
+        let stream_id = 1 + i * 2;
+        tester.send_get(stream_id, "/");
+        tester.send_rst(stream_id, ErrorCode::Cancel);
+    }
+
+    assert_eq!(
+        ErrorCode::EnhanceYourCalm,
+        recv_goaway_skip_other(&mut tester)
+    );
+    tester.recv_eof();
+}
+
+#[test]
+fn ping_flood() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.max_control_frame_rate = Some(frame_rate_limit(5));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    // initial SETTINGS is counted too
+    tester.settings_xchg();
+
+    for _ in 0..4 {
+        tester.send_frame(PingFrame::new());
+        assert!(recv_ping(&mut tester).is_ack());
+    }
+
+    tester.send_frame(PingFrame::new());
+    assert_eq!(
+        ErrorCode::EnhanceYourCalm,
+        recv_goaway_skip_other(&mut tester)
+    );
+    tester.recv_eof();
+}
+
+#[test]
+fn empty_data_flood() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.max_empty_frame_rate = Some(frame_rate_limit(5));
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    let mut headers = Headers::new();
+    headers.add(":method", "POST");
+    headers.add(":path", "/");
+    headers.add(":scheme", "http");
+    tester.send_headers(1, headers, false);
+
+    for _ in 0..6 {
+        tester.send_data(1, b"", false);
+    }
+
+    assert_eq!(
+        ErrorCode::EnhanceYourCalm,
+        recv_goaway_skip_other(&mut tester)
+    );
+    tester.recv_eof();
+}
+
+#[test]
+fn header_list_too_large() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.max_header_list_size = Some(300);
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(300, tester.peer_settings.max_header_list_size);
+
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/");
+    headers.add(":scheme", "http");
+    headers.add("x-large", "a".repeat(300));
+    tester.send_headers(1, headers, true);
+
+    let headers = tester.recv_frame_headers_check(1, true);
+    assert_eq!(431, headers.status());
+
+    // decoder state is kept, connection is usable
+    assert_eq!(200, tester.get(3, "/").headers.status());
+}
+
+#[test]
+fn header_limits_default() {
+    init_logger();
+
+    let server = ServerOneConn::new_fn(0, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+    assert_eq!(
+        CommonConf::DEFAULT_MAX_HEADER_LIST_SIZE,
+        tester.peer_settings.max_header_list_size
+    );
+
+    tester.send_frame(HeadersFrame::new(Bytes::new(), 1));
+    for _ in 0..=CommonConf::DEFAULT_MAX_CONTINUATION_FRAMES {
+        tester.send_frame(ContinuationFrame::new(Bytes::new(), 1));
+    }
+
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+#[test]
+fn header_list_too_large_max_concurrent_streams() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.max_concurrent_streams = Some(1);
+    conf.common.max_header_list_size = Some(300);
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, req, mut resp| {
+        resp.send_headers(Headers::ok_200())?;
+        resp.pull_from_stream(req.make_stream())?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    start_echo_stream(&mut tester, 1);
+
+    let mut headers = Headers::new();
+    headers.add(":method", "GET");
+    headers.add(":path", "/");
+    headers.add(":scheme", "http");
+    headers.add("x-large", "a".repeat(300));
+    tester.send_headers(3, headers, true);
+    tester.recv_rst_frame_check(3, ErrorCode::RefusedStream);
+
+    tester.send_data(1, b"abcd", true);
+    assert_eq!(&b"abcd"[..], &tester.recv_frame_data_tail(1)[..]);
+}
+
+#[test]
+fn continuation_flood() {
+    init_logger();
+
+    let mut conf = ServerConf::new();
+    conf.common.max_continuation_frames = Some(10);
+    let server = ServerOneConn::new_fn_with_conf(0, conf, |_, _req, mut resp| {
+        resp.send_found_200_plain_text("ok")?;
+        Ok(())
+    });
+
+    let mut tester = HttpConnTester::connect(server.port());
+    tester.send_preface();
+    tester.settings_xchg();
+
+    tester.send_frame(HeadersFrame::new(Bytes::new(), 1));
+    for _ in 0..11 {
+        tester.send_frame(ContinuationFrame::new(Bytes::new(), 1));
+    }
+
+    tester.recv_goaway_frame_check(ErrorCode::EnhanceYourCalm);
+    tester.recv_eof();
+}
+
+/// Server which reports cancellation of `/` requests, response is never sent.
+fn cancelled_server() -> (
+    Server,
+    mpsc::Receiver<(ServerCancelReason, ServerCancelReason)>,
+) {
+    let (tx, rx) = mpsc::channel();
+    let tx = Mutex::new(tx);
+
+    let mut server = ServerBuilder::new_plain();
+    server.set_addr((BIND_HOST, 0)).unwrap();
+    server.conf.http1 = Some(true);
+    server
+        .service
+        .set_service_fn("/", move |context, _req, resp| {
+            let tx = tx.lock().unwrap().clone();
+            let context_cancelled = context.cancelled();
+            context.loop_remote().spawn(async move {
+                let reason = resp.cancelled().await;
+                let context_reason = context_cancelled.await;
+                tx.send((reason, context_reason)).unwrap();
+                drop(resp);
+            });
+            Ok(())
+        });
+    (server.build().expect("server"), rx)
+}
+
+#[test]
+fn cancelled_by_rst() {
+    init_logger();
+
+    let (server, rx) = cancelled_server();
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+    tester.send_get(1, "/");
+    tester.send_rst(1, ErrorCode::Cancel);
+
+    match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+        (
+            ServerCancelReason::Reset(ErrorCode::Cancel),
+            ServerCancelReason::Reset(ErrorCode::Cancel),
+        ) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+}
+
+#[test]
+fn cancelled_by_conn_close() {
+    init_logger();
+
+    let (server, rx) = cancelled_server();
+
+    let mut tester = HttpConnTester::connect(server.local_addr().port().unwrap());
+    tester.send_preface();
+    tester.settings_xchg();
+    tester.send_get(1, "/");
+    drop(tester);
+
+    match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+        (ServerCancelReason::ConnDied(_), ServerCancelReason::ConnDied(_)) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+}
+
+#[test]
+fn cancelled_by_conn_close_http_1() {
+    init_logger();
+
+    let (server, rx) = cancelled_server();
+
+    let mut tcp_stream =
+        TcpStream::connect((BIND_HOST, server.local_addr().port().unwrap())).expect("connect");
+    tcp_stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .expect("write");
+    drop(tcp_stream);
+
+    match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+        (ServerCancelReason::ConnDied(_), ServerCancelReason::ConnDied(_)) => {}
+        r => panic!("unexpected: {:?}", r),
+    }
+}
+
+/// Server with compression layer, `/json` is sent in chunks followed by trailers.
+fn compression_server() -> Server {
+    let mut server = ServerBuilder::new_plain();
+    server.set_port(0);
+    server.service.set_service_fn("/json", |_, req, mut resp| {
+        let mut headers = Headers::ok_200();
+        headers.add("content-type", "application/json");
+        headers.add("content-length", "3000");
+        if req.headers.method() == "HEAD" {
+            resp.send_headers_end_of_stream(headers)?;
+            return Ok(());
+        }
+        resp.send_headers(headers)?;
+        for _ in 0..3 {
+            resp.send_data(Bytes::from(vec![b'a'; 1000]))?;
+        }
+        resp.send_trailers(Headers::from_vec(vec![Header::new("x-checksum", "1")]))?;
+        Ok(())
+    });
+    server
+        .service
+        .set_service_fn("/stream", |_, _req, mut resp| {
+            resp.send_headers(Headers::ok_200())?;
+            resp.pull_bytes_from_stream(stream::iter(
+                (0..3).map(|_| Ok(Bytes::from(vec![b'b'; 1000]))),
+            ))?;
+            Ok(())
+        });
+    server
+        .service
+        .set_service_fn("/small", |_, _req, mut resp| {
+            let mut headers = Headers::ok_200();
+            headers.add("content-length", "5");
+            resp.send_headers(headers)?;
+            resp.send_data_end_of_stream(Bytes::from("small"))?;
+            Ok(())
+        });
+    server.layer(ServerHandlerCompressionLayer::new());
+    server.build().expect("server")
+}
+
+fn get_accept_encoding(client: &Client, path: &str, accept_encoding: Option<&str>) -> Response {
+    let mut headers = Headers::new_get(path.to_owned());
+    headers.add(":authority", "localhost");
+    headers.add(":scheme", "http");
+    if let Some(accept_encoding) = accept_encoding {
+        headers.add("accept-encoding", accept_encoding.to_owned());
+    }
+    client.start_request_end_stream(headers, None, None)
+}
+
+#[test]
+fn compression_layer() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let server = compression_server();
+    let client = Client::new_plain(
+        BIND_HOST,
+        server.local_addr().port().unwrap(),
+        Default::default(),
+    )
+    .expect("client");
+
+    let r = rt
+        .block_on(get_accept_encoding(&client, "/json", Some("gzip")).collect())
+        .expect("get");
+    assert_eq!(Some("gzip"), r.headers.get_opt("content-encoding"));
+    assert_eq!(Some("accept-encoding"), r.headers.get_opt("vary"));
+    assert_eq!(None, r.headers.get_opt("content-length"));
+    assert!(r.body.len() < 3000);
+
+    let r = rt
+        .block_on(
+            get_accept_encoding(&client, "/json", Some("gzip"))
+                .decompress()
+                .collect(),
+        )
+        .expect("get");
+    assert_eq!(None, r.headers.get_opt("content-encoding"));
+    assert_eq!(Some("1"), r.headers.get_opt("x-checksum"));
+    assert_eq!(vec![b'a'; 3000], &r.body.get_bytes()[..]);
+
+    let r = rt
+        .block_on(
+            get_accept_encoding(&client, "/stream", Some("deflate, gzip;q=0.5"))
+                .decompress()
+                .collect(),
+        )
+        .expect("get");
+    assert_eq!(vec![b'b'; 3000], &r.body.get_bytes()[..]);
+
+    // client does not accept compression
+    let r = rt
+        .block_on(get_accept_encoding(&client, "/json", None).collect())
+        .expect("get");
+    assert_eq!(None, r.headers.get_opt("content-encoding"));
+    assert_eq!(Some("3000"), r.headers.get_opt("content-length"));
+    assert_eq!(Some("accept-encoding"), r.headers.get_opt("vary"));
+    assert_eq!(vec![b'a'; 3000], &r.body.get_bytes()[..]);
+
+    let r = rt
+        .block_on(get_accept_encoding(&client, "/json", Some("identity")).collect())
+        .expect("get");
+    assert_eq!(None, r.headers.get_opt("content-encoding"));
+    assert_eq!(Some("accept-encoding"), r.headers.get_opt("vary"));
+
+    // HEAD gets headers of compressed GET response, but no body
+    let headers = Headers::from_vec(vec![
+        Header::new(":method", "HEAD"),
+        Header::new(":path", "/json"),
+        Header::new(":authority", "localhost"),
+        Header::new(":scheme", "http"),
+        Header::new("accept-encoding", "gzip"),
+    ]);
+    let r = rt
+        .block_on(
+            client
+                .start_request_end_stream(headers, None, None)
+                .collect(),
+        )
+        .expect("head");
+    assert_eq!(Some("gzip"), r.headers.get_opt("content-encoding"));
+    assert_eq!(Some("accept-encoding"), r.headers.get_opt("vary"));
+    assert_eq!(None, r.headers.get_opt("content-length"));
+    assert!(r.body.get_bytes().is_empty());
+
+    // too small to compress
+    let r = rt
+        .block_on(get_accept_encoding(&client, "/small", Some("gzip")).collect())
+        .expect("get");
+    assert_eq!(None, r.headers.get_opt("content-encoding"));
+    assert_eq!(&b"small"[..], &r.body.get_bytes()[..]);
+}
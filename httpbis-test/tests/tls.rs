@@ -2,13 +2,21 @@
 
 use httpbis_test::*;
 
+use std::net::ToSocketAddrs;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use httpbis::SimpleHttpMessage;
 use httpbis::*;
 
 use httpbis::AnySocketAddr;
 
+use openssl::pkcs12::Pkcs12;
+use openssl::ssl::SslVerifyMode;
+use openssl::x509::X509;
 use tls_api::Certificate;
 use tls_api::TlsAcceptorBuilder as tls_api_TlsAcceptorBuilder;
 use tls_api::TlsConnector as tls_api_TlsConnector;
@@ -80,3 +88,445 @@ fn tls() {
     assert_eq!(200, resp.headers.status());
     assert_eq!(&b"hello"[..], resp.body.get_bytes());
 }
+
+#[test]
+fn tls_conf() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(test_tls_acceptor());
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("hello")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+
+    let client_keys = &httpbis_test::openssl_test_key_gen::keys().client;
+
+    let mut client = ClientBuilder::<TlsConnector>::new();
+    client
+        .set_addr((BIND_HOST, server.local_addr().port().unwrap()))
+        .expect("set_addr");
+    let mut tls_conf = ClientTlsConf::new();
+    tls_conf
+        .root_certificates
+        .push(client_keys.cert_der.clone());
+    tls_conf.sni_host = Some("localhost".to_owned());
+    client
+        .set_tls_conf(BIND_HOST, tls_conf)
+        .expect("set_tls_conf");
+    let client = client.build().expect("client");
+
+    let resp: SimpleHttpMessage = rt
+        .block_on(client.start_get("/hi", "localhost").collect())
+        .unwrap();
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+}
+
+/// Start server replying `hello` to `/`.
+fn hello_server<A: tls_api::TlsAcceptor>(acceptor: A) -> Server<A> {
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(acceptor);
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("hello")?;
+        Ok(())
+    });
+    server.build().expect("server")
+}
+
+fn get_hello<C: tls_api::TlsConnector>(
+    rt: &mut Runtime,
+    server: &Server<impl tls_api::TlsAcceptor>,
+    tls_conf: ClientTlsConf,
+) -> httpbis::Result<SimpleHttpMessage> {
+    let mut client = ClientBuilder::<C>::new();
+    client
+        .set_addr((BIND_HOST, server.local_addr().port().unwrap()))
+        .expect("set_addr");
+    client.set_tls_conf(BIND_HOST, tls_conf)?;
+    let client = client.build().expect("client");
+    rt.block_on(client.start_get("/hi", "localhost").collect())
+}
+
+#[test]
+fn tls_conf_verify_hostname() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let server = hello_server(test_tls_acceptor());
+    let client_keys = &httpbis_test::openssl_test_key_gen::keys().client;
+
+    // certificate is issued for `localhost`, not for the IP address
+    let mut tls_conf = ClientTlsConf::new();
+    tls_conf
+        .root_certificates
+        .push(client_keys.cert_der.clone());
+    assert!(get_hello::<TlsConnector>(&mut rt, &server, tls_conf).is_err());
+
+    let mut tls_conf = ClientTlsConf::new();
+    tls_conf
+        .root_certificates
+        .push(client_keys.cert_der.clone());
+    tls_conf.verify_hostname = Some(false);
+    let resp = get_hello::<TlsConnector>(&mut rt, &server, tls_conf).expect("get");
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+}
+
+/// Server requiring client certificate signed by the test certificate.
+fn client_cert_server() -> Server<tls_api_openssl::TlsAcceptor> {
+    let keys = httpbis_test::openssl_test_key_gen::keys();
+
+    let mut acceptor = tls_api_openssl::TlsAcceptorBuilder::from_pkcs12(
+        &keys.server.pkcs12,
+        &keys.server.pkcs12_password,
+    )
+    .unwrap();
+    acceptor
+        .underlying_mut()
+        .set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    acceptor
+        .underlying_mut()
+        .cert_store_mut()
+        .add_cert(X509::from_der(&keys.client.cert_der).unwrap())
+        .unwrap();
+    hello_server(acceptor.build().unwrap())
+}
+
+fn client_cert_tls_conf() -> ClientTlsConf {
+    let keys = httpbis_test::openssl_test_key_gen::keys();
+    let mut tls_conf = ClientTlsConf::new();
+    tls_conf
+        .root_certificates
+        .push(keys.client.cert_der.clone());
+    tls_conf.sni_host = Some("localhost".to_owned());
+    tls_conf
+}
+
+#[test]
+fn tls_conf_client_identity_openssl() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let keys = httpbis_test::openssl_test_key_gen::keys();
+    let server = client_cert_server();
+
+    // no client certificate
+    assert!(
+        get_hello::<tls_api_openssl::TlsConnector>(&mut rt, &server, client_cert_tls_conf())
+            .is_err()
+    );
+
+    let mut pkcs12 = client_cert_tls_conf();
+    pkcs12.identity = Some(ClientTlsIdentity::Pkcs12 {
+        der: keys.server.pkcs12.clone(),
+        password: keys.server.pkcs12_password.clone(),
+    });
+    let resp = get_hello::<tls_api_openssl::TlsConnector>(&mut rt, &server, pkcs12).expect("get");
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+
+    let identity = Pkcs12::from_der(&keys.server.pkcs12)
+        .unwrap()
+        .parse2(&keys.server.pkcs12_password)
+        .unwrap();
+    let mut pem = client_cert_tls_conf();
+    pem.identity = Some(ClientTlsIdentity::Pem {
+        cert: identity.cert.unwrap().to_pem().unwrap(),
+        key: identity.pkey.unwrap().private_key_to_pem_pkcs8().unwrap(),
+    });
+    let resp = get_hello::<tls_api_openssl::TlsConnector>(&mut rt, &server, pem).expect("get");
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn tls_conf_client_identity_native_tls() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let keys = httpbis_test::openssl_test_key_gen::keys();
+    let server = client_cert_server();
+
+    let mut tls_conf = client_cert_tls_conf();
+    tls_conf.identity = Some(ClientTlsIdentity::Pkcs12 {
+        der: keys.server.pkcs12.clone(),
+        password: keys.server.pkcs12_password.clone(),
+    });
+    let resp = get_hello::<TlsConnector>(&mut rt, &server, tls_conf).expect("get");
+    assert_eq!(&b"hello"[..], resp.body.get_bytes());
+}
+
+#[test]
+fn tls_reload() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+    let pkcs12_path = tempdir.path().join("server.p12");
+    let server_keys = &httpbis_test::openssl_test_key_gen::keys().server;
+    std::fs::write(&pkcs12_path, &server_keys.pkcs12).unwrap();
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(test_tls_acceptor());
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("hello")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let tls_handle = server.tls_handle();
+
+    let loads = Arc::new(AtomicUsize::new(0));
+    let errors = Arc::new(AtomicUsize::new(0));
+    let loads_copy = loads.clone();
+    let errors_copy = errors.clone();
+    let load_path = pkcs12_path.clone();
+    tls_handle
+        .watch_tls_files(
+            0,
+            vec![pkcs12_path.clone()],
+            Duration::from_millis(20),
+            move || {
+                loads_copy.fetch_add(1, Ordering::SeqCst);
+                let pkcs12 = std::fs::read(&load_path)?;
+                let builder =
+                    TlsAcceptorBuilder::from_pkcs12(&pkcs12, &server_keys.pkcs12_password)?;
+                Ok(builder.build()?)
+            },
+            move |_e| {
+                errors_copy.fetch_add(1, Ordering::SeqCst);
+            },
+        )
+        .expect("watch_tls_files");
+
+    let socket_addr = match server.local_addr() {
+        &AnySocketAddr::Inet(ref sock_addr) => *sock_addr,
+        _ => panic!("Assumed server was an inet server"),
+    };
+    let new_client = || {
+        Client::new_expl(
+            &socket_addr,
+            ClientTlsOption::Tls("localhost".to_owned(), Arc::new(test_tls_connector())),
+            Default::default(),
+        )
+        .expect("http client")
+    };
+    let mut get = |client: &Client| {
+        let resp: SimpleHttpMessage = rt
+            .block_on(client.start_get("/hi", "localhost").collect())
+            .unwrap();
+        assert_eq!(&b"hello"[..], resp.body.get_bytes());
+    };
+    let wait = |counter: &AtomicUsize, value: usize| {
+        for _ in 0..500 {
+            if counter.load(Ordering::SeqCst) >= value {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("counter is {}", counter.load(Ordering::SeqCst));
+    };
+
+    // replace file atomically, so watcher does not see it partially written
+    let replace = |content: &[u8]| {
+        let tmp_path = tempdir.path().join("server.p12.tmp");
+        std::fs::write(&tmp_path, content).unwrap();
+        std::fs::rename(&tmp_path, &pkcs12_path).unwrap();
+    };
+
+    let client = new_client();
+    get(&client);
+
+    // broken file does not replace acceptor
+    replace(b"garbage");
+    wait(&errors, 1);
+    get(&new_client());
+
+    replace(&server_keys.pkcs12);
+    wait(&loads, 2);
+    assert_eq!(1, errors.load(Ordering::SeqCst));
+    get(&new_client());
+
+    tls_handle.set_tls(0, test_tls_acceptor()).expect("set_tls");
+    get(&new_client());
+
+    // established connection is not affected
+    get(&client);
+
+    // server has single listener
+    match tls_handle.set_tls_sni(1, ServerTlsSniMap::new()) {
+        Err(httpbis::Error::NotTlsListener(1)) => {}
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn tls_reload_plain() {
+    let mut server = ServerBuilder::<TlsAcceptor>::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    let server = server.build().expect("server");
+    let tls_handle = server.tls_handle();
+
+    match tls_handle.set_tls(0, test_tls_acceptor()) {
+        Err(httpbis::Error::NotTlsListener(0)) => {}
+        r => panic!("{:?}", r),
+    }
+    let r = tls_handle.watch_tls_files(
+        0,
+        Vec::new(),
+        Duration::from_secs(1),
+        || Ok(test_tls_acceptor()),
+        |_| {},
+    );
+    match r {
+        Err(httpbis::Error::NotTlsListener(0)) => {}
+        r => panic!("{:?}", r),
+    }
+}
+
+#[test]
+fn tls_sni_hosts() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let mut acceptors = ServerTlsSniMap::new();
+    acceptors.insert("localhost", test_tls_acceptor());
+
+    let mut localhost = ServerHandlerPaths::new();
+    localhost.set_service_fn("/", |context, _, mut resp| {
+        assert!(context.conn_info().is_tls());
+        resp.send_found_200_plain_text(context.conn_info().tls_sni().unwrap_or("none"))?;
+        Ok(())
+    });
+
+    let mut hosts = ServerHandlerHosts::new();
+    hosts.set_host("localhost", Arc::new(localhost));
+    hosts.set_host("*.example", Arc::new(ServerHandlerPaths::new()));
+
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls_sni(acceptors);
+    server.service.set_service("/", Arc::new(hosts));
+    let server = server.build().expect("server");
+
+    let socket_addr = match server.local_addr() {
+        &AnySocketAddr::Inet(ref sock_addr) => sock_addr,
+        _ => panic!("Assumed server was an inet server"),
+    };
+
+    let client: Client = Client::new_expl(
+        socket_addr,
+        ClientTlsOption::Tls("localhost".to_owned(), Arc::new(test_tls_connector())),
+        Default::default(),
+    )
+    .expect("http client");
+
+    let resp: SimpleHttpMessage = rt
+        .block_on(client.start_get("/hi", "LocalHost:443").collect())
+        .unwrap();
+    assert_eq!(200, resp.headers.status());
+    assert_eq!(&b"localhost"[..], resp.body.get_bytes());
+
+    // host is served, but not by this connection
+    let resp: SimpleHttpMessage = rt
+        .block_on(client.start_get("/hi", "other.example").collect())
+        .unwrap();
+    assert_eq!(421, resp.headers.status());
+}
+
+#[test]
+fn tls_sni_reload() {
+    init_logger();
+
+    let mut rt = Runtime::new().unwrap();
+
+    let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+    let names_path = tempdir.path().join("names");
+    std::fs::write(&names_path, "other.example").unwrap();
+
+    let mut acceptors = ServerTlsSniMap::new();
+    acceptors.insert("localhost", test_tls_acceptor());
+
+    // listener 0 is `Tls`, listener 1 is `TlsSni`
+    let mut server = ServerBuilder::new();
+    server.set_addr((BIND_HOST, 0)).expect("set_addr");
+    server.set_tls(test_tls_acceptor());
+    server.add_listener(
+        AnySocketAddr::Inet((BIND_HOST, 0).to_socket_addrs().unwrap().next().unwrap()),
+        ServerTlsOption::TlsSni(Arc::new(acceptors)),
+    );
+    server.service.set_service_fn("/", |_, _, mut resp| {
+        resp.send_found_200_plain_text("hello")?;
+        Ok(())
+    });
+    let server = server.build().expect("server");
+    let tls_handle = server.tls_handle();
+
+    let ports: Vec<u16> = server
+        .local_addrs()
+        .iter()
+        .map(|a| a.port().unwrap())
+        .collect();
+    let mut get = |listener: usize| {
+        let client: Client = Client::new_expl(
+            &(BIND_HOST, ports[listener])
+                .to_socket_addrs()
+                .unwrap()
+                .next()
+                .unwrap(),
+            ClientTlsOption::Tls("localhost".to_owned(), Arc::new(test_tls_connector())),
+            Default::default(),
+        )
+        .expect("http client");
+        let r: httpbis::Result<SimpleHttpMessage> =
+            rt.block_on(client.start_get("/hi", "localhost").collect());
+        r
+    };
+
+    assert_eq!(&b"hello"[..], get(0).unwrap().body.get_bytes());
+    assert_eq!(&b"hello"[..], get(1).unwrap().body.get_bytes());
+
+    // names are loaded from the file
+    let load_path = names_path.clone();
+    tls_handle
+        .watch_tls_sni_files(
+            1,
+            vec![names_path.clone()],
+            Duration::from_millis(20),
+            move || {
+                let mut acceptors = ServerTlsSniMap::new();
+                for name in std::fs::read_to_string(&load_path)?.split_whitespace() {
+                    acceptors.insert(name, test_tls_acceptor());
+                }
+                Ok(acceptors)
+            },
+            |e| panic!("{:?}", e),
+        )
+        .expect("watch_tls_sni_files");
+
+    let mut acceptors = ServerTlsSniMap::new();
+    acceptors.insert("other.example", test_tls_acceptor());
+    tls_handle.set_tls_sni(1, acceptors).expect("set_tls_sni");
+    assert!(get(1).is_err());
+    // other listener is not affected
+    assert_eq!(&b"hello"[..], get(0).unwrap().body.get_bytes());
+
+    std::fs::write(&names_path, "other.example localhost").unwrap();
+    let mut reloaded = false;
+    for _ in 0..500 {
+        if get(1).is_ok() {
+            reloaded = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(reloaded);
+}
@@ -10,6 +10,13 @@ pub struct ClientConf {
     pub thread_name: Option<String>,
     /// Connection timeout.
     pub connection_timeout: Option<Duration>,
+    /// Max number of requests waiting for a stream slot
+    /// when peer `SETTINGS_MAX_CONCURRENT_STREAMS` is reached.
+    /// Default is unlimited.
+    pub max_queued_requests: Option<usize>,
+    /// How long a request may wait for a stream slot.
+    /// Default is forever.
+    pub queued_request_timeout: Option<Duration>,
 
     /// Common client/server conf.
     pub common: CommonConf,
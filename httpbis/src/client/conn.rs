@@ -1,5 +1,6 @@
 //! Single client connection
 
+use std::collections::VecDeque;
 use std::io;
 use std::result::Result as std_Result;
 use std::sync::Arc;
@@ -10,6 +11,7 @@ use crate::result;
 use crate::AnySocketAddr;
 
 use crate::solicit::end_stream::EndStream;
+use crate::solicit::frame::HttpFrameDecoded;
 use crate::solicit::frame::HttpSetting;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::header::*;
@@ -38,6 +40,7 @@ use crate::common::conn_command_channel::ConnCommandSender;
 use crate::common::conn_read::ConnReadSideCustom;
 use crate::common::conn_write::CommonToWriteMessage;
 use crate::common::conn_write::ConnWriteSideCustom;
+use crate::common::init_where::InitWhere;
 use crate::common::sender::CommonSender;
 use crate::common::stream::HttpStreamCommon;
 use crate::common::stream::HttpStreamData;
@@ -74,8 +77,18 @@ impl HttpStreamData for ClientStream {
     type Types = ClientTypes;
 }
 
+/// Request waiting for peer `SETTINGS_MAX_CONCURRENT_STREAMS`
+struct QueuedRequest {
+    id: u64,
+    start: ClientStartRequestMessage,
+}
+
 pub struct ClientConnData {
     _callbacks: Box<dyn ClientConnCallbacks>,
+    conf: ClientConf,
+    /// Requests started while peer concurrent streams limit is reached
+    queued_requests: VecDeque<QueuedRequest>,
+    last_queued_request_id: u64,
 }
 
 impl SideSpecific for ClientConnData {}
@@ -102,6 +115,7 @@ pub struct ClientStartRequestMessage {
 pub(crate) enum ClientToWriteMessage {
     Start(ClientStartRequestMessage),
     WaitForHandshake(oneshot::Sender<result::Result<()>>),
+    QueuedRequestTimeout(u64),
     Common(CommonToWriteMessage),
 }
 
@@ -119,21 +133,106 @@ where
 
     fn process_message(&mut self, message: ClientToWriteMessage) -> result::Result<()> {
         match message {
-            ClientToWriteMessage::Start(start) => self.process_start(start),
+            ClientToWriteMessage::Start(start) => self.process_start_or_queue(start),
             ClientToWriteMessage::Common(common) => self.process_common_message(common),
             ClientToWriteMessage::WaitForHandshake(tx) => {
                 // ignore error
                 drop(tx.send(Ok(())));
                 Ok(())
             }
+            ClientToWriteMessage::QueuedRequestTimeout(id) => {
+                self.process_queued_request_timeout(id);
+                Ok(())
+            }
         }
     }
+
+    fn start_queued_streams(&mut self) -> result::Result<bool> {
+        let mut started = false;
+        while self.can_start_stream() {
+            match self.specific.queued_requests.pop_front() {
+                Some(queued) => {
+                    debug!("starting queued request {}", queued.id);
+                    self.process_start(queued.start)?;
+                    started = true;
+                }
+                None => break,
+            }
+        }
+        Ok(started)
+    }
+
+    fn queued_streams_len(&self) -> usize {
+        self.specific.queued_requests.len()
+    }
 }
 
 impl<I> Conn<ClientTypes, I>
 where
     I: SocketStream,
 {
+    /// Pushed streams and streams completed by server
+    /// are not counted against `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    fn can_start_stream(&self) -> bool {
+        self.streams.len_initiated_open(InitWhere::Locally)
+            < self.peer_settings.max_concurrent_streams as usize
+    }
+
+    fn process_start_or_queue(&mut self, start: ClientStartRequestMessage) -> result::Result<()> {
+        if self.specific.queued_requests.is_empty() && self.can_start_stream() {
+            return self.process_start(start);
+        }
+
+        let mut start = start;
+
+        if let Some(max_queued_requests) = self.specific.conf.max_queued_requests {
+            if self.specific.queued_requests.len() >= max_queued_requests {
+                warn!("request queue is full, failing request");
+                start
+                    .start
+                    .stream_handler
+                    .request_failed(Error::RequestQueueFull);
+                return Ok(());
+            }
+        }
+
+        self.specific.last_queued_request_id += 1;
+        let id = self.specific.last_queued_request_id;
+
+        debug!(
+            "peer max concurrent streams {} reached, queueing request {}",
+            self.peer_settings.max_concurrent_streams, id
+        );
+
+        if let Some(timeout) = self.specific.conf.queued_request_timeout {
+            let to_write_tx = self.to_write_tx.clone();
+            self.loop_handle.spawn(async move {
+                time::delay_for(timeout).await;
+                // ignore error, connection might be already dead
+                drop(to_write_tx.unbounded_send(ClientToWriteMessage::QueuedRequestTimeout(id)));
+            });
+        }
+
+        self.specific
+            .queued_requests
+            .push_back(QueuedRequest { id, start });
+        Ok(())
+    }
+
+    fn process_queued_request_timeout(&mut self, id: u64) {
+        let queued_requests = &mut self.specific.queued_requests;
+        // request might be already started
+        if let Some(pos) = queued_requests.iter().position(|q| q.id == id) {
+            let mut queued = queued_requests.remove(pos).unwrap();
+            warn!("queued request {} timed out", id);
+            queued
+                .start
+                .start
+                .stream_handler
+                .request_failed(Error::RequestQueueTimeout);
+        }
+    }
+
     fn process_start(&mut self, start: ClientStartRequestMessage) -> result::Result<()> {
         let ClientStartRequestMessage {
             start:
@@ -240,7 +339,11 @@ impl ClientConn {
             write_tx: to_write_tx.clone(),
         };
 
-        let settings_frame = SettingsFrame::from_settings(vec![HttpSetting::EnablePush(false)]);
+        let mut settings = vec![HttpSetting::EnablePush(false)];
+        settings.push(HttpSetting::MaxHeaderListSize(
+            conf.common.max_header_list_size(),
+        ));
+        let settings_frame = SettingsFrame::from_settings(settings);
         let mut settings = DEFAULT_SETTINGS;
         settings.apply_from_frame(&settings_frame);
 
@@ -257,6 +360,9 @@ impl ClientConn {
                 lh_copy,
                 ClientConnData {
                     _callbacks: Box::new(callbacks),
+                    conf: conf.clone(),
+                    queued_requests: VecDeque::new(),
+                    last_queued_request_id: 0,
                 },
                 conf.common,
                 settings,
@@ -552,4 +658,26 @@ where
 
         Ok(Some(stream))
     }
+
+    fn in_body_limit_exceeded(&mut self, stream_id: StreamId) -> result::Result<()> {
+        // response body is not limited
+        self.send_rst_stream(stream_id, ErrorCode::Cancel)
+    }
+
+    fn header_list_too_large(
+        &mut self,
+        stream_id: StreamId,
+        _end_stream: EndStream,
+    ) -> result::Result<()> {
+        if let Some(mut stream) = self.streams.get_mut(stream_id) {
+            if let Some(handler) = stream.stream().peer_tx.take() {
+                drop(handler.error(error::Error::HeaderListTooLarge));
+            }
+        }
+        self.send_rst_stream(stream_id, ErrorCode::Cancel)
+    }
+
+    fn frame_received(&mut self, _frame: &HttpFrameDecoded) -> result::Result<bool> {
+        Ok(true)
+    }
 }
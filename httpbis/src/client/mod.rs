@@ -49,6 +49,9 @@ use crate::client::conn::StartRequestMessage;
 use crate::client::req::ClientRequest;
 
 use crate::client::stream_handler::ClientStreamCreatedHandler;
+use crate::client::tls::identity_supported;
+use crate::client::tls::set_identity;
+use crate::client::tls::ClientTlsConf;
 pub use crate::client::tls::ClientTlsOption;
 
 use crate::client_died_error_holder::ClientDiedType;
@@ -116,6 +119,18 @@ impl<C: TlsConnector> ClientBuilder<C> {
     }
 
     pub fn set_tls(&mut self, host: &str) -> Result<()> {
+        self.set_tls_conf(host, ClientTlsConf::new())
+    }
+
+    /// Build TLS connector from given configuration.
+    ///
+    /// Returns an error if the TLS backend does not support
+    /// some of the requested parameters.
+    pub fn set_tls_conf(&mut self, host: &str, tls_conf: ClientTlsConf) -> Result<()> {
+        if tls_conf.identity.is_some() && !identity_supported::<C::Builder>() {
+            return Err(Error::TlsFeatureNotSupported("client identity"));
+        }
+
         let mut tls_connector = C::builder()?;
 
         if C::supports_alpn() {
@@ -123,10 +138,25 @@ impl<C: TlsConnector> ClientBuilder<C> {
             tls_connector.set_alpn_protocols(&[b"h2"])?;
         }
 
+        for root in tls_conf.root_certificates {
+            tls_connector.add_root_certificate(tls_api::Certificate::from_der(root))?;
+        }
+
+        if let Some(verify_hostname) = tls_conf.verify_hostname {
+            tls_connector.set_verify_hostname(verify_hostname)?;
+        }
+
+        if let Some(identity) = &tls_conf.identity {
+            set_identity(&mut tls_connector, identity)?;
+        }
+
         let tls_connector = tls_connector.build()?;
 
+        // Domain is used both for SNI and for certificate verification
+        let domain = tls_conf.sni_host.as_deref().unwrap_or(host);
+
         let tls_connector = Arc::new(tls_connector);
-        self.tls = ClientTlsOption::Tls(host.to_owned(), tls_connector);
+        self.tls = ClientTlsOption::Tls(domain.to_owned(), tls_connector);
         Ok(())
     }
 
@@ -298,7 +328,7 @@ impl Client {
         let (tx, rx) = oneshot::channel();
 
         struct Impl {
-            tx: Option<oneshot::Sender<(ClientRequest, Response)>>,
+            tx: Option<oneshot::Sender<result::Result<(ClientRequest, Response)>>>,
         }
 
         impl ClientStreamCreatedHandler for Impl {
@@ -309,12 +339,19 @@ impl Client {
             ) -> result::Result<()> {
                 let tx = self.tx.take().unwrap();
 
-                if let Err(_) = tx.send((req, resp.make_stream())) {
+                if let Err(_) = tx.send(Ok((req, resp.make_stream()))) {
                     return Err(error::Error::CallerDied);
                 }
 
                 Ok(())
             }
+
+            fn request_failed(&mut self, error: error::Error) {
+                let tx = self.tx.take().unwrap();
+
+                // ignore error, caller might be already dead
+                drop(tx.send(Err(error)));
+            }
         }
 
         if let Err(e) = self.start_request_low_level(
@@ -328,7 +365,9 @@ impl Client {
         }
 
         let client_error = self.client_died_error_holder.clone();
-        let resp_rx = rx.map_err(move |oneshot::Canceled| client_error.error());
+        let resp_rx = rx
+            .map_err(move |oneshot::Canceled| client_error.error())
+            .and_then(future::ready);
 
         Box::pin(resp_rx)
     }
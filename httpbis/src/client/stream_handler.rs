@@ -11,6 +11,12 @@ use bytes::Bytes;
 pub trait ClientStreamCreatedHandler: Send + 'static {
     /// Called when stream is created
     fn request_created(&mut self, req: ClientRequest, resp: ClientResponse) -> crate::Result<()>;
+    /// Called instead of `request_created` when stream could not be created
+    ///
+    /// Default implementation only logs the error.
+    fn request_failed(&mut self, error: error::Error) {
+        warn!("request failed: {}", error);
+    }
 }
 
 /// Synchrnous callback of incoming data
@@ -1,7 +1,12 @@
+use std::any::Any;
+use std::any::TypeId;
 use std::sync::Arc;
 
 use tls_api::TlsConnector;
+use tls_api::TlsConnectorBuilder;
 
+use crate::error::Error;
+use crate::result::Result;
 use crate::solicit::HttpScheme;
 
 pub enum ClientTlsOption<C: TlsConnector> {
@@ -26,3 +31,165 @@ impl<C: TlsConnector> ClientTlsOption<C> {
         }
     }
 }
+
+/// Client certificate used for mutual TLS.
+#[derive(Clone)]
+pub enum ClientTlsIdentity {
+    /// PKCS#12 archive and its password.
+    Pkcs12 { der: Vec<u8>, password: String },
+    /// PEM encoded certificate chain and PEM encoded PKCS#8 private key.
+    Pem { cert: Vec<u8>, key: Vec<u8> },
+}
+
+/// TLS parameters used to build a connector in `ClientBuilder::set_tls_conf`.
+#[derive(Default, Clone)]
+pub struct ClientTlsConf {
+    /// Client certificate.
+    ///
+    /// `tls_api` has no identity operation, so it is supported only
+    /// for `tls-api-openssl` and `tls-api-native-tls` connectors with
+    /// `tls-openssl` and `tls-native-tls` crate features enabled,
+    /// other connectors fail with `Error::TlsFeatureNotSupported`.
+    pub identity: Option<ClientTlsIdentity>,
+    /// DER encoded certificates trusted in addition to system roots.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// Server name sent in SNI and checked against server certificate
+    /// instead of the host passed to `set_tls_conf`.
+    pub sni_host: Option<String>,
+    /// Verify server hostname. Default is `true`.
+    pub verify_hostname: Option<bool>,
+}
+
+impl ClientTlsConf {
+    /// Default configuration.
+    pub fn new() -> ClientTlsConf {
+        Default::default()
+    }
+}
+
+/// Connector builder supports `ClientTlsConf::identity`.
+pub(crate) fn identity_supported<B: TlsConnectorBuilder>() -> bool {
+    let id = TypeId::of::<B>();
+    #[cfg(feature = "tls-openssl")]
+    {
+        if id == TypeId::of::<tls_api_openssl::TlsConnectorBuilder>() {
+            return true;
+        }
+    }
+    #[cfg(feature = "tls-native-tls")]
+    {
+        if id == TypeId::of::<tls_api_native_tls::TlsConnectorBuilder>() {
+            return true;
+        }
+    }
+    let _ = id;
+    false
+}
+
+/// Set client certificate with the backend builder.
+pub(crate) fn set_identity<B: TlsConnectorBuilder>(
+    builder: &mut B,
+    identity: &ClientTlsIdentity,
+) -> Result<()> {
+    let builder: &mut dyn Any = builder;
+    #[cfg(feature = "tls-openssl")]
+    {
+        if let Some(builder) = builder.downcast_mut::<tls_api_openssl::TlsConnectorBuilder>() {
+            return set_identity_openssl(builder.underlying_mut(), identity);
+        }
+    }
+    #[cfg(feature = "tls-native-tls")]
+    {
+        if let Some(builder) = builder.downcast_mut::<tls_api_native_tls::TlsConnectorBuilder>() {
+            return set_identity_native_tls(builder.underlying_mut(), identity);
+        }
+    }
+    let _ = (builder, identity);
+    Err(Error::TlsFeatureNotSupported("client identity"))
+}
+
+#[cfg(feature = "tls-openssl")]
+fn set_identity_openssl(
+    builder: &mut openssl::ssl::SslConnectorBuilder,
+    identity: &ClientTlsIdentity,
+) -> Result<()> {
+    use openssl::pkcs12::Pkcs12;
+    use openssl::pkey::PKey;
+    use openssl::x509::X509;
+
+    let (cert, key, chain) = match identity {
+        ClientTlsIdentity::Pkcs12 { der, password } => {
+            let pkcs12 = Pkcs12::from_der(der)
+                .and_then(|p| p.parse2(password))
+                .map_err(tls_api::Error::new)?;
+            let chain: Vec<X509> = pkcs12.ca.into_iter().flatten().collect();
+            (pkcs12.cert, pkcs12.pkey, chain)
+        }
+        ClientTlsIdentity::Pem { cert, key } => {
+            let mut certs = X509::stack_from_pem(cert).map_err(tls_api::Error::new)?;
+            let key = PKey::private_key_from_pem(key).map_err(tls_api::Error::new)?;
+            let cert = if certs.is_empty() {
+                None
+            } else {
+                Some(certs.remove(0))
+            };
+            (cert, Some(key), certs)
+        }
+    };
+
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => {
+            return Err(Error::TlsError(tls_api::Error::new_other(
+                "client identity has no certificate or private key",
+            )))
+        }
+    };
+
+    builder
+        .set_certificate(&cert)
+        .map_err(tls_api::Error::new)?;
+    builder.set_private_key(&key).map_err(tls_api::Error::new)?;
+    for cert in chain {
+        builder
+            .add_extra_chain_cert(cert)
+            .map_err(tls_api::Error::new)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tls-native-tls")]
+fn set_identity_native_tls(
+    builder: &mut native_tls::TlsConnectorBuilder,
+    identity: &ClientTlsIdentity,
+) -> Result<()> {
+    let identity = match identity {
+        ClientTlsIdentity::Pkcs12 { der, password } => {
+            native_tls::Identity::from_pkcs12(der, password)
+        }
+        ClientTlsIdentity::Pem { cert, key } => native_tls::Identity::from_pkcs8(cert, key),
+    }
+    .map_err(tls_api::Error::new)?;
+    builder.identity(identity);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ClientBuilder;
+
+    #[test]
+    fn identity_not_supported() {
+        let mut tls_conf = ClientTlsConf::new();
+        tls_conf.identity = Some(ClientTlsIdentity::Pem {
+            cert: Vec::new(),
+            key: Vec::new(),
+        });
+        let mut client = ClientBuilder::<tls_api_stub::TlsConnector>::new();
+        match client.set_tls_conf("localhost", tls_conf) {
+            Err(Error::TlsFeatureNotSupported("client identity")) => {}
+            r => panic!("{:?}", r),
+        }
+    }
+}
@@ -0,0 +1,822 @@
+//! HTTP/1.1 request parsing and response encoding.
+//!
+//! Used by server to serve HTTP/1 requests with the same handlers as HTTP/2.
+
+use bytes::Buf;
+use bytes::Bytes;
+use bytes::BytesMut;
+
+use crate::error;
+use crate::headers_place::HeadersPlace;
+use crate::req_resp::RequestOrResponse;
+use crate::result;
+use crate::solicit::frame::SettingsFrame;
+use crate::solicit::header::Header;
+use crate::solicit::header::Headers;
+use crate::solicit::HttpScheme;
+
+/// Max size of request line with headers or of chunked body trailers
+pub(crate) const MAX_HEAD_LEN: usize = 64 * 1024;
+
+/// Max size of chunk size line
+const MAX_CHUNK_SIZE_LINE_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Http1Version {
+    Http10,
+    Http11,
+}
+
+/// How request body is delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Http1BodyLength {
+    Empty,
+    ContentLength(u64),
+    Chunked,
+}
+
+/// Parsed HTTP/1 request line and headers.
+#[derive(Debug)]
+pub(crate) struct Http1RequestHead {
+    pub version: Http1Version,
+    /// HTTP/2 request headers with pseudo-headers, connection-specific headers removed
+    pub headers: Headers,
+    pub body: Http1BodyLength,
+    pub keep_alive: bool,
+    pub expect_continue: bool,
+    /// `HTTP2-Settings` value of plaintext request asking for `h2c` upgrade (RFC 7540 3.2)
+    pub h2c_settings: Option<Bytes>,
+}
+
+fn bad_request(message: &str) -> error::Error {
+    error::Error::InvalidHttp1Request(message.to_owned())
+}
+
+/// Headers which must not be forwarded to HTTP/2 handler (RFC 7540 8.1.2.2)
+fn is_connection_specific_header(name: &str) -> bool {
+    matches!(
+        name,
+        "connection" | "keep-alive" | "proxy-connection" | "transfer-encoding" | "upgrade"
+    )
+}
+
+/// Token chars (RFC 7230 3.2.6)
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+fn is_token(s: &[u8]) -> bool {
+    !s.is_empty() && s.iter().cloned().all(is_tchar)
+}
+
+fn trim_ows(mut s: &[u8]) -> &[u8] {
+    while let Some((&c, rem)) = s.split_first() {
+        if c != b' ' && c != b'\t' {
+            break;
+        }
+        s = rem;
+    }
+    while let Some((&c, rem)) = s.split_last() {
+        if c != b' ' && c != b'\t' {
+            break;
+        }
+        s = rem;
+    }
+    s
+}
+
+/// Split comma-separated header value into lowercase elements
+fn split_list(value: &[u8]) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(|&c| c == b',')
+        .map(trim_ows)
+        .filter(|e| !e.is_empty())
+        .map(|e| String::from_utf8_lossy(e).to_ascii_lowercase())
+}
+
+/// Find end of request head (or trailers) terminated by an empty line.
+///
+/// Returns length of head including the empty line. Bare `LF` line endings are accepted.
+pub(crate) fn find_head_end(buf: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let lf = pos + buf[pos..].iter().position(|&c| c == b'\n')?;
+        let line = &buf[pos..lf];
+        if line.is_empty() || line == b"\r" {
+            return Some(lf + 1);
+        }
+        pos = lf + 1;
+    }
+}
+
+/// Split head into lines without line terminators, empty line is not included.
+fn head_lines(head: &[u8]) -> impl Iterator<Item = &[u8]> {
+    head.split(|&c| c == b'\n')
+        .map(|line| match line.split_last() {
+            Some((b'\r', line)) => line,
+            _ => line,
+        })
+        .take_while(|line| !line.is_empty())
+}
+
+/// Parse `name: value` header lines into lowercase names and values.
+fn parse_header_lines<'a>(
+    lines: impl Iterator<Item = &'a [u8]>,
+) -> result::Result<Vec<(String, Bytes)>> {
+    let mut r = Vec::new();
+    for line in lines {
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            return Err(bad_request("obsolete line folding"));
+        }
+        let colon = match line.iter().position(|&c| c == b':') {
+            Some(colon) => colon,
+            None => return Err(bad_request("header line without colon")),
+        };
+        let name = &line[..colon];
+        if !is_token(name) {
+            return Err(bad_request("invalid header name"));
+        }
+        let name = String::from_utf8_lossy(name).to_ascii_lowercase();
+        let value = Bytes::copy_from_slice(trim_ows(&line[colon + 1..]));
+        r.push((name, value));
+    }
+    Ok(r)
+}
+
+fn new_header(name: String, value: Bytes) -> result::Result<Header> {
+    Header::new_validate(Bytes::from(name), value).map_err(|_| bad_request("invalid header"))
+}
+
+/// Parse request line with headers.
+///
+/// `head` is the data returned by `find_head_end`.
+pub(crate) fn parse_request_head(
+    head: &[u8],
+    scheme: HttpScheme,
+) -> result::Result<Http1RequestHead> {
+    let mut lines = head_lines(head);
+
+    let request_line = lines.next().ok_or_else(|| bad_request("empty request"))?;
+    let mut parts = request_line.split(|&c| c == b' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) => (method, target, version),
+        _ => return Err(bad_request("malformed request line")),
+    };
+
+    if !is_token(method) {
+        return Err(bad_request("invalid method"));
+    }
+    if method == b"CONNECT" {
+        return Err(bad_request("CONNECT is not supported"));
+    }
+
+    let version = match version {
+        b"HTTP/1.1" => Http1Version::Http11,
+        b"HTTP/1.0" => Http1Version::Http10,
+        _ => return Err(bad_request("unsupported HTTP version")),
+    };
+
+    // absolute-form is accepted for compatibility with proxies (RFC 7230 5.3.2)
+    let (target_authority, path) = if target.starts_with(b"/") || target == b"*" {
+        (None, target)
+    } else {
+        let rem = match target.iter().position(|&c| c == b':') {
+            Some(colon) if target[colon..].starts_with(b"://") => &target[colon + 3..],
+            _ => return Err(bad_request("invalid request target")),
+        };
+        match rem.iter().position(|&c| c == b'/' || c == b'?') {
+            Some(slash) => (Some(&rem[..slash]), &rem[slash..]),
+            None => (Some(rem), &b"/"[..]),
+        }
+    };
+    if path.is_empty() {
+        return Err(bad_request("invalid request target"));
+    }
+
+    let raw_headers = parse_header_lines(lines)?;
+
+    let mut connection_options = Vec::new();
+    let mut upgrade = Vec::new();
+    let mut http2_settings = Vec::new();
+    let mut transfer_encoding = Vec::new();
+    let mut content_length = None;
+    let mut host = None;
+    let mut expect_continue = false;
+
+    for (name, value) in &raw_headers {
+        match name.as_str() {
+            "connection" => connection_options.extend(split_list(value)),
+            "upgrade" => upgrade.extend(split_list(value)),
+            "http2-settings" => http2_settings.push(value.clone()),
+            "transfer-encoding" => transfer_encoding.extend(split_list(value)),
+            "content-length" => {
+                let len = std::str::from_utf8(value)
+                    .ok()
+                    .filter(|v| !v.is_empty() && v.bytes().all(|c| c.is_ascii_digit()))
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .ok_or_else(|| bad_request("invalid content-length"))?;
+                if content_length.is_some() && content_length != Some(len) {
+                    return Err(bad_request("conflicting content-length"));
+                }
+                content_length = Some(len);
+            }
+            "host" => {
+                if host.is_some() {
+                    return Err(bad_request("more than one host header"));
+                }
+                host = Some(value.clone());
+            }
+            "expect" => {
+                expect_continue = value.eq_ignore_ascii_case(b"100-continue");
+            }
+            _ => {}
+        }
+    }
+
+    let body = if !transfer_encoding.is_empty() {
+        // 3.3.3
+        // If a Transfer-Encoding header field is present in a request and the
+        // chunked transfer coding is not the final encoding, the message body
+        // length cannot be determined reliably; the server MUST respond with
+        // the 400 (Bad Request) status code and then close the connection.
+        if transfer_encoding.last().map(String::as_str) != Some("chunked") {
+            return Err(bad_request("unsupported transfer-encoding"));
+        }
+        Http1BodyLength::Chunked
+    } else {
+        match content_length {
+            None | Some(0) => Http1BodyLength::Empty,
+            Some(len) => Http1BodyLength::ContentLength(len),
+        }
+    };
+
+    let keep_alive = match version {
+        Http1Version::Http11 => !connection_options.iter().any(|o| o == "close"),
+        Http1Version::Http10 => connection_options.iter().any(|o| o == "keep-alive"),
+    };
+    // 3.3.3
+    // If a message is received with both a Transfer-Encoding and a
+    // Content-Length header field, the Transfer-Encoding overrides the
+    // Content-Length. Such a message might indicate an attempt to
+    // perform request smuggling, so the connection is closed after the response.
+    let keep_alive = keep_alive && !(body == Http1BodyLength::Chunked && content_length.is_some());
+
+    // 3.2
+    // A request that upgrades from HTTP/1.1 to HTTP/2 MUST include exactly
+    // one HTTP2-Settings header field.
+    // "h2c" upgrade is only defined for cleartext connections.
+    let h2c_settings = if scheme == HttpScheme::Http
+        && version == Http1Version::Http11
+        && upgrade.iter().any(|u| u == "h2c")
+        && connection_options.iter().any(|o| o == "upgrade")
+        && connection_options.iter().any(|o| o == "http2-settings")
+        && http2_settings.len() == 1
+    {
+        http2_settings.pop()
+    } else {
+        None
+    };
+
+    let mut headers = Headers::new();
+    headers.add_header(new_header(
+        ":method".to_owned(),
+        Bytes::copy_from_slice(method),
+    )?);
+    headers.add_header(new_header(
+        ":scheme".to_owned(),
+        Bytes::from_static(scheme.as_bytes()),
+    )?);
+    headers.add_header(new_header(
+        ":path".to_owned(),
+        Bytes::copy_from_slice(path),
+    )?);
+    if let Some(authority) = target_authority
+        .map(Bytes::copy_from_slice)
+        .or(host.clone())
+    {
+        headers.add_header(new_header(":authority".to_owned(), authority)?);
+    }
+
+    for (name, value) in raw_headers {
+        if is_connection_specific_header(&name) || name == "host" {
+            continue;
+        }
+        if connection_options.contains(&name) || name == "http2-settings" {
+            continue;
+        }
+        if name == "content-length" && body == Http1BodyLength::Chunked {
+            continue;
+        }
+        // The only exception to this is the TE header field, which MAY be
+        // present in an HTTP/2 request; when it is, it MUST NOT contain any
+        // value other than "trailers".
+        if name == "te" && &value[..] != b"trailers" {
+            continue;
+        }
+        headers.add_header(new_header(name, value)?);
+    }
+
+    headers
+        .validate(RequestOrResponse::Request, HeadersPlace::Initial)
+        .map_err(|_| bad_request("invalid request headers"))?;
+
+    Ok(Http1RequestHead {
+        version,
+        headers,
+        body,
+        keep_alive,
+        expect_continue,
+        h2c_settings,
+    })
+}
+
+fn base64url_value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+        b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+        b'-' => Some(62),
+        b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode base64url without padding (RFC 4648 5)
+fn decode_base64url(value: &[u8]) -> Option<Vec<u8>> {
+    // trailing '=' is not allowed by RFC 7540, but tolerated
+    let end = value
+        .iter()
+        .rposition(|&c| c != b'=')
+        .map(|p| p + 1)
+        .unwrap_or(0);
+    let value = &value[..end];
+    if value.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut r = Vec::with_capacity(value.len() * 3 / 4);
+    for chunk in value.chunks(4) {
+        let mut acc = 0;
+        for &c in chunk {
+            acc = (acc << 6) | base64url_value(c)?;
+        }
+        acc <<= 6 * (4 - chunk.len()) as u32;
+        r.extend_from_slice(&acc.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(r)
+}
+
+/// Decode `HTTP2-Settings` header value into `SETTINGS` frame (RFC 7540 3.2.1)
+pub(crate) fn decode_h2c_settings(value: &[u8]) -> result::Result<SettingsFrame> {
+    let payload = decode_base64url(value).ok_or_else(|| bad_request("invalid HTTP2-Settings"))?;
+    let settings = SettingsFrame::parse_payload(&payload)
+        .map_err(|_| bad_request("invalid HTTP2-Settings"))?;
+    Ok(SettingsFrame::from_settings(settings))
+}
+
+/// Part of decoded chunked body
+#[derive(Debug, PartialEq)]
+pub(crate) enum ChunkedPart {
+    Data(Bytes),
+    Trailers(Headers),
+    End,
+}
+
+#[derive(Debug)]
+enum ChunkedState {
+    Size,
+    Data(u64),
+    DataCrlf,
+    Trailers,
+    Done,
+}
+
+/// Decoder of `Transfer-Encoding: chunked` body.
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: ChunkedState,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> ChunkedDecoder {
+        ChunkedDecoder {
+            state: ChunkedState::Size,
+        }
+    }
+
+    /// Decode next part consuming bytes from `buf`,
+    /// returned data is not longer than `max_data`.
+    ///
+    /// Returns `None` if more data is needed.
+    pub fn decode(
+        &mut self,
+        buf: &mut BytesMut,
+        max_data: usize,
+    ) -> result::Result<Option<ChunkedPart>> {
+        loop {
+            match self.state {
+                ChunkedState::Size => {
+                    let lf = match buf.iter().position(|&c| c == b'\n') {
+                        Some(lf) => lf,
+                        None if buf.len() > MAX_CHUNK_SIZE_LINE_LEN => {
+                            return Err(bad_request("chunk size line is too long"));
+                        }
+                        None => return Ok(None),
+                    };
+                    let line = buf.split_to(lf + 1);
+                    // chunk extensions are ignored
+                    let size = line[..lf].split(|&c| c == b';').next().unwrap();
+                    let size = std::str::from_utf8(trim_ows(size))
+                        .ok()
+                        .map(|s| s.trim_end_matches('\r'))
+                        // `from_str_radix` also accepts a sign
+                        .filter(|s| !s.is_empty() && s.bytes().all(|c| c.is_ascii_hexdigit()))
+                        .and_then(|s| u64::from_str_radix(s, 16).ok())
+                        .ok_or_else(|| bad_request("invalid chunk size"))?;
+                    self.state = match size {
+                        0 => ChunkedState::Trailers,
+                        size => ChunkedState::Data(size),
+                    };
+                }
+                ChunkedState::Data(rem) => {
+                    if buf.is_empty() || max_data == 0 {
+                        return Ok(None);
+                    }
+                    let len = if (buf.len() as u64) < rem {
+                        buf.len()
+                    } else {
+                        rem as usize
+                    };
+                    let len = len.min(max_data);
+                    let data = buf.split_to(len).freeze();
+                    self.state = match rem - len as u64 {
+                        0 => ChunkedState::DataCrlf,
+                        rem => ChunkedState::Data(rem),
+                    };
+                    return Ok(Some(ChunkedPart::Data(data)));
+                }
+                ChunkedState::DataCrlf => {
+                    if buf.starts_with(b"\r\n") {
+                        buf.advance(2);
+                    } else if buf.starts_with(b"\n") {
+                        buf.advance(1);
+                    } else if &buf[..] == b"\r" || buf.is_empty() {
+                        return Ok(None);
+                    } else {
+                        return Err(bad_request("missing CRLF after chunk data"));
+                    }
+                    self.state = ChunkedState::Size;
+                }
+                ChunkedState::Trailers => {
+                    let len = match find_head_end(buf) {
+                        Some(len) => len,
+                        None if buf.len() > MAX_HEAD_LEN => {
+                            return Err(bad_request("trailers are too large"));
+                        }
+                        None => return Ok(None),
+                    };
+                    let trailers = buf.split_to(len);
+                    self.state = ChunkedState::Done;
+                    let trailers = parse_header_lines(head_lines(&trailers))?;
+                    if trailers.is_empty() {
+                        return Ok(Some(ChunkedPart::End));
+                    }
+                    let mut headers = Headers::new();
+                    for (name, value) in trailers {
+                        if is_connection_specific_header(&name) {
+                            continue;
+                        }
+                        headers.add_header(new_header(name, value)?);
+                    }
+                    return Ok(Some(ChunkedPart::Trailers(headers)));
+                }
+                ChunkedState::Done => return Ok(Some(ChunkedPart::End)),
+            }
+        }
+    }
+}
+
+/// Standard reason phrase or empty string
+fn reason_phrase(status: u32) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        410 => "Gone",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        421 => "Misdirected Request",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => "",
+    }
+}
+
+fn write_header_line(buf: &mut Vec<u8>, name: &str, value: &[u8]) {
+    // CR or LF would allow injecting headers or a whole response
+    if name
+        .bytes()
+        .chain(value.iter().cloned())
+        .any(|c| c == b'\r' || c == b'\n')
+    {
+        warn!("skipping response header with CR or LF: {:?}", name);
+        return;
+    }
+    buf.extend_from_slice(name.as_bytes());
+    buf.extend_from_slice(b": ");
+    buf.extend_from_slice(value);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Encode response status line and headers.
+///
+/// Pseudo-headers from `headers` are skipped, `extra` headers are written after `headers`.
+pub(crate) fn write_response_head(
+    buf: &mut Vec<u8>,
+    status: u32,
+    headers: &Headers,
+    extra: &[(&str, &str)],
+) {
+    buf.extend_from_slice(format!("HTTP/1.1 {} {}\r\n", status, reason_phrase(status)).as_bytes());
+    for header in headers.iter() {
+        if header.is_preudo_header() {
+            continue;
+        }
+        write_header_line(buf, header.name(), header.value());
+    }
+    for (name, value) in extra {
+        write_header_line(buf, name, value.as_bytes());
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Encode a chunk of `Transfer-Encoding: chunked` body
+pub(crate) fn write_chunk(buf: &mut Vec<u8>, data: &[u8]) {
+    if data.is_empty() {
+        // empty chunk would terminate the body
+        return;
+    }
+    buf.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+    buf.extend_from_slice(data);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Encode last chunk with optional trailers
+pub(crate) fn write_last_chunk(buf: &mut Vec<u8>, trailers: Option<&Headers>) {
+    buf.extend_from_slice(b"0\r\n");
+    if let Some(trailers) = trailers {
+        for header in trailers.iter() {
+            if header.is_preudo_header() {
+                continue;
+            }
+            write_header_line(buf, header.name(), header.value());
+        }
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solicit::frame::HttpSetting;
+    use crate::HeaderValue;
+
+    fn parse(head: &[u8]) -> result::Result<Http1RequestHead> {
+        let len = find_head_end(head).expect("head end");
+        assert_eq!(head.len(), len);
+        parse_request_head(head, HttpScheme::Http)
+    }
+
+    #[test]
+    fn find_head_end_crlf_and_lf() {
+        assert_eq!(None, find_head_end(b"GET / HTTP/1.1\r\nhost: a\r\n"));
+        assert_eq!(
+            Some(27),
+            find_head_end(b"GET / HTTP/1.1\r\nhost: a\r\n\r\nbody")
+        );
+        assert_eq!(Some(24), find_head_end(b"GET / HTTP/1.1\nhost: a\n\nbody"));
+    }
+
+    #[test]
+    fn parse_get() {
+        let head =
+            parse(b"GET /a?b=c HTTP/1.1\r\nHost: example.com\r\nX-Foo:  bar \r\n\r\n").unwrap();
+        assert_eq!(Http1Version::Http11, head.version);
+        assert_eq!(Http1BodyLength::Empty, head.body);
+        assert!(head.keep_alive);
+        assert_eq!("GET", head.headers.method());
+        assert_eq!("/a?b=c", head.headers.path());
+        assert_eq!(Some("http"), head.headers.get_opt(":scheme"));
+        assert_eq!(Some("example.com"), head.headers.get_opt(":authority"));
+        assert_eq!(Some("bar"), head.headers.get_opt("x-foo"));
+        assert_eq!(None, head.headers.get_opt("host"));
+    }
+
+    #[test]
+    fn parse_absolute_form() {
+        let head = parse(b"GET http://example.com:80 HTTP/1.1\r\nHost: other\r\n\r\n").unwrap();
+        assert_eq!("/", head.headers.path());
+        assert_eq!(Some("example.com:80"), head.headers.get_opt(":authority"));
+    }
+
+    #[test]
+    fn parse_body_length() {
+        let head = parse(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n").unwrap();
+        assert_eq!(Http1BodyLength::ContentLength(10), head.body);
+        assert_eq!(Some(10), head.headers.content_length());
+
+        let head =
+            parse(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 10\r\n\r\n")
+                .unwrap();
+        assert_eq!(Http1BodyLength::Chunked, head.body);
+        assert_eq!(None, head.headers.get_opt("content-length"));
+        assert_eq!(None, head.headers.get_opt("transfer-encoding"));
+        assert!(!head.keep_alive);
+
+        assert!(parse(b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n").is_err());
+        assert!(parse(b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n").is_err());
+        assert!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n").is_err()
+        );
+    }
+
+    #[test]
+    fn parse_keep_alive() {
+        assert!(
+            !parse(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .unwrap()
+                .keep_alive
+        );
+        assert!(!parse(b"GET / HTTP/1.0\r\n\r\n").unwrap().keep_alive);
+        let head =
+            parse(b"GET / HTTP/1.0\r\nConnection: keep-alive, x-hop\r\nx-hop: 1\r\n\r\n").unwrap();
+        assert!(head.keep_alive);
+        assert_eq!(None, head.headers.get_opt("x-hop"));
+        assert_eq!(None, head.headers.get_opt("connection"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse(b"GET /\r\n\r\n").is_err());
+        assert!(parse(b"GET / HTTP/2.0\r\n\r\n").is_err());
+        assert!(parse(b"GET / HTTP/1.1\r\nx-foo: a\r\n b\r\n\r\n").is_err());
+        assert!(parse(b"GET / HTTP/1.1\r\nbad header: a\r\n\r\n").is_err());
+        assert!(parse(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn chunked() {
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::from(&b"4\r\nab"[..]);
+        assert_eq!(
+            Some(ChunkedPart::Data(Bytes::from_static(b"ab"))),
+            decoder.decode(&mut buf, usize::MAX).unwrap()
+        );
+        assert_eq!(None, decoder.decode(&mut buf, usize::MAX).unwrap());
+        buf.extend_from_slice(b"cd\r\n1;ext=1\r\ne\r\n0\r\n");
+        assert_eq!(
+            Some(ChunkedPart::Data(Bytes::from_static(b"cd"))),
+            decoder.decode(&mut buf, usize::MAX).unwrap()
+        );
+        assert_eq!(
+            Some(ChunkedPart::Data(Bytes::from_static(b"e"))),
+            decoder.decode(&mut buf, usize::MAX).unwrap()
+        );
+        assert_eq!(None, decoder.decode(&mut buf, usize::MAX).unwrap());
+        buf.extend_from_slice(b"\r\nGET");
+        assert_eq!(
+            Some(ChunkedPart::End),
+            decoder.decode(&mut buf, usize::MAX).unwrap()
+        );
+        assert_eq!(&b"GET"[..], &buf[..]);
+    }
+
+    #[test]
+    fn chunked_trailers() {
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::from(&b"0\r\nx-checksum: 1\r\n\r\n"[..]);
+        match decoder.decode(&mut buf, usize::MAX).unwrap() {
+            Some(ChunkedPart::Trailers(trailers)) => {
+                assert_eq!(Some("1"), trailers.get_opt("x-checksum"));
+            }
+            part => panic!("expecting trailers, got: {:?}", part),
+        }
+        assert!(buf.is_empty());
+
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::from(&b"zz\r\n"[..]);
+        assert!(decoder.decode(&mut buf, usize::MAX).is_err());
+
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::from(&b"+4\r\nabcd\r\n"[..]);
+        assert!(decoder.decode(&mut buf, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn chunked_max_data() {
+        let mut decoder = ChunkedDecoder::new();
+        let mut buf = BytesMut::from(&b"4\r\nabcd\r\n0\r\n\r\n"[..]);
+        assert_eq!(None, decoder.decode(&mut buf, 0).unwrap());
+        assert_eq!(
+            Some(ChunkedPart::Data(Bytes::from_static(b"abc"))),
+            decoder.decode(&mut buf, 3).unwrap()
+        );
+        assert_eq!(
+            Some(ChunkedPart::Data(Bytes::from_static(b"d"))),
+            decoder.decode(&mut buf, 3).unwrap()
+        );
+        assert_eq!(Some(ChunkedPart::End), decoder.decode(&mut buf, 0).unwrap());
+    }
+
+    #[test]
+    fn response_head() {
+        let mut headers = Headers::ok_200();
+        headers.add("content-type", "text/plain");
+        let mut buf = Vec::new();
+        write_response_head(&mut buf, 200, &headers, &[("transfer-encoding", "chunked")]);
+        assert_eq!(
+            &b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ntransfer-encoding: chunked\r\n\r\n"[..],
+            &buf[..]
+        );
+
+        // values are validated by `Headers`, but unchecked values are not
+        let value =
+            unsafe { HeaderValue::from_bytes_unchecked(Bytes::from_static(b"a\r\nset-cookie: b")) };
+        let mut headers = Headers::ok_200();
+        headers.add("x-injected", value);
+        let mut buf = Vec::new();
+        write_response_head(&mut buf, 200, &headers, &[]);
+        assert_eq!(&b"HTTP/1.1 200 OK\r\n\r\n"[..], &buf[..]);
+
+        let mut buf = Vec::new();
+        write_chunk(&mut buf, b"abc");
+        write_last_chunk(&mut buf, None);
+        assert_eq!(&b"3\r\nabc\r\n0\r\n\r\n"[..], &buf[..]);
+    }
+
+    #[test]
+    fn parse_h2c_upgrade() {
+        let head = parse(
+            b"GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+              Upgrade: h2c\r\nHTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(Bytes::from_static(b"AAMAAABkAAQAAP__")),
+            head.h2c_settings
+        );
+        assert_eq!(None, head.headers.get_opt("http2-settings"));
+        assert_eq!(None, head.headers.get_opt("upgrade"));
+
+        let head = parse(b"GET / HTTP/1.1\r\nHost: a\r\nUpgrade: h2c\r\nHTTP2-Settings: \r\n\r\n")
+            .unwrap();
+        assert_eq!(None, head.h2c_settings);
+
+        let head = b"GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade, HTTP2-Settings\r\n\
+              Upgrade: h2c\r\nHTTP2-Settings: \r\n\r\n";
+        let head = parse_request_head(head, HttpScheme::Https).unwrap();
+        assert_eq!(None, head.h2c_settings);
+    }
+
+    #[test]
+    fn h2c_settings() {
+        let frame = decode_h2c_settings(b"AAMAAABkAAQAAP__").unwrap();
+        assert_eq!(
+            vec![
+                HttpSetting::MaxConcurrentStreams(100),
+                HttpSetting::InitialWindowSize(0xffff),
+            ],
+            frame.settings
+        );
+
+        assert_eq!(0, decode_h2c_settings(b"").unwrap().settings.len());
+        assert!(decode_h2c_settings(b"AAMAAABk=").is_ok());
+        assert!(decode_h2c_settings(b"AAMAAA").is_err());
+        assert!(decode_h2c_settings(b"AAMAAAB!").is_err());
+    }
+}
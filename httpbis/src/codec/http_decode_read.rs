@@ -1,6 +1,11 @@
 use crate::codec::http_framed_read::HttpFramedJoinContinuationRead;
+use crate::common::conf::CommonConf;
+use crate::error;
 use crate::hpack;
+use crate::hpack::decoder::DecoderError;
 use crate::result;
+use crate::solicit::end_stream::EndStream;
+use crate::solicit::frame::HeadersFlag;
 use crate::solicit::frame::HttpFrameDecoded;
 use crate::solicit::frame::{HeadersDecodedFrame, HttpFrame};
 use crate::solicit::stream_id::StreamId;
@@ -21,29 +26,57 @@ pub enum HttpFrameDecodedOrGoaway {
     Frame(HttpFrameDecoded),
     SendGoaway(ErrorCode),
     _SendRst(StreamId, ErrorCode),
+    /// Decoded headers exceed `CommonConf::max_header_list_size`
+    HeaderListTooLarge(StreamId, EndStream),
 }
 
 impl<R: AsyncRead + Unpin> HttpDecodeRead<R> {
-    pub fn new(read: R) -> Self {
+    pub fn new(read: R, conf: &CommonConf) -> Self {
+        let mut decoder = hpack::Decoder::new();
+        decoder.set_max_header_list_size(conf.max_header_list_size());
         HttpDecodeRead {
-            framed_read: HttpFramedJoinContinuationRead::new(read),
-            decoder: hpack::Decoder::new(),
+            framed_read: HttpFramedJoinContinuationRead::new(read, conf),
+            decoder,
         }
     }
 
+    /// Header block is partially received.
+    pub fn is_header_block_pending(&self) -> bool {
+        self.framed_read.is_header_block_pending()
+    }
+
     pub fn poll_http_frame(
         &mut self,
         cx: &mut Context<'_>,
         max_frame_size: u32,
     ) -> Poll<result::Result<HttpFrameDecodedOrGoaway>> {
-        let frame = match self.framed_read.poll_http_frame(cx, max_frame_size)? {
-            Poll::Ready(frame) => frame,
+        let frame = match self.framed_read.poll_http_frame(cx, max_frame_size) {
+            Poll::Ready(Ok(frame)) => frame,
+            Poll::Ready(Err(error::Error::HeaderBlockTooLarge)) => {
+                warn!("header block too large");
+                return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
+                    ErrorCode::EnhanceYourCalm,
+                )));
+            }
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
             Poll::Pending => return Poll::Pending,
         };
         Poll::Ready(Ok(HttpFrameDecodedOrGoaway::Frame(match frame {
             HttpFrame::Data(frame) => HttpFrameDecoded::Data(frame),
             HttpFrame::Headers(frame) => {
                 let headers = match self.decoder.decode(frame.header_fragment) {
+                    Err(DecoderError::HeaderListTooLarge) => {
+                        warn!("header list too large in stream {}", frame.stream_id);
+                        let end_stream = if frame.flags.is_set(HeadersFlag::EndStream) {
+                            EndStream::Yes
+                        } else {
+                            EndStream::No
+                        };
+                        return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::HeaderListTooLarge(
+                            frame.stream_id,
+                            end_stream,
+                        )));
+                    }
                     Err(e) => {
                         warn!("failed to decode headers: {:?}", e);
                         return Poll::Ready(Ok(HttpFrameDecodedOrGoaway::SendGoaway(
@@ -1,6 +1,7 @@
 use bytes::Bytes;
 use bytes::BytesMut;
 
+use crate::common::conf::CommonConf;
 use crate::error;
 use crate::result;
 use crate::solicit::frame::unpack_header_from_slice;
@@ -33,6 +34,15 @@ impl<R: AsyncRead + Unpin> HttpFramedRead<R> {
         }
     }
 
+    /// Type of partially read frame, if frame header is read.
+    fn partial_frame_type(&self) -> Option<RawHttpFrameType> {
+        if self.buf.len() > 3 {
+            Some(RawHttpFrameType(self.buf[3]))
+        } else {
+            None
+        }
+    }
+
     fn fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<result::Result<()>> {
         let mut_self = self.get_mut();
         mut_self.buf.reserve(8192);
@@ -111,6 +121,8 @@ enum ContinuableFrame {
 
 struct Continuable {
     header_fragment: BytesMut,
+    /// `CONTINUATION` frames joined
+    continuation_frames: u32,
     /// Note frame contatains a header fragment, but it is not used
     frame: ContinuableFrame,
 }
@@ -119,6 +131,7 @@ impl Continuable {
     fn headers(header: HeadersFrame) -> Continuable {
         Continuable {
             header_fragment: BytesMut::from(&header.header_fragment[..]),
+            continuation_frames: 0,
             frame: ContinuableFrame::Headers(header),
         }
     }
@@ -126,6 +139,7 @@ impl Continuable {
     fn push_promise(push_promise: PushPromiseFrame) -> Continuable {
         Continuable {
             header_fragment: BytesMut::from(&push_promise.header_fragment[..]),
+            continuation_frames: 0,
             frame: ContinuableFrame::PushPromise(push_promise),
         }
     }
@@ -144,6 +158,7 @@ impl Continuable {
 
     fn extend_header_fragment(&mut self, bytes: Bytes) {
         self.header_fragment.extend_from_slice(&bytes[..]);
+        self.continuation_frames += 1;
     }
 
     fn set_end_headers(&mut self) {
@@ -167,16 +182,37 @@ impl Continuable {
 
 pub struct HttpFramedJoinContinuationRead<R: AsyncRead + Unpin> {
     framed_read: HttpFramedRead<R>,
-    // TODO: check total size is not exceeded some limit
     header_opt: Option<Continuable>,
+    max_header_block_size: u32,
+    max_continuation_frames: u32,
 }
 
 impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
-    pub fn new(read: R) -> Self {
+    pub fn new(read: R, conf: &CommonConf) -> Self {
         HttpFramedJoinContinuationRead {
             framed_read: HttpFramedRead::new(read),
             header_opt: None,
+            max_header_block_size: conf.max_header_block_size(),
+            max_continuation_frames: conf.max_continuation_frames(),
+        }
+    }
+
+    /// Check header block with given fragment size and `CONTINUATION` frame count
+    /// does not exceed limits.
+    fn check_header_block(&self, size: usize, continuation_frames: u32) -> result::Result<()> {
+        if size > self.max_header_block_size as usize
+            || continuation_frames > self.max_continuation_frames
+        {
+            return Err(error::Error::HeaderBlockTooLarge);
         }
+        Ok(())
+    }
+
+    /// `HEADERS` or `CONTINUATION` frames of incomplete header block are read
+    /// or `HEADERS` frame is partially read.
+    pub fn is_header_block_pending(&self) -> bool {
+        self.header_opt.is_some()
+            || self.framed_read.partial_frame_type() == Some(RawHttpFrameType::HEADERS)
     }
 
     pub fn poll_http_frame(
@@ -197,6 +233,7 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                             RawHttpFrameType::HEADERS,
                         )));
                     } else {
+                        self.check_header_block(h.header_fragment.len(), 0)?;
                         if h.flags.is_set(HeadersFlag::EndHeaders) {
                             return Poll::Ready(Ok(HttpFrame::Headers(h)));
                         } else {
@@ -211,6 +248,7 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                             RawHttpFrameType::PUSH_PROMISE,
                         )));
                     } else {
+                        self.check_header_block(p.header_fragment.len(), 0)?;
                         if p.flags.is_set(PushPromiseFlag::EndHeaders) {
                             return Poll::Ready(Ok(HttpFrame::PushPromise(p)));
                         } else {
@@ -229,6 +267,10 @@ impl<R: AsyncRead + Unpin> HttpFramedJoinContinuationRead<R> {
                                 ),
                             ));
                         } else {
+                            self.check_header_block(
+                                h.header_fragment.len() + c.header_fragment.len(),
+                                h.continuation_frames + 1,
+                            )?;
                             let header_end = c.is_headers_end();
                             h.extend_header_fragment(c.header_fragment);
                             if header_end {
@@ -1,3 +1,4 @@
+pub mod http1;
 pub mod http_decode_read;
 pub mod http_framed_read;
 pub mod http_framed_write;
@@ -1,8 +1,47 @@
 #[derive(Default, Debug, Clone)]
-pub struct CommonConf {}
+pub struct CommonConf {
+    /// Max size of received header list, computed as for `SETTINGS_MAX_HEADER_LIST_SIZE`,
+    /// and advertised to peer in that setting.
+    /// Default is `DEFAULT_MAX_HEADER_LIST_SIZE`, use `u32::MAX` for unlimited.
+    ///
+    /// Server replies 431 to requests with larger headers, client resets the stream.
+    pub max_header_list_size: Option<u32>,
+    /// Max compressed size of received header block, including `CONTINUATION` frames.
+    /// Default is `DEFAULT_MAX_HEADER_BLOCK_SIZE`, use `u32::MAX` for unlimited.
+    ///
+    /// Connection is closed with `GOAWAY(ENHANCE_YOUR_CALM)` when exceeded.
+    pub max_header_block_size: Option<u32>,
+    /// Max number of `CONTINUATION` frames in received header block.
+    /// Default is `DEFAULT_MAX_CONTINUATION_FRAMES`, use `u32::MAX` for unlimited.
+    ///
+    /// Connection is closed with `GOAWAY(ENHANCE_YOUR_CALM)` when exceeded.
+    pub max_continuation_frames: Option<u32>,
+}
 
 impl CommonConf {
+    /// Default max size of received header list.
+    pub const DEFAULT_MAX_HEADER_LIST_SIZE: u32 = 64 * 1024;
+    /// Default max compressed size of received header block.
+    pub const DEFAULT_MAX_HEADER_BLOCK_SIZE: u32 = 64 * 1024;
+    /// Default max number of `CONTINUATION` frames in received header block.
+    pub const DEFAULT_MAX_CONTINUATION_FRAMES: u32 = 32;
+
     pub fn new() -> CommonConf {
         Default::default()
     }
+
+    pub(crate) fn max_header_list_size(&self) -> u32 {
+        self.max_header_list_size
+            .unwrap_or(CommonConf::DEFAULT_MAX_HEADER_LIST_SIZE)
+    }
+
+    pub(crate) fn max_header_block_size(&self) -> u32 {
+        self.max_header_block_size
+            .unwrap_or(CommonConf::DEFAULT_MAX_HEADER_BLOCK_SIZE)
+    }
+
+    pub(crate) fn max_continuation_frames(&self) -> u32 {
+        self.max_continuation_frames
+            .unwrap_or(CommonConf::DEFAULT_MAX_CONTINUATION_FRAMES)
+    }
 }
@@ -30,6 +30,10 @@ use crate::codec::queued_write::QueuedWrite;
 use crate::common::conn_command_channel::ConnCommandReceiver;
 use crate::common::conn_command_channel::ConnCommandSender;
 use crate::common::conn_read::ConnReadSideCustom;
+use crate::common::conn_timers::ConnTimeouts;
+use crate::common::conn_timers::ConnTimerEvent;
+use crate::common::conn_timers::ConnTimers;
+use crate::common::conn_timers::ConnTimersState;
 use crate::common::conn_write::ConnWriteSideCustom;
 use crate::common::init_where::InitWhere;
 use crate::hpack;
@@ -58,6 +62,15 @@ use tokio::runtime::Handle;
 /// Client or server fields of connection
 pub trait SideSpecific: Send + 'static {}
 
+/// Stage of graceful shutdown (RFC 7540 6.8)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum GracefulShutdownStage {
+    /// `GOAWAY` with max stream id and `PING` sent, waiting for `PING` ack
+    WaitPingAck,
+    /// Final `GOAWAY` sent, waiting for remaining streams
+    Drain,
+}
+
 /// HTTP/2 connection state with socket and streams
 pub(crate) struct Conn<T: Types, I: SocketStream> {
     pub peer_addr: AnySocketAddr,
@@ -80,6 +93,15 @@ pub(crate) struct Conn<T: Types, I: SocketStream> {
     pub goaway_sent: Option<GoawayFrame>,
     pub goaway_received: Option<GoawayFrame>,
     pub ping_sent: Option<u64>,
+    /// Keepalive `PING` is sent and not acknowledged yet
+    pub keepalive_ping_sent: bool,
+    pub graceful_shutdown: Option<GracefulShutdownStage>,
+
+    pub timers: ConnTimers,
+    /// First peer `SETTINGS` frame received
+    pub settings_received: bool,
+    /// Error returned after connection is closed gracefully
+    pub close_reason: Option<error::Error>,
 
     /// Tracks the size of the outbound flow control window
     pub out_window_size: WindowSize,
@@ -122,6 +144,8 @@ pub struct ConnStateSnapshot {
     pub pump_out_window_size: isize,
     pub out_buf_bytes: usize,
     pub streams: HashMap<StreamId, HttpStreamStateSnapshot>,
+    /// Streams waiting for peer `SETTINGS_MAX_CONCURRENT_STREAMS`
+    pub queued_streams: usize,
 }
 
 impl ConnStateSnapshot {
@@ -144,7 +168,7 @@ where
     pub fn new(
         loop_handle: Handle,
         specific: T::SideSpecific,
-        _conf: CommonConf,
+        conf: CommonConf,
         sent_settings: HttpSettings,
         to_write_tx: ConnCommandSender<T>,
         write_rx: ConnCommandReceiver<T>,
@@ -160,7 +184,7 @@ where
 
         let (read, write) = split(socket);
 
-        let framed_read = HttpDecodeRead::new(read);
+        let framed_read = HttpDecodeRead::new(read, &conf);
         let queued_write = QueuedWrite::new(write);
 
         Conn {
@@ -175,6 +199,11 @@ where
             goaway_sent: None,
             goaway_received: None,
             ping_sent: None,
+            keepalive_ping_sent: false,
+            graceful_shutdown: None,
+            timers: ConnTimers::new(ConnTimeouts::default()),
+            settings_received: false,
+            close_reason: None,
             pump_out_window_size: pump_window_size,
             peer_closed_streams: ClosedStreams::new(),
             framed_read,
@@ -210,6 +239,26 @@ where
             .pump_out_window_size
             .new_stream(self.peer_settings.initial_window_size as u32);
 
+        let stream = self.insert_stream_data(
+            stream_id,
+            out_window_sender,
+            in_rem_content_length,
+            in_message_stage,
+            specific,
+        );
+
+        (stream, out_window_receiver)
+    }
+
+    /// Insert stream with window created outside of the connection loop.
+    pub fn insert_stream_data(
+        &mut self,
+        stream_id: StreamId,
+        out_window_sender: window_size::StreamOutWindowSender,
+        in_rem_content_length: Option<u64>,
+        in_message_stage: InMessageStage,
+        specific: T::HttpStreamSpecific,
+    ) -> HttpStreamRef<T> {
         let stream = HttpStreamCommon::new(
             self.our_settings_sent().initial_window_size,
             self.peer_settings.initial_window_size,
@@ -219,9 +268,7 @@ where
             specific,
         );
 
-        let stream = self.streams.insert(stream_id, stream);
-
-        (stream, out_window_receiver)
+        self.streams.insert(stream_id, stream)
     }
 
     pub fn dump_state(&self) -> ConnStateSnapshot {
@@ -232,6 +279,7 @@ where
             pump_out_window_size: self.pump_out_window_size.get(),
             out_buf_bytes: self.queued_write.queued_bytes_len(),
             streams: self.streams.snapshot(),
+            queued_streams: self.queued_streams_len(),
         }
     }
 
@@ -437,6 +485,11 @@ where
         // Always flush outgoing queue
         self.poll_flush(cx)?;
 
+        // Streams might have been closed since last iteration
+        if self.start_queued_streams()? {
+            self.poll_flush(cx)?;
+        }
+
         if self.queued_write.goaway_queued_and_flushed() {
             info!("GOAWAY written and flushed, closing connection");
             return Poll::Ready(Ok(LoopEvent::ExitLoop));
@@ -447,6 +500,14 @@ where
             return Poll::Ready(Ok(LoopEvent::ExitLoop));
         }
 
+        if self.graceful_shutdown == Some(GracefulShutdownStage::Drain)
+            && self.streams.is_empty()
+            && self.queued_write.queued_bytes_len() == 0
+        {
+            info!("graceful shutdown complete, closing connection");
+            return Poll::Ready(Ok(LoopEvent::ExitLoop));
+        }
+
         match Pin::new(&mut self.write_rx).poll_next(cx) {
             Poll::Pending => {}
             Poll::Ready(Some(m)) => return Poll::Ready(Ok(LoopEvent::ToWriteMessage(m))),
@@ -461,9 +522,41 @@ where
             Poll::Pending => {}
         }
 
+        let timers_state = ConnTimersState {
+            settings_received: self.settings_received,
+            header_block_pending: self.framed_read.is_header_block_pending(),
+            idle: self.streams.is_empty() && self.goaway_sent.is_none(),
+        };
+        if let Poll::Ready(event) = self.timers.poll(cx, timers_state) {
+            return Poll::Ready(Ok(LoopEvent::Timer(event)));
+        }
+
         Poll::Pending
     }
 
+    fn process_timer(&mut self, event: ConnTimerEvent) -> result::Result<()> {
+        match event {
+            ConnTimerEvent::Settings => Err(error::Error::HandshakeTimeout),
+            ConnTimerEvent::HeaderBlock => Err(error::Error::HeaderBlockTimeout),
+            ConnTimerEvent::Idle => {
+                info!("connection is idle, sending GOAWAY");
+                self.close_reason = Some(error::Error::IdleTimeout);
+                self.send_goaway(ErrorCode::NoError)
+            }
+            ConnTimerEvent::GracefulShutdownPing => {
+                debug!("graceful shutdown PING is not acknowledged in time");
+                self.graceful_shutdown_final_goaway()
+            }
+            ConnTimerEvent::Ping => {
+                if self.keepalive_ping_sent {
+                    return Err(error::Error::PingTimeout);
+                }
+                self.send_keepalive_ping();
+                Ok(())
+            }
+        }
+    }
+
     /// Each connection is a single future which polls event and processed them
     async fn next_event(&mut self) -> result::Result<LoopEvent<T>> {
         future::poll_fn(|cx| self.poll_next_event(cx)).await
@@ -475,7 +568,13 @@ where
             match event {
                 LoopEvent::ToWriteMessage(m) => self.process_message(m)?,
                 LoopEvent::Frame(f) => self.process_http_frame_of_goaway(f)?,
-                LoopEvent::ExitLoop => return Ok(()),
+                LoopEvent::Timer(e) => self.process_timer(e)?,
+                LoopEvent::ExitLoop => {
+                    return match self.close_reason.take() {
+                        Some(e) => Err(e),
+                        None => Ok(()),
+                    }
+                }
             }
         }
     }
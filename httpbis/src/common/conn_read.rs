@@ -1,6 +1,9 @@
 use crate::codec::http_decode_read::HttpFrameDecodedOrGoaway;
 use crate::common::conn::Conn;
+use crate::common::conn::GracefulShutdownStage;
 use crate::common::conn_write::ConnWriteSideCustom;
+use crate::common::conn_write::GRACEFUL_SHUTDOWN_PING_DATA;
+use crate::common::conn_write::KEEPALIVE_PING_DATA;
 use crate::common::init_where::InitWhere;
 use crate::common::stream::DroppedData;
 use crate::common::stream::HttpStreamCommon;
@@ -45,6 +48,20 @@ pub(crate) trait ConnReadSideCustom {
         end_stream: EndStream,
         headers: Headers,
     ) -> result::Result<Option<HttpStreamRef<Self::Types>>>;
+
+    /// Called when incoming `DATA` exceeds stream body size limit.
+    fn in_body_limit_exceeded(&mut self, stream_id: StreamId) -> result::Result<()>;
+
+    /// Called when decoded headers exceed `CommonConf::max_header_list_size`.
+    fn header_list_too_large(
+        &mut self,
+        stream_id: StreamId,
+        end_stream: EndStream,
+    ) -> result::Result<()>;
+
+    /// Called for each frame before it is processed.
+    /// Return `false` to drop the frame.
+    fn frame_received(&mut self, frame: &HttpFrameDecoded) -> result::Result<bool>;
 }
 
 impl<T, I> Conn<T, I>
@@ -85,7 +102,14 @@ where
                 None
             };
 
+        // connection window is replenished even if the stream is already gone
+        if let Some(increment_conn) = increment_conn {
+            let window_update = WindowUpdateFrame::for_connection(increment_conn);
+            self.send_frame_and_notify(window_update);
+        }
+
         let mut error = None;
+        let mut body_limit_exceeded = false;
 
         loop {
             // If a DATA frame is received whose stream is not in "open" or
@@ -111,6 +135,17 @@ where
                 stream.stream().in_rem_content_length = Some(in_rem_content_length);
             }
 
+            if let Some(in_rem_body_limit) = stream.stream().in_rem_body_limit {
+                if in_rem_body_limit < frame.data.len() as u64 {
+                    warn!("stream {} body exceeds limit", stream_id);
+                    body_limit_exceeded = true;
+                    break;
+                }
+
+                let in_rem_body_limit = in_rem_body_limit - frame.data.len() as u64;
+                stream.stream().in_rem_body_limit = Some(in_rem_body_limit);
+            }
+
             assert_eq!(
                 InMessageStage::AfterInitialHeaders,
                 stream.stream().in_message_stage
@@ -136,16 +171,16 @@ where
             break;
         }
 
-        if let Some(increment_conn) = increment_conn {
-            let window_update = WindowUpdateFrame::for_connection(increment_conn);
-            self.send_frame_and_notify(window_update);
-        }
-
         if let Some(error) = error {
             self.send_rst_stream(stream_id, error)?;
             return Ok(None);
         }
 
+        if body_limit_exceeded {
+            self.in_body_limit_exceeded(stream_id)?;
+            return Ok(None);
+        }
+
         Ok(Some(
             self.streams
                 .get_mut(stream_id)
@@ -155,6 +190,16 @@ where
 
     fn process_ping(&mut self, frame: PingFrame) -> result::Result<()> {
         if frame.is_ack() {
+            if self.keepalive_ping_sent && frame.opaque_data == KEEPALIVE_PING_DATA {
+                self.keepalive_ping_sent = false;
+                self.timers.ping_acked();
+                return Ok(());
+            }
+            if self.graceful_shutdown == Some(GracefulShutdownStage::WaitPingAck)
+                && frame.opaque_data == GRACEFUL_SHUTDOWN_PING_DATA
+            {
+                return self.graceful_shutdown_final_goaway();
+            }
             if let Some(opaque_data) = self.ping_sent.take() {
                 if opaque_data == frame.opaque_data {
                     Ok(())
@@ -176,8 +221,13 @@ where
     }
 
     fn process_goaway(&mut self, frame: GoawayFrame) -> result::Result<()> {
-        if let Some(..) = self.goaway_received {
-            return Err(error::Error::GoawayAfterGoaway);
+        // 6.8
+        // Endpoints MAY send multiple GOAWAY frames ...
+        // Endpoints MUST NOT increase the value they send in the last stream identifier
+        if let Some(ref prev) = self.goaway_received {
+            if frame.last_stream_id > prev.last_stream_id {
+                return Err(error::Error::GoawayAfterGoaway);
+            }
         }
 
         let last_stream_id = frame.last_stream_id;
@@ -228,6 +278,8 @@ where
     fn process_settings_req(&mut self, frame: SettingsFrame) -> result::Result<()> {
         assert!(!frame.is_ack());
 
+        self.settings_received = true;
+
         for setting in frame.settings {
             match setting {
                 HttpSetting::InitialWindowSize(new_size) => {
@@ -419,6 +471,9 @@ where
         } else {
             debug!("received frame: {:?}", frame.debug_no_data());
         }
+        if !self.frame_received(&frame)? {
+            return Ok(());
+        }
         match HttpFrameClassified::from(frame) {
             HttpFrameClassified::Conn(f) => self.process_conn_frame(f),
             HttpFrameClassified::Stream(f) => self.process_stream_frame(f),
@@ -455,6 +510,9 @@ where
                 self.process_stream_error(stream_id, error_code)
             }
             HttpFrameDecodedOrGoaway::SendGoaway(error_code) => self.send_goaway(error_code),
+            HttpFrameDecodedOrGoaway::HeaderListTooLarge(stream_id, end_stream) => {
+                self.header_list_too_large(stream_id, end_stream)
+            }
         }
     }
 }
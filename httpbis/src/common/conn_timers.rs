@@ -0,0 +1,152 @@
+//! Connection-level timers.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::time;
+use tokio::time::Delay;
+use tokio::time::Instant;
+
+/// Max time to wait for graceful shutdown `PING` ack,
+/// final `GOAWAY` is sent when it expires.
+const GRACEFUL_SHUTDOWN_PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Timeouts of established connection.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct ConnTimeouts {
+    /// Deadline for the first peer `SETTINGS` frame
+    pub settings_deadline: Option<Instant>,
+    /// Max time to receive a header block after the first `HEADERS` bytes
+    pub header_block: Option<Duration>,
+    /// Max time without streams
+    pub idle: Option<Duration>,
+    /// Interval of `PING` sent to check peer is alive
+    pub ping_interval: Option<Duration>,
+    /// Max time to wait for `PING` ack, defaults to `ping_interval`
+    pub ping_timeout: Option<Duration>,
+}
+
+/// Timer fired.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ConnTimerEvent {
+    Settings,
+    HeaderBlock,
+    Idle,
+    /// Time to send `PING` or `PING` ack not received in time
+    Ping,
+    /// Graceful shutdown `PING` ack not received in time
+    GracefulShutdownPing,
+}
+
+/// Connection state observed by timers.
+pub(crate) struct ConnTimersState {
+    pub settings_received: bool,
+    pub header_block_pending: bool,
+    pub idle: bool,
+}
+
+/// Timers armed according to `ConnTimeouts` and connection state.
+pub(crate) struct ConnTimers {
+    timeouts: ConnTimeouts,
+    settings: Option<Delay>,
+    header_block: Option<Delay>,
+    idle: Option<Delay>,
+    ping: Option<Delay>,
+    graceful_shutdown_ping: Option<Delay>,
+}
+
+fn poll_delay(delay: &mut Option<Delay>, cx: &mut Context<'_>) -> bool {
+    match delay {
+        Some(d) => Pin::new(d).poll(cx).is_ready(),
+        None => false,
+    }
+}
+
+impl ConnTimers {
+    pub fn new(timeouts: ConnTimeouts) -> ConnTimers {
+        ConnTimers {
+            settings: timeouts.settings_deadline.map(time::delay_until),
+            header_block: None,
+            idle: None,
+            ping: timeouts.ping_interval.map(time::delay_for),
+            graceful_shutdown_ping: None,
+            timeouts,
+        }
+    }
+
+    /// Arm or disarm timers according to the state and poll them.
+    pub fn poll(&mut self, cx: &mut Context<'_>, state: ConnTimersState) -> Poll<ConnTimerEvent> {
+        if state.settings_received {
+            self.settings = None;
+        }
+        if poll_delay(&mut self.settings, cx) {
+            self.settings = None;
+            return Poll::Ready(ConnTimerEvent::Settings);
+        }
+
+        match (state.header_block_pending, self.timeouts.header_block) {
+            (true, Some(timeout)) => {
+                if self.header_block.is_none() {
+                    self.header_block = Some(time::delay_for(timeout));
+                }
+            }
+            _ => self.header_block = None,
+        }
+        if poll_delay(&mut self.header_block, cx) {
+            self.header_block = None;
+            return Poll::Ready(ConnTimerEvent::HeaderBlock);
+        }
+
+        match (state.idle, self.timeouts.idle) {
+            (true, Some(timeout)) => {
+                if self.idle.is_none() {
+                    self.idle = Some(time::delay_for(timeout));
+                }
+            }
+            _ => self.idle = None,
+        }
+        if poll_delay(&mut self.idle, cx) {
+            self.idle = None;
+            return Poll::Ready(ConnTimerEvent::Idle);
+        }
+
+        if poll_delay(&mut self.ping, cx) {
+            return Poll::Ready(ConnTimerEvent::Ping);
+        }
+
+        if poll_delay(&mut self.graceful_shutdown_ping, cx) {
+            self.graceful_shutdown_ping = None;
+            return Poll::Ready(ConnTimerEvent::GracefulShutdownPing);
+        }
+
+        Poll::Pending
+    }
+
+    /// `PING` sent, wait for ack.
+    pub fn ping_sent(&mut self) {
+        if let (Some(ping), Some(interval)) = (&mut self.ping, self.timeouts.ping_interval) {
+            let timeout = self.timeouts.ping_timeout.unwrap_or(interval);
+            ping.reset(Instant::now() + timeout);
+        }
+    }
+
+    /// Graceful shutdown `PING` sent, wait for ack.
+    pub fn graceful_shutdown_ping_sent(&mut self) {
+        self.graceful_shutdown_ping = Some(time::delay_for(GRACEFUL_SHUTDOWN_PING_TIMEOUT));
+    }
+
+    /// Graceful shutdown `PING` ack received or timed out.
+    pub fn graceful_shutdown_ping_done(&mut self) {
+        self.graceful_shutdown_ping = None;
+    }
+
+    /// `PING` ack received, wait for the next interval.
+    pub fn ping_acked(&mut self) {
+        if let (Some(ping), Some(interval)) = (&mut self.ping, self.timeouts.ping_interval) {
+            ping.reset(Instant::now() + interval);
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::common::types::Types;
 use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
 
 use crate::common::conn::ConnStateSnapshot;
+use crate::common::conn::GracefulShutdownStage;
 use crate::common::conn_read::ConnReadSideCustom;
 use crate::common::pump_stream_to_write_loop::PumpStreamToWrite;
 use crate::common::stream::HttpStreamCommand;
@@ -21,9 +22,11 @@ use crate::solicit::frame::GoawayFrame;
 use crate::solicit::frame::HeadersFlag;
 use crate::solicit::frame::HeadersMultiFrame;
 use crate::solicit::frame::HttpFrame;
+use crate::solicit::frame::PingFrame;
 use crate::solicit::frame::RstStreamFrame;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::stream_id::StreamId;
+use crate::solicit::stream_id::MAX_STREAM_ID;
 use crate::ErrorCode;
 use crate::Headers;
 use crate::HttpStreamAfterHeaders;
@@ -35,6 +38,11 @@ use std::cmp;
 use crate::net::socket::SocketStream;
 use std::task::Poll;
 
+/// Opaque data of `PING` sent during graceful shutdown
+pub(crate) const GRACEFUL_SHUTDOWN_PING_DATA: u64 = 0x6874_7470_6269_7321;
+/// Opaque data of `PING` sent to check peer is alive
+pub(crate) const KEEPALIVE_PING_DATA: u64 = 0x6874_7470_6269_733f;
+
 pub(crate) trait ConnWriteSideCustom {
     type Types: Types;
 
@@ -42,6 +50,13 @@ pub(crate) trait ConnWriteSideCustom {
         &mut self,
         message: <Self::Types as Types>::ToWriteMessage,
     ) -> result::Result<()>;
+
+    /// Start streams postponed because of peer `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    /// Return `true` if any stream was started.
+    fn start_queued_streams(&mut self) -> result::Result<bool>;
+
+    /// Number of streams waiting to be started.
+    fn queued_streams_len(&self) -> usize;
 }
 
 impl<T, I> Conn<T, I>
@@ -93,7 +108,12 @@ where
         }
     }
 
-    fn write_part_headers(&mut self, stream_id: StreamId, headers: Headers, end_stream: EndStream) {
+    pub fn write_part_headers(
+        &mut self,
+        stream_id: StreamId,
+        headers: Headers,
+        end_stream: EndStream,
+    ) {
         let mut flags = Flags::new(0);
         if end_stream == EndStream::Yes {
             flags.set(HeadersFlag::EndStream);
@@ -265,6 +285,57 @@ where
         Ok(())
     }
 
+    /// Start graceful shutdown: send `GOAWAY` with max stream id
+    /// followed by `PING`, final `GOAWAY` is sent after `PING` ack
+    /// or when the ack is not received in time.
+    pub fn start_graceful_shutdown(&mut self) -> result::Result<()> {
+        if self.graceful_shutdown.is_some() {
+            return Ok(());
+        }
+
+        debug!("starting graceful shutdown");
+
+        self.send_goaway_not_final(MAX_STREAM_ID);
+
+        self.send_frame_and_notify(PingFrame::with_data(GRACEFUL_SHUTDOWN_PING_DATA));
+        self.timers.graceful_shutdown_ping_sent();
+
+        self.graceful_shutdown = Some(GracefulShutdownStage::WaitPingAck);
+        Ok(())
+    }
+
+    /// Send `PING` to check peer is alive.
+    pub fn send_keepalive_ping(&mut self) {
+        self.keepalive_ping_sent = true;
+        self.send_frame_and_notify(PingFrame::with_data(KEEPALIVE_PING_DATA));
+        self.timers.ping_sent();
+    }
+
+    /// `PING` ack received, so peer has seen the first `GOAWAY`,
+    /// or peer did not ack in time.
+    pub fn graceful_shutdown_final_goaway(&mut self) -> result::Result<()> {
+        if self.graceful_shutdown != Some(GracefulShutdownStage::WaitPingAck) {
+            return Ok(());
+        }
+        self.timers.graceful_shutdown_ping_done();
+
+        debug!(
+            "sending final GOAWAY with last stream id {}",
+            self.last_peer_stream_id
+        );
+
+        self.send_goaway_not_final(self.last_peer_stream_id);
+        self.graceful_shutdown = Some(GracefulShutdownStage::Drain);
+        Ok(())
+    }
+
+    /// Send `GOAWAY` which does not close the connection.
+    fn send_goaway_not_final(&mut self, last_stream_id: StreamId) {
+        let frame = GoawayFrame::new(last_stream_id, ErrorCode::NoError);
+        self.queued_write.queue_not_goaway(frame.clone());
+        self.goaway_sent = Some(frame);
+    }
+
     pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> result::Result<()> {
         self.buffer_outg_conn()?;
         loop {
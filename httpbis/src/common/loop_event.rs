@@ -1,8 +1,10 @@
 use crate::codec::http_decode_read::HttpFrameDecodedOrGoaway;
+use crate::common::conn_timers::ConnTimerEvent;
 use crate::common::types::Types;
 
 pub(crate) enum LoopEvent<T: Types> {
     ToWriteMessage(T::ToWriteMessage),
     Frame(HttpFrameDecodedOrGoaway),
+    Timer(ConnTimerEvent),
     ExitLoop,
 }
@@ -7,6 +7,7 @@ pub(crate) mod conf;
 pub(crate) mod conn;
 pub(crate) mod conn_command_channel;
 pub(crate) mod conn_read;
+pub(crate) mod conn_timers;
 pub(crate) mod conn_write;
 pub(crate) mod hash_set_shallow_clone;
 pub(crate) mod increase_in_window;
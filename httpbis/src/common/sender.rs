@@ -83,6 +83,23 @@ impl<T: Types> CommonSender<T> {
         }
     }
 
+    /// Stream is finished locally, reset or the connection died
+    pub fn is_closed(&self) -> bool {
+        match self.state {
+            Some(ref state) => state.out_window.is_closed(),
+            None => true,
+        }
+    }
+
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    /// Connection command channel, available until the stream is finished
+    pub fn write_tx(&mut self) -> Result<&ConnCommandSender<T>, SendError> {
+        Ok(&self.get_can_send()?.write_tx)
+    }
+
     pub fn state(&self) -> SenderState {
         match self.state {
             Some(CanSendData {
@@ -74,6 +74,8 @@ pub(crate) struct HttpStreamCommon<T: Types> {
     pub pump_out_window: window_size::StreamOutWindowSender,
     // Incoming remaining content-length
     pub in_rem_content_length: Option<u64>,
+    // Incoming remaining body size limit
+    pub in_rem_body_limit: Option<u64>,
     pub in_message_stage: InMessageStage,
 }
 
@@ -95,6 +97,7 @@ impl<T: Types> HttpStreamCommon<T> {
             peer_tx: None,
             pump_out_window,
             in_rem_content_length,
+            in_rem_body_limit: None,
             in_message_stage,
         }
     }
@@ -259,6 +262,7 @@ impl<T: Types> HttpStreamCommon<T> {
     }
 
     pub fn rst_recvd(&mut self, error_code: ErrorCode) -> DroppedData {
+        self.specific.rst_recvd(error_code);
         if let Some(response_handler) = self.peer_tx.take() {
             drop(response_handler.rst(error_code));
         }
@@ -268,6 +272,7 @@ impl<T: Types> HttpStreamCommon<T> {
     }
 
     pub fn goaway_recvd(&mut self, _raw_error_code: u32) {
+        self.specific.conn_died(error::Error::GoawayReceived);
         if let Some(response_handler) = self.peer_tx.take() {
             // it is OK to ignore error: handler may be already dead
             drop(response_handler.error(error::Error::GoawayReceived));
@@ -275,7 +280,13 @@ impl<T: Types> HttpStreamCommon<T> {
     }
 }
 
-pub(crate) trait HttpStreamDataSpecific: Send + 'static {}
+pub(crate) trait HttpStreamDataSpecific: Send + 'static {
+    /// Called when peer reset the stream.
+    fn rst_recvd(&mut self, _error_code: ErrorCode) {}
+
+    /// Called when the stream is removed because connection is closing.
+    fn conn_died(&mut self, _error: error::Error) {}
+}
 
 pub(crate) trait HttpStreamData {
     type Types: Types;
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 
 use super::stream::HttpStreamCommand;
 use super::stream::HttpStreamCommon;
+use super::stream::HttpStreamDataSpecific;
 use super::stream::HttpStreamStateSnapshot;
 use super::types::Types;
 use crate::common::hash_set_shallow_clone::HashSetShallowClone;
@@ -127,6 +128,26 @@ impl<T: Types> StreamMap<T> {
         self.map.is_empty()
     }
 
+    /// Number of streams initiated by given side.
+    pub fn len_initiated(&self, init_where: InitWhere) -> usize {
+        self.map
+            .keys()
+            .filter(|&&s| T::init_where(s) == init_where)
+            .count()
+    }
+
+    /// Number of streams initiated by given side and not closed by peer.
+    pub fn len_initiated_open(&self, init_where: InitWhere) -> usize {
+        self.map
+            .iter()
+            .filter(|&(&s, stream)| {
+                T::init_where(s) == init_where
+                    && stream.state != StreamState::HalfClosedRemote
+                    && stream.state != StreamState::Closed
+            })
+            .count()
+    }
+
     pub fn _stream_ids(&self) -> Vec<StreamId> {
         self.map.keys().cloned().collect()
     }
@@ -143,7 +164,8 @@ impl<T: Types> StreamMap<T> {
     where
         F: Fn() -> error::Error,
     {
-        for (_, s) in self.map.drain() {
+        for (_, mut s) in self.map.drain() {
+            s.specific.conn_died(error());
             s.conn_died(error());
         }
     }
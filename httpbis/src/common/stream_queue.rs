@@ -21,6 +21,8 @@ pub struct StreamQueue {
     // Some(NoError) means data is successfully generated
     end: Option<ErrorCode>,
     data_size: usize,
+    // anything was pushed or queue was closed
+    started: bool,
 }
 
 impl StreamQueue {
@@ -29,6 +31,7 @@ impl StreamQueue {
             queue: VecDeque::new(),
             end: None,
             data_size: 0,
+            started: false,
         }
     }
 
@@ -44,6 +47,7 @@ impl StreamQueue {
         if let Some(_) = self.end {
             return;
         }
+        self.started = true;
         self.data_size += data_size(&part);
         self.queue.push_back(part);
     }
@@ -74,11 +78,17 @@ impl StreamQueue {
     }
 
     pub fn close(&mut self, error_code: ErrorCode) {
+        self.started = true;
         if None == self.end {
             self.end = Some(error_code);
         }
     }
 
+    /// True if response was started or finished.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
     pub fn end(&self) -> Option<ErrorCode> {
         if !self.is_empty() {
             None
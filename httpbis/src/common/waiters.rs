@@ -14,6 +14,7 @@ struct WakerShared {
     waiters: Mutex<Vec<Arc<WaiterShared>>>,
 }
 
+#[derive(Clone)]
 pub struct Waker {
     shared: Arc<WakerShared>,
 }
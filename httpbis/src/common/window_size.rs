@@ -59,17 +59,14 @@ pub struct StreamOutWindowReceiver {
     shared: Arc<StreamWindowShared>,
 }
 
-impl ConnOutWindowSender {
-    pub fn new(size: u32) -> ConnOutWindowSender {
-        ConnOutWindowSender {
-            waker: Waker::new(),
-            shared: Arc::new(ConnOutWindowShared {
-                window_size: AtomicIsize::new(size as isize),
-                closed: AtomicBool::new(false),
-            }),
-        }
-    }
+/// Does not close the connection window when dropped.
+#[derive(Clone)]
+pub struct ConnOutWindowStreams {
+    waker: Waker,
+    shared: Arc<ConnOutWindowShared>,
+}
 
+impl ConnOutWindowStreams {
     pub fn new_stream(&self, initial: u32) -> (StreamOutWindowSender, StreamOutWindowReceiver) {
         let shared = Arc::new(StreamWindowShared {
             conn: self.shared.clone(),
@@ -87,6 +84,30 @@ impl ConnOutWindowSender {
         };
         (sender, receiver)
     }
+}
+
+impl ConnOutWindowSender {
+    pub fn new(size: u32) -> ConnOutWindowSender {
+        ConnOutWindowSender {
+            waker: Waker::new(),
+            shared: Arc::new(ConnOutWindowShared {
+                window_size: AtomicIsize::new(size as isize),
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    pub fn new_stream(&self, initial: u32) -> (StreamOutWindowSender, StreamOutWindowReceiver) {
+        self.streams().new_stream(initial)
+    }
+
+    /// Handle to create stream windows outside of the connection loop.
+    pub fn streams(&self) -> ConnOutWindowStreams {
+        ConnOutWindowStreams {
+            waker: self.waker.clone(),
+            shared: self.shared.clone(),
+        }
+    }
 
     pub fn get(&self) -> isize {
         self.shared.window_size.load(Ordering::SeqCst) as isize
@@ -152,6 +173,11 @@ impl StreamOutWindowReceiver {
             .fetch_sub(size as isize, Ordering::SeqCst);
     }
 
+    /// Stream was removed from the connection or the connection died.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst) || self.check_conn_closed().is_err()
+    }
+
     fn check_conn_closed(&self) -> Result<(), ConnDead> {
         if self.shared.conn.closed.load(Ordering::Relaxed) {
             Err(ConnDead)
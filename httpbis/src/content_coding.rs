@@ -0,0 +1,362 @@
+//! `gzip` and `deflate` content codings of message body.
+
+use std::io;
+use std::io::Write;
+use std::mem;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::Bytes;
+use flate2::write::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::write::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use futures::stream::Stream;
+use futures::stream::StreamExt;
+
+use crate::data_or_trailers::DataOrTrailers;
+use crate::result;
+use crate::solicit::end_stream::EndStream;
+use crate::Headers;
+use crate::HttpStreamAfterHeaders;
+
+/// Content coding supported by this crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ContentCoding {
+    Gzip,
+    /// `zlib` format (RFC 1950), as specified for HTTP `deflate` coding
+    Deflate,
+}
+
+impl ContentCoding {
+    /// Preferred first when client accepts several codings with the same weight.
+    const ALL: [ContentCoding; 2] = [ContentCoding::Gzip, ContentCoding::Deflate];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    /// Parse `content-encoding` header value.
+    pub fn parse(name: &str) -> Option<ContentCoding> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentCoding::Gzip)
+        } else if name.eq_ignore_ascii_case("deflate") {
+            Some(ContentCoding::Deflate)
+        } else {
+            None
+        }
+    }
+
+    /// Select coding from request `accept-encoding` header value.
+    ///
+    /// Coding with the highest weight wins, codings with `q=0` are never selected.
+    pub fn negotiate(accept_encoding: &str) -> Option<ContentCoding> {
+        // weight for each coding and for `*`
+        let mut weights = [None; 2];
+        let mut any = None;
+        for item in accept_encoding.split(',') {
+            let mut parts = item.split(';');
+            let name = parts.next().unwrap().trim();
+            let mut q = 1.0;
+            for param in parts {
+                let mut kv = param.splitn(2, '=');
+                let key = kv.next().unwrap().trim();
+                if key.eq_ignore_ascii_case("q") {
+                    q = kv
+                        .next()
+                        .and_then(|v| v.trim().parse::<f32>().ok())
+                        .unwrap_or(0.0);
+                }
+            }
+            if name == "*" {
+                any = Some(q);
+            } else if let Some(coding) = ContentCoding::parse(name) {
+                let i = ContentCoding::ALL
+                    .iter()
+                    .position(|c| *c == coding)
+                    .unwrap();
+                weights[i] = Some(q);
+            }
+        }
+
+        let mut best: Option<(ContentCoding, f32)> = None;
+        for (coding, q) in ContentCoding::ALL.iter().zip(weights.iter()) {
+            let q = match q.or(any) {
+                Some(q) if q > 0.0 => q,
+                _ => continue,
+            };
+            if best.iter().all(|&(_, best_q)| q > best_q) {
+                best = Some((*coding, q));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+}
+
+fn take_buf(buf: &mut Vec<u8>) -> Bytes {
+    Bytes::from(mem::take(buf))
+}
+
+/// Transformation of body chunks.
+pub(crate) trait BodyCoder: Send + 'static {
+    /// Transform a chunk, returning output available so far.
+    fn code(&mut self, data: &[u8]) -> result::Result<Bytes>;
+    /// Complete the body, returning the remaining output.
+    fn finish(&mut self) -> result::Result<Bytes>;
+}
+
+enum BodyEncoderImpl {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+/// Compressor of the body.
+///
+/// Every chunk is flushed, so buffered output never exceeds
+/// the size of a compressed chunk.
+pub(crate) struct BodyEncoder(BodyEncoderImpl);
+
+impl BodyEncoder {
+    pub fn new(coding: ContentCoding) -> BodyEncoder {
+        BodyEncoder(match coding {
+            ContentCoding::Gzip => {
+                BodyEncoderImpl::Gzip(GzEncoder::new(Vec::new(), Compression::default()))
+            }
+            ContentCoding::Deflate => {
+                BodyEncoderImpl::Deflate(ZlibEncoder::new(Vec::new(), Compression::default()))
+            }
+        })
+    }
+
+    fn encode_impl(&mut self, data: &[u8]) -> io::Result<Bytes> {
+        if data.is_empty() {
+            return Ok(Bytes::new());
+        }
+        Ok(match self.0 {
+            BodyEncoderImpl::Gzip(ref mut e) => {
+                e.write_all(data)?;
+                e.flush()?;
+                take_buf(e.get_mut())
+            }
+            BodyEncoderImpl::Deflate(ref mut e) => {
+                e.write_all(data)?;
+                e.flush()?;
+                take_buf(e.get_mut())
+            }
+        })
+    }
+
+    fn finish_impl(&mut self) -> io::Result<Bytes> {
+        Ok(match self.0 {
+            BodyEncoderImpl::Gzip(ref mut e) => {
+                e.try_finish()?;
+                take_buf(e.get_mut())
+            }
+            BodyEncoderImpl::Deflate(ref mut e) => {
+                e.try_finish()?;
+                take_buf(e.get_mut())
+            }
+        })
+    }
+
+    // compression into memory buffer cannot fail
+    pub fn encode(&mut self, data: &[u8]) -> Bytes {
+        self.encode_impl(data).expect("compress")
+    }
+
+    pub fn finish(&mut self) -> Bytes {
+        self.finish_impl().expect("compress")
+    }
+}
+
+impl BodyCoder for BodyEncoder {
+    fn code(&mut self, data: &[u8]) -> result::Result<Bytes> {
+        Ok(self.encode(data))
+    }
+
+    fn finish(&mut self) -> result::Result<Bytes> {
+        Ok(BodyEncoder::finish(self))
+    }
+}
+
+enum BodyDecoderImpl {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>),
+}
+
+/// Decompressor of the body.
+pub(crate) struct BodyDecoder(BodyDecoderImpl);
+
+impl BodyDecoder {
+    pub fn new(coding: ContentCoding) -> BodyDecoder {
+        BodyDecoder(match coding {
+            ContentCoding::Gzip => BodyDecoderImpl::Gzip(GzDecoder::new(Vec::new())),
+            ContentCoding::Deflate => BodyDecoderImpl::Deflate(ZlibDecoder::new(Vec::new())),
+        })
+    }
+}
+
+impl BodyCoder for BodyDecoder {
+    fn code(&mut self, data: &[u8]) -> result::Result<Bytes> {
+        Ok(match self.0 {
+            BodyDecoderImpl::Gzip(ref mut d) => {
+                d.write_all(data)?;
+                take_buf(d.get_mut())
+            }
+            BodyDecoderImpl::Deflate(ref mut d) => {
+                d.write_all(data)?;
+                take_buf(d.get_mut())
+            }
+        })
+    }
+
+    fn finish(&mut self) -> result::Result<Bytes> {
+        Ok(match self.0 {
+            BodyDecoderImpl::Gzip(ref mut d) => {
+                d.try_finish()?;
+                take_buf(d.get_mut())
+            }
+            BodyDecoderImpl::Deflate(ref mut d) => {
+                d.try_finish()?;
+                take_buf(d.get_mut())
+            }
+        })
+    }
+}
+
+/// Stream with DATA transformed by coder, trailers are passed as is.
+struct CodedStream<C: BodyCoder> {
+    inner: HttpStreamAfterHeaders,
+    /// `None` after body is finished
+    coder: Option<C>,
+    /// Trailers to be returned after the remaining coder output
+    trailers: Option<Headers>,
+}
+
+// fields are never pinned
+impl<C: BodyCoder> Unpin for CodedStream<C> {}
+
+impl<C: BodyCoder> Stream for CodedStream<C> {
+    type Item = result::Result<DataOrTrailers>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<result::Result<DataOrTrailers>>> {
+        let this = &mut *self;
+        loop {
+            if let Some(trailers) = this.trailers.take() {
+                return Poll::Ready(Some(Ok(DataOrTrailers::Trailers(trailers))));
+            }
+
+            let coder = match this.coder {
+                Some(ref mut coder) => coder,
+                None => return this.inner.0.poll_next_unpin(cx),
+            };
+
+            let part = match this.inner.0.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(part) => part,
+            };
+
+            let r = match part {
+                Some(Ok(DataOrTrailers::Data(data, EndStream::No))) => {
+                    match coder.code(&data) {
+                        // do not send empty frames
+                        Ok(ref data) if data.is_empty() => continue,
+                        Ok(data) => Ok(DataOrTrailers::Data(data, EndStream::No)),
+                        Err(e) => Err(e),
+                    }
+                }
+                Some(Ok(DataOrTrailers::Data(data, EndStream::Yes))) => {
+                    let r = coder.code(&data).and_then(|data| {
+                        let tail = coder.finish()?;
+                        Ok(DataOrTrailers::Data(
+                            [&data[..], &tail[..]].concat().into(),
+                            EndStream::Yes,
+                        ))
+                    });
+                    this.coder = None;
+                    r
+                }
+                Some(Ok(DataOrTrailers::Trailers(trailers))) => {
+                    let r = coder.finish();
+                    this.coder = None;
+                    this.trailers = Some(trailers);
+                    r.map(|tail| DataOrTrailers::Data(tail, EndStream::No))
+                }
+                Some(Err(e)) => Err(e),
+                None => {
+                    let r = coder.finish();
+                    this.coder = None;
+                    r.map(|tail| DataOrTrailers::Data(tail, EndStream::Yes))
+                }
+            };
+            return Poll::Ready(Some(r));
+        }
+    }
+}
+
+/// Transform DATA of the stream with given coder.
+pub(crate) fn code_stream<C: BodyCoder>(
+    stream: HttpStreamAfterHeaders,
+    coder: C,
+) -> HttpStreamAfterHeaders {
+    HttpStreamAfterHeaders::new(CodedStream {
+        inner: stream,
+        coder: Some(coder),
+        trailers: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        assert_eq!(None, ContentCoding::negotiate(""));
+        assert_eq!(None, ContentCoding::negotiate("identity, br"));
+        assert_eq!(
+            Some(ContentCoding::Gzip),
+            ContentCoding::negotiate("deflate, gzip")
+        );
+        assert_eq!(
+            Some(ContentCoding::Deflate),
+            ContentCoding::negotiate("gzip;q=0.5, deflate")
+        );
+        assert_eq!(None, ContentCoding::negotiate("gzip;q=0, deflate;q=0"));
+        assert_eq!(Some(ContentCoding::Gzip), ContentCoding::negotiate("*"));
+        assert_eq!(
+            Some(ContentCoding::Deflate),
+            ContentCoding::negotiate("gzip;q=0, *")
+        );
+    }
+
+    #[test]
+    fn encode_decode() {
+        for &coding in &ContentCoding::ALL {
+            let mut encoder = BodyEncoder::new(coding);
+            let mut decoder = BodyDecoder::new(coding);
+            let mut decoded = Vec::new();
+            for _ in 0..3 {
+                let chunk = encoder.encode(&[b'a'; 1000]);
+                assert!(!chunk.is_empty());
+                assert!(chunk.len() < 1000);
+                // every chunk is flushed
+                decoded.extend_from_slice(&decoder.code(&chunk).unwrap());
+                assert_eq!(decoded.len() % 1000, 0);
+            }
+            let tail = encoder.finish();
+            decoded.extend_from_slice(&decoder.code(&tail).unwrap());
+            decoded.extend_from_slice(&BodyCoder::finish(&mut decoder).unwrap());
+            assert_eq!(vec![b'a'; 3000], decoded);
+        }
+    }
+}
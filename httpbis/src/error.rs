@@ -103,6 +103,11 @@ pub enum Error {
     ExpectingContinuationGotDifferentStreamId(StreamId, StreamId),
     /// `CONTINUATION` frame without headers.
     ContinuationFrameWithoutHeaders,
+    /// Header block exceeds `CommonConf::max_header_block_size`
+    /// or `CommonConf::max_continuation_frames`.
+    HeaderBlockTooLarge,
+    /// Header list exceeds `CommonConf::max_header_list_size`.
+    HeaderListTooLarge,
     /// Wrong stream id.
     InitiatedStreamWithServerIdFromClient(StreamId),
     /// Wrong stream id.
@@ -135,8 +140,50 @@ pub enum Error {
     PayloadTooLarge(u32, u32),
     /// Request is made using HTTP/1
     RequestIsMadeUsingHttp1,
+    /// Malformed HTTP/1 request.
+    InvalidHttp1Request(String),
     /// Listen address is not specified.
     ListenAddrNotSpecified,
+    /// `ServerConf::max_conns_per_ip` is set together with `proxy_protocol`.
+    MaxConnsPerIpWithProxyProtocol,
+    /// Too many requests are waiting for peer `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    RequestQueueFull,
+    /// Request waited for peer `SETTINGS_MAX_CONCURRENT_STREAMS` too long.
+    RequestQueueTimeout,
+    /// TLS parameter cannot be configured with the TLS backend.
+    TlsFeatureNotSupported(&'static str),
+    /// Peer disabled server push with `SETTINGS_ENABLE_PUSH`.
+    PushDisabled,
+    /// Pushed streams reached peer `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    PushConcurrencyLimit(u32),
+    /// `PUSH_PROMISE` can only be sent on client-initiated stream.
+    PushPromiseOnPushedStream,
+    /// Server push is only available in HTTP/2.
+    PushNotSupported,
+    /// `PUSH_PROMISE` is not sent after `GOAWAY`.
+    PushPromiseAfterGoaway,
+    /// Parent stream of `PUSH_PROMISE` is not open.
+    PushPromiseOnClosedStream(StreamId),
+    /// Promised request must be a valid `GET` or `HEAD` request.
+    InvalidPushPromiseHeaders,
+    /// Request body exceeds configured limit.
+    RequestBodyTooLarge,
+    /// Connection handshake (TLS, preface and the first `SETTINGS`) is not completed in time.
+    HandshakeTimeout,
+    /// Header block is not completed in time.
+    HeaderBlockTimeout,
+    /// Connection had no streams for too long.
+    IdleTimeout,
+    /// `PING` is not acknowledged in time.
+    PingTimeout,
+    /// Client exceeded `ServerConf` rate limit of frames of this type.
+    FrameRateLimitExceeded(HttpFrameType),
+    /// Server has no TLS listener with given index.
+    NotTlsListener(usize),
+    /// No TLS acceptor for server name sent by client.
+    UnknownTlsServerName(Option<String>),
+    /// Malformed or missing PROXY protocol header.
+    InvalidProxyHeader(String),
 }
 
 fn _assert_error_sync_send() {
@@ -246,6 +293,8 @@ impl fmt::Display for Error {
                 HttpFrameType::Continuation,
                 HttpFrameType::Headers
             ),
+            Error::HeaderBlockTooLarge => write!(f, "Header block too large"),
+            Error::HeaderListTooLarge => write!(f, "Header list too large"),
             Error::InitiatedStreamWithServerIdFromClient(stream_id) => write!(
                 f,
                 "Initiated stream with server id from client: {}",
@@ -278,7 +327,55 @@ impl fmt::Display for Error {
             Error::PullStreamDied => write!(f, "Pull stream died"),
             Error::PayloadTooLarge(_, _) => write!(f, "Payload too large"),
             Error::RequestIsMadeUsingHttp1 => write!(f, "Request is made using HTTP/1"),
+            Error::InvalidHttp1Request(ref message) => {
+                write!(f, "Invalid HTTP/1 request: {}", message)
+            }
             Error::ListenAddrNotSpecified => write!(f, "Listen addr not specified"),
+            Error::MaxConnsPerIpWithProxyProtocol => {
+                write!(f, "max_conns_per_ip cannot be used with PROXY protocol")
+            }
+            Error::RequestQueueFull => write!(f, "Request queue is full"),
+            Error::RequestQueueTimeout => write!(f, "Request queue timeout"),
+            Error::TlsFeatureNotSupported(feature) => {
+                write!(f, "TLS backend does not support {}", feature)
+            }
+            Error::PushDisabled => write!(f, "Peer disabled server push"),
+            Error::PushConcurrencyLimit(limit) => {
+                write!(f, "Pushed streams limit {} reached", limit)
+            }
+            Error::PushPromiseOnPushedStream => write!(
+                f,
+                "{} can only be sent on client-initiated stream",
+                HttpFrameType::PushPromise
+            ),
+            Error::PushNotSupported => write!(f, "Server push is not supported in HTTP/1"),
+            Error::PushPromiseAfterGoaway => {
+                write!(f, "{} after GOAWAY", HttpFrameType::PushPromise)
+            }
+            Error::PushPromiseOnClosedStream(stream_id) => write!(
+                f,
+                "{} on stream {} which is not open",
+                HttpFrameType::PushPromise,
+                stream_id
+            ),
+            Error::InvalidPushPromiseHeaders => {
+                write!(f, "Invalid {} request headers", HttpFrameType::PushPromise)
+            }
+            Error::RequestBodyTooLarge => write!(f, "Request body too large"),
+            Error::HandshakeTimeout => write!(f, "Handshake timeout"),
+            Error::HeaderBlockTimeout => write!(f, "Header block timeout"),
+            Error::IdleTimeout => write!(f, "Connection idle timeout"),
+            Error::PingTimeout => write!(f, "{} ack timeout", HttpFrameType::Ping),
+            Error::FrameRateLimitExceeded(t) => write!(f, "{} frame rate limit exceeded", t),
+            Error::NotTlsListener(listener) => {
+                write!(f, "Server listener {} is not a TLS listener", listener)
+            }
+            Error::UnknownTlsServerName(ref name) => {
+                write!(f, "No TLS acceptor for server name {:?}", name)
+            }
+            Error::InvalidProxyHeader(ref message) => {
+                write!(f, "Invalid PROXY header: {}", message)
+            }
         }
     }
 }
@@ -195,6 +195,9 @@ pub enum DecoderError {
     /// made by SizeUpdate blocks).
     InvalidMaxDynamicSize(u32, u32),
     SizeUpdateMustBeFirstField,
+    /// Decoded header list is larger than `Decoder::set_max_header_list_size`.
+    /// Whole block is decoded, so decoder can be used for subsequent blocks.
+    HeaderListTooLarge,
 }
 
 /// The result returned by the `decode` method of the `Decoder`.
@@ -210,6 +213,8 @@ pub struct Decoder {
     header_table: HeaderTable,
     // Max configured size
     max_size: u32,
+    // Max size of decoded header list
+    max_header_list_size: u32,
 }
 
 /// Represents a decoder of HPACK encoded headers. Maintains the state
@@ -233,6 +238,7 @@ impl Decoder {
         Decoder {
             header_table: HeaderTable::with_static_table(static_table),
             max_size: 4096,
+            max_header_list_size: u32::MAX,
         }
     }
 
@@ -244,6 +250,12 @@ impl Decoder {
             .set_max_table_size(new_max_size);
     }
 
+    /// Sets maximum size of decoded header list as defined
+    /// for `SETTINGS_MAX_HEADER_LIST_SIZE`.
+    pub fn set_max_header_list_size(&mut self, max_header_list_size: u32) {
+        self.max_header_list_size = max_header_list_size;
+    }
+
     /// Decodes the headers found in the given buffer `buf`. Invokes the callback `cb` for each
     /// decoded header in turn, by providing it the header name and value as `Cow` byte array
     /// slices.
@@ -259,12 +271,29 @@ impl Decoder {
     ///
     /// If an error is encountered during the decoding of any header, decoding halts and the
     /// appropriate error is returned as the `Err` variant of the `Result`.
+    ///
+    /// If decoded header list exceeds max header list size, the callback is not invoked
+    /// for the remaining headers, but the block is still decoded to keep header table in sync,
+    /// and `HeaderListTooLarge` is returned.
     pub fn decode_with_cb<F>(&mut self, mut buf: Bytes, mut cb: F) -> Result<(), DecoderError>
     where
         F: FnMut(Bytes, Bytes),
     {
         let mut current_size_update = true;
 
+        let max_header_list_size = self.max_header_list_size as u64;
+        let mut header_list_size = 0;
+        let mut cb = |name: Bytes, value: Bytes| {
+            // 6.5.2
+            // The size of a header list is calculated based on the uncompressed
+            // size of header fields, including the length of the name and value
+            // in octets plus an overhead of 32 octets for each header field.
+            header_list_size += name.len() as u64 + value.len() as u64 + 32;
+            if header_list_size <= max_header_list_size {
+                cb(name, value);
+            }
+        };
+
         while buf.has_remaining() {
             // At this point we are always at the beginning of the next block
             // within the HPACK data.
@@ -328,6 +357,10 @@ impl Decoder {
             }
         }
 
+        if header_list_size > max_header_list_size {
+            return Err(DecoderError::HeaderListTooLarge);
+        }
+
         Ok(())
     }
 
@@ -762,6 +795,36 @@ mod tests {
         assert_eq!(actual, expected_table);
     }
 
+    /// Tests that the block exceeding max header list size is still
+    /// added to the dynamic table.
+    #[test]
+    fn test_decode_header_list_too_large() {
+        let mut decoder = Decoder::new();
+        // "custom-key" and "custom-header" take 10 + 13 + 32 bytes
+        decoder.set_max_header_list_size(54);
+        let hex_dump = [
+            0x40, 0x0a, 0x63, 0x75, 0x73, 0x74, 0x6f, 0x6d, 0x2d, 0x6b, 0x65, 0x79, 0x0d, 0x63,
+            0x75, 0x73, 0x74, 0x6f, 0x6d, 0x2d, 0x68, 0x65, 0x61, 0x64, 0x65, 0x72,
+        ];
+
+        assert_eq!(
+            Err(DecoderError::HeaderListTooLarge),
+            decoder.decode_for_test(&hex_dump)
+        );
+        assert_eq!(decoder.header_table.dynamic_table.len(), 1);
+
+        decoder.set_max_header_list_size(55);
+        // indexed from the dynamic table
+        let header_list = decoder.decode_for_test(&[0xbe]).unwrap();
+        assert_eq!(
+            header_list,
+            [(
+                Bytes::from(&b"custom-key"[..]),
+                Bytes::from(&b"custom-header"[..])
+            ),]
+        );
+    }
+
     /// Tests that a header with a name indexed from the dynamic table and a
     /// literal value is correctly decoded.
     #[test]
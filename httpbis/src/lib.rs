@@ -25,6 +25,7 @@ mod ascii;
 
 mod client_died_error_holder;
 mod common;
+mod content_coding;
 
 mod data_or_headers;
 mod data_or_headers_with_flag;
@@ -56,6 +57,7 @@ pub(crate) mod net;
 pub(crate) mod bytes_ext;
 
 pub use crate::net::addr::AnySocketAddr;
+pub use crate::net::unix::SocketAddrUnix;
 
 pub use crate::solicit::error_code::ErrorCode;
 pub use crate::solicit::header::name::HeaderName;
@@ -68,24 +70,49 @@ pub use crate::solicit::HttpScheme;
 
 pub use crate::client::conf::ClientConf;
 pub use crate::client::req::ClientRequest;
+pub use crate::client::tls::ClientTlsConf;
+pub use crate::client::tls::ClientTlsIdentity;
 pub use crate::client::tls::ClientTlsOption;
 pub use crate::client::Client;
 pub use crate::client::ClientBuilder;
 pub use crate::client::ClientInterface;
+pub use crate::common::conf::CommonConf;
 pub use crate::common::sender::SendError;
 pub use crate::common::sender::SenderState;
 pub use crate::common::window_size::StreamDead;
 
+pub use crate::server::cancel::ServerCancelReason;
+pub use crate::server::cancel::ServerCancelled;
 pub use crate::server::conf::ServerAlpn;
 pub use crate::server::conf::ServerConf;
+pub use crate::server::conf::ServerConnLoopSelection;
+pub use crate::server::conf::ServerFrameRateLimit;
+pub use crate::server::conn_info::ServerConnInfo;
+pub use crate::server::conn_loops::ConnLoopStateSnapshot;
+pub use crate::server::extensions::Extensions;
 pub use crate::server::handler::ServerHandler;
 pub use crate::server::handler::ServerHandlerContext;
+pub use crate::server::handler_async::AsyncServerHandler;
+pub use crate::server::handler_async::AsyncServerHandlerAdapter;
+pub use crate::server::handler_compression::ServerHandlerCompressionLayer;
+pub use crate::server::handler_files::StaticFilesHandler;
+pub use crate::server::handler_hosts::ServerHandlerHosts;
+pub use crate::server::handler_layer::ServerHandlerLayer;
+pub use crate::server::handler_layer::ServerHandlerLoggingLayer;
+pub use crate::server::handler_layer::ServerHandlerTimeoutLayer;
+pub use crate::server::handler_paths::PathParams;
 pub use crate::server::handler_paths::ServerHandlerPaths;
 pub use crate::server::increase_in_window::ServerIncreaseInWindow;
+pub use crate::server::listener::ServerListener;
+pub use crate::server::listener::ServerSocket;
+pub use crate::server::proxy_protocol::ProxyHeader;
+pub use crate::server::proxy_protocol::ProxyTlv;
 pub use crate::server::req::ServerRequest;
 pub use crate::server::resp::ServerResponse;
 pub use crate::server::stream_handler::ServerRequestStreamHandler;
+pub use crate::server::tls::ServerTlsHandle;
 pub use crate::server::tls::ServerTlsOption;
+pub use crate::server::tls::ServerTlsSniMap;
 pub use crate::server::Server;
 pub use crate::server::ServerBuilder;
 
@@ -2,6 +2,7 @@
 pub(crate) mod addr;
 pub(crate) mod connect;
 pub(crate) mod listen;
+pub(crate) mod prefixed;
 pub(crate) mod socket;
 pub(crate) mod tcp;
 pub(crate) mod tls;
@@ -0,0 +1,75 @@
+use std::io;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::Buf;
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use crate::net::socket::SocketStream;
+use crate::AnySocketAddr;
+
+/// Socket which returns already read bytes before reading from the socket.
+///
+/// Used to continue as HTTP/2 connection after data is read by HTTP/1 code.
+#[derive(Debug)]
+pub(crate) struct PrefixedSocket<S: SocketStream> {
+    prefix: BytesMut,
+    socket: S,
+}
+
+impl<S: SocketStream> PrefixedSocket<S> {
+    pub fn new(prefix: BytesMut, socket: S) -> PrefixedSocket<S> {
+        PrefixedSocket { prefix, socket }
+    }
+}
+
+impl<S: SocketStream> AsyncRead for PrefixedSocket<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        if !self.prefix.is_empty() {
+            let len = self.prefix.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.prefix[..len]);
+            self.prefix.advance(len);
+            return Poll::Ready(Ok(len));
+        }
+        Pin::new(&mut self.socket).poll_read(cx, buf)
+    }
+}
+
+impl<S: SocketStream> AsyncWrite for PrefixedSocket<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.socket).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_shutdown(cx)
+    }
+}
+
+impl<S: SocketStream> SocketStream for PrefixedSocket<S> {
+    fn is_tcp(&self) -> bool {
+        self.socket.is_tcp()
+    }
+
+    fn set_tcp_nodelay(&self, no_delay: bool) -> io::Result<()> {
+        self.socket.set_tcp_nodelay(no_delay)
+    }
+
+    fn peer_addr(&self) -> io::Result<AnySocketAddr> {
+        self.socket.peer_addr()
+    }
+}
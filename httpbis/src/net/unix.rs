@@ -103,9 +103,10 @@ impl ToTokioListener for ::std::os::unix::net::UnixListener {
     }
 
     fn local_addr(&self) -> io::Result<AnySocketAddr> {
-        let addr = self.local_addr().unwrap();
-        let path = addr.as_pathname().unwrap();
-        Ok(AnySocketAddr::Unix(SocketAddrUnix::from(path)))
+        // inherited socket can be unnamed
+        Ok(AnySocketAddr::Unix(SocketAddrUnix::from(
+            self.local_addr()?,
+        )))
     }
 }
 
@@ -7,6 +7,9 @@ use std::future::Future;
 
 use bytes::Bytes;
 
+use crate::content_coding::code_stream;
+use crate::content_coding::BodyDecoder;
+use crate::content_coding::ContentCoding;
 use crate::message::SimpleHttpMessage;
 use crate::solicit::header::Headers;
 use crate::solicit_async::*;
@@ -102,6 +105,27 @@ impl Response {
         Response::new(future::err(err))
     }
 
+    /// Decompress response body encoded with `gzip` or `deflate`.
+    ///
+    /// `content-encoding` and `content-length` headers are removed
+    /// if the body is decompressed, trailers are returned as is.
+    /// Responses with other content encodings are returned unchanged.
+    pub fn decompress(self) -> Response {
+        Response::new(self.0.map_ok(|(mut headers, stream)| {
+            match headers
+                .get_opt("content-encoding")
+                .and_then(ContentCoding::parse)
+            {
+                Some(coding) => {
+                    headers.remove("content-encoding");
+                    headers.remove("content-length");
+                    (headers, code_stream(stream, BodyDecoder::new(coding)))
+                }
+                None => (headers, stream),
+            }
+        }))
+    }
+
     // getters
 
     pub fn into_stream_flag(self) -> HttpFutureStreamSend<DataOrHeadersWithFlag> {
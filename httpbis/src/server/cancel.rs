@@ -0,0 +1,74 @@
+//! Notification of server handlers about cancelled streams.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::channel::oneshot;
+use futures::future::FutureExt;
+use futures::future::Shared;
+
+use crate::error;
+use crate::ErrorCode;
+
+/// Why the stream was cancelled.
+#[derive(Debug, Clone)]
+pub enum ServerCancelReason {
+    /// Client sent `RST_STREAM` with this code.
+    Reset(ErrorCode),
+    /// Connection was closed before the stream completed.
+    ConnDied(Arc<error::Error>),
+    /// Connection did not send `PUSH_PROMISE` for the pushed stream.
+    PushRejected(Arc<error::Error>),
+}
+
+/// Future resolved when the stream is reset by client or the connection dies.
+///
+/// Never resolves if the stream completes normally.
+#[derive(Clone)]
+pub struct ServerCancelled {
+    rx: Shared<oneshot::Receiver<ServerCancelReason>>,
+}
+
+impl ServerCancelled {
+    /// Resolved when the stream is removed from the connection,
+    /// whether it was cancelled or completed.
+    pub(crate) fn finished(&self) -> impl Future<Output = ()> + Send + 'static {
+        self.rx.clone().map(|_| ())
+    }
+}
+
+impl Future for ServerCancelled {
+    type Output = ServerCancelReason;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ServerCancelReason> {
+        match self.rx.poll_unpin(cx) {
+            Poll::Ready(Ok(reason)) => Poll::Ready(reason),
+            // sender dropped: stream completed without cancellation
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Pending,
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Kept by connection while the stream is alive.
+pub(crate) struct ServerCancelSender {
+    tx: oneshot::Sender<ServerCancelReason>,
+}
+
+impl ServerCancelSender {
+    pub fn cancel(self, reason: ServerCancelReason) {
+        // it is fine if nobody waits
+        drop(self.tx.send(reason));
+    }
+}
+
+pub(crate) fn server_cancel() -> (ServerCancelSender, ServerCancelled) {
+    let (tx, rx) = oneshot::channel();
+    (
+        ServerCancelSender { tx },
+        ServerCancelled { rx: rx.shared() },
+    )
+}
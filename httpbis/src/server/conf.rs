@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::common::conf::CommonConf;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,6 +10,24 @@ pub enum ServerAlpn {
     Require,
 }
 
+/// How accepted connections are assigned to `ServerBuilder::conn_event_loops`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerConnLoopSelection {
+    /// Each loop in turn
+    RoundRobin,
+    /// Loop with fewest connections, then fewest streams
+    LeastConnections,
+    /// Random loop
+    Random,
+}
+
+/// Max number of frames received from a client in a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerFrameRateLimit {
+    pub count: u32,
+    pub period: Duration,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct ServerConf {
     /// TCP_NODELAY
@@ -23,6 +43,78 @@ pub struct ServerConf {
     pub reuse_port: Option<bool>,
     pub backlog: Option<i32>,
 
+    /// How connections are spread over connection event loops.
+    /// Default is least connections.
+    pub conn_loop_selection: Option<ServerConnLoopSelection>,
+
+    /// Max number of open connections. Default is unlimited.
+    pub max_conns: Option<usize>,
+    /// Max number of open connections from one IP address. Default is unlimited.
+    ///
+    /// Limit is checked on accept by socket peer address, so it cannot be used
+    /// with `proxy_protocol`: `ServerBuilder::build` fails.
+    pub max_conns_per_ip: Option<usize>,
+    /// Refuse connections over limits with `GOAWAY(ENHANCE_YOUR_CALM)`
+    /// after the client preface instead of closing the socket. Default is false.
+    ///
+    /// Number of refusals in progress is limited, sockets over that limit are closed.
+    pub refuse_with_goaway: Option<bool>,
+
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS` sent to clients.
+    /// Streams over the limit are refused with `RST_STREAM(REFUSED_STREAM)`.
+    /// Default is unlimited.
+    pub max_concurrent_streams: Option<u32>,
+
+    /// Serve HTTP/1.1 requests with the same handler.
+    /// When disabled (default) HTTP/1 requests are replied with 500.
+    ///
+    /// Also enables `Upgrade: h2c` of the first request on cleartext connections.
+    /// Client half-close while response is pending cancels the request.
+    pub http1: Option<bool>,
+
+    /// Expect PROXY protocol v1 or v2 header before TLS handshake or HTTP preface,
+    /// as sent by HAProxy or AWS NLB. Default is false.
+    ///
+    /// Client address from the header replaces socket peer address.
+    /// Connections without valid header are closed.
+    pub proxy_protocol: Option<bool>,
+
+    /// Maximum request body size in bytes. Default is unlimited.
+    ///
+    /// Requests with larger `content-length` or body are replied with 413,
+    /// or reset if response headers were already sent.
+    /// Can be overridden per request with `ServerHandler::max_request_body_size`.
+    pub max_request_body_size: Option<u64>,
+
+    /// Max rate of `RST_STREAM` frames from client. Default is unlimited.
+    ///
+    /// Clients exceeding any frame rate limit are sent `GOAWAY(ENHANCE_YOUR_CALM)`
+    /// and disconnected.
+    pub max_reset_rate: Option<ServerFrameRateLimit>,
+    /// Max rate of `PING`, `SETTINGS` and `PRIORITY` frames from client,
+    /// acks are not counted. Default is unlimited.
+    pub max_control_frame_rate: Option<ServerFrameRateLimit>,
+    /// Max rate of empty `DATA` frames without `END_STREAM` from client.
+    /// Default is unlimited.
+    pub max_empty_frame_rate: Option<ServerFrameRateLimit>,
+
+    /// Max time for PROXY header, TLS handshake, connection preface
+    /// and the first `SETTINGS` frame. Default is unlimited,
+    /// except PROXY header which is limited to 10 seconds.
+    pub handshake_timeout: Option<Duration>,
+    /// Max time to receive a complete header block after `HEADERS` frame starts,
+    /// or HTTP/1 request head after its first byte. Default is unlimited.
+    pub header_block_timeout: Option<Duration>,
+    /// Close connection with `GOAWAY(NO_ERROR)` after it has no open streams
+    /// for this time. HTTP/1 connection is closed after waiting this long
+    /// for the next request. Default is unlimited.
+    pub idle_timeout: Option<Duration>,
+    /// Send `PING` with this interval to check client is alive.
+    /// Connection is closed if `PING` is not acknowledged in `ping_timeout`.
+    pub ping_interval: Option<Duration>,
+    /// Max time to wait for `PING` ack, default is `ping_interval`.
+    pub ping_timeout: Option<Duration>,
+
     pub common: CommonConf,
 }
 
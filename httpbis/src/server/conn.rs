@@ -6,11 +6,17 @@ use crate::result;
 use crate::AnySocketAddr;
 
 use crate::solicit::end_stream::EndStream;
+use crate::solicit::frame::FrameIR;
+use crate::solicit::frame::GoawayFrame;
+use crate::solicit::frame::HttpFrameDecoded;
 use crate::solicit::frame::HttpSetting;
+use crate::solicit::frame::PushPromiseMultiFrame;
 use crate::solicit::frame::SettingsFrame;
 use crate::solicit::header::*;
+use crate::solicit::HttpScheme;
 use crate::solicit::DEFAULT_SETTINGS;
 
+use bytes::BytesMut;
 use futures::channel::oneshot;
 use futures::future;
 use futures::FutureExt;
@@ -18,6 +24,7 @@ use futures::TryFutureExt;
 
 use crate::common::types::Types;
 
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 
 use tls_api::TlsAcceptor;
@@ -36,6 +43,8 @@ use crate::common::conn::SideSpecific;
 use crate::common::conn_command_channel::conn_command_channel;
 use crate::common::conn_command_channel::ConnCommandSender;
 use crate::common::conn_read::ConnReadSideCustom;
+use crate::common::conn_timers::ConnTimeouts;
+use crate::common::conn_timers::ConnTimers;
 use crate::common::conn_write::CommonToWriteMessage;
 use crate::common::conn_write::ConnWriteSideCustom;
 use crate::common::sender::CommonSender;
@@ -47,23 +56,78 @@ use crate::common::stream_handler::StreamHandlerInternal;
 use crate::common::stream_map::HttpStreamRef;
 use crate::headers_place::HeadersPlace;
 use crate::misc::any_to_string;
+use crate::net::prefixed::PrefixedSocket;
 use crate::req_resp::RequestOrResponse;
+use crate::server::cancel::server_cancel;
+use crate::server::cancel::ServerCancelReason;
+use crate::server::cancel::ServerCancelSender;
+use crate::server::conn_http1::ServerConnHttp1;
+use crate::server::conn_info::next_conn_id;
+use crate::server::conn_info::ServerConnInfo;
+use crate::server::conn_loops::ConnLoopLoad;
+use crate::server::conn_loops::ConnLoopStreamGuard;
+use crate::server::extensions::Extensions;
+use crate::server::frame_rate::FrameRateLimits;
 use crate::server::handler::ServerHandler;
 use crate::server::handler::ServerHandlerContext;
+use crate::server::proxy_protocol::read_proxy_header;
+use crate::server::proxy_protocol::ProxyHeader;
+use crate::server::push::ServerPushPromise;
+use crate::server::push::ServerPushState;
+use crate::server::push::ServerResponsePush;
 use crate::server::req::ServerRequest;
+use crate::server::sni::peek_sni;
 use crate::server::types::ServerTypes;
+use crate::solicit::session::StreamState;
 use crate::solicit::stream_id::StreamId;
 use crate::ErrorCode;
 use crate::ServerConf;
 use crate::ServerResponse;
 use crate::ServerTlsOption;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::time::Duration;
 use tokio::runtime::Handle;
+use tokio::time;
+use tokio::time::Instant;
+
+/// Default limit of PROXY header read time when handshake timeout is not set.
+const DEFAULT_PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default limit of refused connection handshake when handshake timeout is not set.
+const DEFAULT_REFUSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Socket ready for HTTP after PROXY header and TLS handshake.
+pub(crate) struct ServerSocketAccepted<I> {
+    socket: I,
+    /// Server name sent by TLS client
+    tls_sni: Option<String>,
+    alpn_protocol: Option<Vec<u8>>,
+    proxy_header: Option<ProxyHeader>,
+}
+
+type ServerSocketAcceptedDyn = ServerSocketAccepted<Pin<Box<dyn SocketStream>>>;
 
-pub struct ServerStreamData {}
+pub struct ServerStreamData {
+    /// Counts the stream in its event loop load
+    _loop_guard: Option<ConnLoopStreamGuard>,
+    /// Resolves `ServerResponse::cancelled`
+    cancel: Option<ServerCancelSender>,
+}
+
+impl HttpStreamDataSpecific for ServerStreamData {
+    fn rst_recvd(&mut self, error_code: ErrorCode) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel.cancel(ServerCancelReason::Reset(error_code));
+        }
+    }
 
-impl HttpStreamDataSpecific for ServerStreamData {}
+    fn conn_died(&mut self, error: error::Error) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel.cancel(ServerCancelReason::ConnDied(Arc::new(error)));
+        }
+    }
+}
 
 pub(crate) type ServerStream = HttpStreamCommon<ServerTypes>;
 
@@ -82,6 +146,19 @@ impl HttpStreamData for ServerStream {
 
 pub(crate) struct ServerConnData {
     factory: Arc<dyn ServerHandler>,
+    max_request_body_size: Option<u64>,
+    conn_info: Arc<ServerConnInfo>,
+    /// Load of the event loop running the connection
+    loop_load: Option<Arc<ConnLoopLoad>>,
+    frame_rate: FrameRateLimits,
+    /// Shared with responses to reserve pushed streams
+    push: Option<Arc<ServerPushState>>,
+}
+
+impl ServerConnData {
+    fn loop_guard(&self) -> Option<ConnLoopStreamGuard> {
+        self.loop_load.as_ref().map(ConnLoopLoad::stream_guard)
+    }
 }
 
 impl SideSpecific for ServerConnData {}
@@ -93,12 +170,32 @@ impl<I> ServerInner<I>
 where
     I: SocketStream,
 {
+    /// Reply with given 4xx status if response is not started, otherwise reset the stream.
+    fn reply_too_large(
+        &mut self,
+        stream_id: StreamId,
+        status: u32,
+        response_started: bool,
+    ) -> result::Result<()> {
+        if response_started {
+            self.send_rst_stream(stream_id, ErrorCode::Cancel)
+        } else {
+            self.write_part_headers(stream_id, Headers::new_status(status), EndStream::Yes);
+            // 8.1
+            // A server can send a complete response prior to the client
+            // sending an entire request ... the server MAY request that the
+            // client abort transmission of a request without error by sending
+            // a RST_STREAM with an error code of NO_ERROR.
+            self.send_rst_stream(stream_id, ErrorCode::NoError)
+        }
+    }
+
     fn new_stream_from_client(
         &mut self,
         stream_id: StreamId,
         headers: Headers,
         end_stream: EndStream,
-    ) -> result::Result<HttpStreamRef<ServerTypes>> {
+    ) -> result::Result<Option<HttpStreamRef<ServerTypes>>> {
         if ServerTypes::init_where(stream_id) == InitWhere::Locally {
             return Err(error::Error::InitiatedStreamWithServerIdFromClient(
                 stream_id,
@@ -114,14 +211,54 @@ where
 
         self.last_peer_stream_id = stream_id;
 
+        // 5.1.2
+        // An endpoint that receives a HEADERS frame that causes its advertised
+        // concurrent stream limit to be exceeded MUST treat this as a stream error
+        // of type PROTOCOL_ERROR or REFUSED_STREAM.
+        // pushed streams are limited by client settings
+        let max_concurrent_streams = self.our_settings_sent().max_concurrent_streams;
+        if self.streams.len_initiated(InitWhere::Peer) >= max_concurrent_streams as usize {
+            warn!(
+                "max concurrent streams {} exceeded, refusing stream {}",
+                max_concurrent_streams, stream_id
+            );
+            self.send_rst_stream(stream_id, ErrorCode::RefusedStream)?;
+            return Ok(None);
+        }
+
+        let max_request_body_size = self
+            .specific
+            .factory
+            .max_request_body_size(&headers)
+            .or(self.specific.max_request_body_size);
+
+        if let (Some(limit), Some(content_length)) =
+            (max_request_body_size, headers.content_length())
+        {
+            if content_length > limit && end_stream == EndStream::No {
+                warn!(
+                    "stream {} content-length {} exceeds limit {}",
+                    stream_id, content_length, limit
+                );
+                self.reply_too_large(stream_id, 413, false)?;
+                return Ok(None);
+            }
+        }
+
         debug!("new stream: {}", stream_id);
 
-        let (_, out_window) = self.new_stream_data(
+        let (cancel, cancelled) = server_cancel();
+
+        let (mut stream, out_window) = self.new_stream_data(
             stream_id,
             headers.content_length(),
             InMessageStage::AfterInitialHeaders,
-            ServerStreamData {},
+            ServerStreamData {
+                _loop_guard: self.specific.loop_guard(),
+                cancel: Some(cancel),
+            },
         );
+        stream.stream().in_rem_body_limit = max_request_body_size;
 
         let in_window_size = self
             .streams
@@ -136,10 +273,18 @@ where
         let sender = ServerResponse {
             common: CommonSender::new(stream_id, self.to_write_tx.clone(), out_window, false),
             drop_callback: None,
+            push: ServerResponsePush::Allowed(self.push_state()),
+            headers_hooks: Vec::new(),
+            cancelled: cancelled.clone(),
+            body_encoder_factory: None,
+            body_encoder: None,
         };
 
         let context = ServerHandlerContext {
             loop_handle: self.loop_handle.clone(),
+            conn_info: self.specific.conn_info.clone(),
+            stream_id,
+            cancelled,
         };
 
         let mut stream_handler = None;
@@ -147,6 +292,7 @@ where
             let req = ServerRequest {
                 headers,
                 end_stream: end_stream == EndStream::Yes,
+                extensions: Extensions::new(),
                 stream_id,
                 in_window_size,
                 stream_handler: &mut stream_handler,
@@ -175,12 +321,140 @@ where
 
         stream.stream().peer_tx = stream_handler;
 
-        Ok(stream)
+        Ok(Some(stream))
+    }
+
+    /// Apply `HTTP2-Settings` and start stream 1 with upgrade request.
+    fn process_h2c_upgrade(
+        &mut self,
+        headers: Headers,
+        settings: SettingsFrame,
+    ) -> result::Result<()> {
+        self.peer_settings.apply_from_frame(&settings);
+
+        // 3.2
+        // The HTTP/1.1 request that is sent prior to upgrade is assigned a
+        // stream identifier of 1 (see Section 5.1.1) with default priority
+        // values (Section 5.3.5).  Stream 1 is implicitly "half-closed" from
+        // the client toward the server (see Section 5.1), since the request is
+        // completed as an HTTP/1.1 request.
+        if let Some(stream) = self.new_stream_from_client(1, headers, EndStream::Yes)? {
+            stream.close_remote();
+        }
+        self.peer_closed_streams.add(1);
+
+        Ok(())
+    }
+
+    /// Push state shared with responses, created with the first request stream.
+    fn push_state(&mut self) -> Arc<ServerPushState> {
+        if self.specific.push.is_none() {
+            let push = ServerPushState::new(self.pump_out_window_size.streams());
+            self.specific.push = Some(Arc::new(push));
+            self.update_push_state();
+        }
+        self.specific.push.clone().unwrap()
+    }
+
+    /// Mirror the state checked by `ServerResponse::push_promise`.
+    fn update_push_state(&self) {
+        if let Some(ref push) = self.specific.push {
+            push.update(
+                self.peer_settings.enable_push,
+                self.goaway_received.is_some() || self.goaway_sent.is_some(),
+                self.peer_settings.max_concurrent_streams,
+                self.streams.len_initiated(InitWhere::Locally),
+            );
+        }
+    }
+
+    fn process_push_promise(&mut self, push_promise: ServerPushPromise) -> result::Result<()> {
+        let ServerPushPromise {
+            parent_stream_id,
+            promised_stream_id,
+            headers,
+            out_window,
+            cancel,
+        } = push_promise;
+
+        // Promised ids are allocated in order, rejected ids are skipped
+        self.last_local_stream_id = promised_stream_id;
+
+        let r = self.check_push_promise(parent_stream_id);
+        if let Some(ref push) = self.specific.push {
+            push.processed(r.is_ok());
+        }
+        if let Err(e) = r {
+            debug!(
+                "not sending PUSH_PROMISE {} on stream {}: {}",
+                promised_stream_id, parent_stream_id, e
+            );
+            cancel.cancel(ServerCancelReason::PushRejected(Arc::new(e)));
+            return Ok(());
+        }
+
+        debug!(
+            "push promise: {} on stream {}",
+            promised_stream_id, parent_stream_id
+        );
+
+        self.queued_write.queue_not_goaway(PushPromiseMultiFrame {
+            stream_id: parent_stream_id,
+            promised_stream_id,
+            headers,
+            encoder: &mut self.encoder,
+            max_frame_size: self.peer_settings.max_frame_size,
+        });
+
+        out_window.increase(self.peer_settings.initial_window_size as isize);
+
+        let stream = self.insert_stream_data(
+            promised_stream_id,
+            out_window,
+            None,
+            InMessageStage::AfterTrailingHeaders,
+            ServerStreamData {
+                _loop_guard: self.specific.loop_guard(),
+                cancel: Some(cancel),
+            },
+        );
+
+        // 8.2.2
+        // The peer cannot send on a pushed stream.
+        stream.close_remote();
+
+        Ok(())
+    }
+
+    /// Check that `PUSH_PROMISE` reserved by the response can still be sent.
+    fn check_push_promise(&self, parent_stream_id: StreamId) -> result::Result<()> {
+        if self.goaway_received.is_some() || self.goaway_sent.is_some() {
+            return Err(error::Error::PushPromiseAfterGoaway);
+        }
+
+        if !self.peer_settings.enable_push {
+            return Err(error::Error::PushDisabled);
+        }
+
+        let max_concurrent_streams = self.peer_settings.max_concurrent_streams;
+        if self.streams.len_initiated(InitWhere::Locally) >= max_concurrent_streams as usize {
+            return Err(error::Error::PushConcurrencyLimit(max_concurrent_streams));
+        }
+
+        // 6.6
+        // PUSH_PROMISE frames MUST only be sent on a peer-initiated stream that
+        // is in either the "open" or "half-closed (remote)" state.
+        match self.streams.get_stream_state(parent_stream_id) {
+            Some(StreamState::Open) | Some(StreamState::HalfClosedRemote) => Ok(()),
+            _ => Err(error::Error::PushPromiseOnClosedStream(parent_stream_id)),
+        }
     }
 }
 
 pub enum ServerToWriteMessage {
     Common(CommonToWriteMessage),
+    GracefulShutdown,
+    PushPromise(ServerPushPromise),
 }
 
 impl From<CommonToWriteMessage> for ServerToWriteMessage {
@@ -198,8 +472,23 @@ where
     fn process_message(&mut self, message: ServerToWriteMessage) -> result::Result<()> {
         match message {
             ServerToWriteMessage::Common(common) => self.process_common_message(common),
+            ServerToWriteMessage::GracefulShutdown => self.start_graceful_shutdown(),
+            ServerToWriteMessage::PushPromise(push_promise) => {
+                self.process_push_promise(push_promise)
+            }
         }
     }
+
+    fn start_queued_streams(&mut self) -> result::Result<bool> {
+        // server does not initiate streams,
+        // but this is called after each event, so responses see up to date push state
+        self.update_push_state();
+        Ok(false)
+    }
+
+    fn queued_streams_len(&self) -> usize {
+        0
+    }
 }
 
 impl<I> ConnReadSideCustom for Conn<ServerTypes, I>
@@ -230,9 +519,7 @@ where
         }
 
         if !existing_stream {
-            return self
-                .new_stream_from_client(stream_id, headers, end_stream)
-                .map(Some);
+            return self.new_stream_from_client(stream_id, headers, end_stream);
         }
 
         if end_stream == EndStream::No {
@@ -245,53 +532,308 @@ where
         stream.stream().trailers_recvd(headers);
         Ok(Some(stream))
     }
+
+    fn in_body_limit_exceeded(&mut self, stream_id: StreamId) -> result::Result<()> {
+        let mut response_started = true;
+        if let Some(mut stream) = self.streams.get_mut(stream_id) {
+            response_started = stream.stream().outgoing.is_started();
+            if let Some(handler) = stream.stream().peer_tx.take() {
+                drop(handler.error(error::Error::RequestBodyTooLarge));
+            }
+        }
+        self.reply_too_large(stream_id, 413, response_started)
+    }
+
+    fn header_list_too_large(
+        &mut self,
+        stream_id: StreamId,
+        end_stream: EndStream,
+    ) -> result::Result<()> {
+        if let Some(mut stream) = self.streams.get_mut(stream_id) {
+            // trailers
+            let response_started = stream.stream().outgoing.is_started();
+            if let Some(handler) = stream.stream().peer_tx.take() {
+                drop(handler.error(error::Error::HeaderListTooLarge));
+            }
+            return self.reply_too_large(stream_id, 431, response_started);
+        }
+
+        if ServerTypes::init_where(stream_id) == InitWhere::Locally
+            || stream_id <= self.last_peer_stream_id
+        {
+            return self.send_rst_stream(stream_id, ErrorCode::StreamClosed);
+        }
+
+        // 6.8
+        // Streams above last stream id of sent `GOAWAY` are ignored, same as other frames.
+        if let Some(goaway) = &self.goaway_sent {
+            if stream_id > goaway.last_stream_id {
+                return Ok(());
+            }
+        }
+
+        // Request is not passed to the handler, but the stream is counted as opened
+        self.last_peer_stream_id = stream_id;
+
+        // Same admission check as `new_stream_from_client`
+        let max_concurrent_streams = self.our_settings_sent().max_concurrent_streams;
+        if self.streams.len_initiated(InitWhere::Peer) >= max_concurrent_streams as usize {
+            warn!(
+                "max concurrent streams {} exceeded, refusing stream {}",
+                max_concurrent_streams, stream_id
+            );
+            return self.send_rst_stream(stream_id, ErrorCode::RefusedStream);
+        }
+
+        if end_stream == EndStream::Yes {
+            self.write_part_headers(stream_id, Headers::new_status(431), EndStream::Yes);
+            Ok(())
+        } else {
+            self.reply_too_large(stream_id, 431, false)
+        }
+    }
+
+    fn frame_received(&mut self, frame: &HttpFrameDecoded) -> result::Result<bool> {
+        let frame_type = match self.specific.frame_rate.check(frame, Instant::now()) {
+            None => return Ok(true),
+            Some(frame_type) => frame_type,
+        };
+        if self.close_reason.is_none() {
+            warn!("{} frame rate limit exceeded, sending GOAWAY", frame_type);
+            self.close_reason = Some(error::Error::FrameRateLimitExceeded(frame_type));
+            self.send_goaway(ErrorCode::EnhanceYourCalm)?;
+        }
+        Ok(false)
+    }
 }
 
 pub struct ServerConn {
+    id: u64,
     write_tx: ConnCommandSender<ServerTypes>,
 }
 
+/// Fail with `HandshakeTimeout` if handshake is not completed before deadline.
+async fn with_handshake_deadline<F, R>(deadline: Option<Instant>, handshake: F) -> result::Result<R>
+where
+    F: Future<Output = result::Result<R>>,
+{
+    match deadline {
+        Some(deadline) => match time::timeout_at(deadline, handshake).await {
+            Ok(r) => r,
+            Err(_) => Err(error::Error::HandshakeTimeout),
+        },
+        None => handshake.await,
+    }
+}
+
+/// Read PROXY header and complete TLS handshake.
+fn accept_socket<A>(
+    socket: Pin<Box<dyn SocketStream>>,
+    tls: ServerTlsOption<A>,
+    conf: &ServerConf,
+) -> (HttpScheme, HttpFutureSend<ServerSocketAcceptedDyn>)
+where
+    A: TlsAcceptor,
+{
+    let proxy_protocol = conf.proxy_protocol.unwrap_or(false);
+    let proxy_timeout = conf
+        .handshake_timeout
+        .unwrap_or(DEFAULT_PROXY_HEADER_TIMEOUT);
+    // PROXY header precedes TLS handshake
+    let socket = async move {
+        if !proxy_protocol {
+            return Ok((PrefixedSocket::new(BytesMut::new(), socket), None));
+        }
+        let mut socket = socket;
+        let (header, rem) = match time::timeout(proxy_timeout, read_proxy_header(&mut socket)).await
+        {
+            Ok(r) => r?,
+            Err(_) => return Err(error::Error::HandshakeTimeout),
+        };
+        Ok((PrefixedSocket::new(rem, socket), Some(header)))
+    };
+
+    match tls {
+        ServerTlsOption::Plain => {
+            let socket = Box::pin(async move {
+                let (socket, proxy_header) = socket.await?;
+                Ok(ServerSocketAccepted {
+                    socket: Box::pin(socket) as Pin<Box<dyn SocketStream>>,
+                    tls_sni: None,
+                    alpn_protocol: None,
+                    proxy_header,
+                })
+            });
+            (HttpScheme::Http, socket)
+        }
+        ServerTlsOption::Tls(acceptor) => {
+            let socket = Box::pin(async move {
+                let (socket, proxy_header) = socket.await?;
+                // peek server name to expose it to handlers
+                let (socket, tls_sni) = peek_sni(socket).await?;
+                let socket = acceptor.accept(socket).await?;
+                Ok(ServerSocketAccepted {
+                    alpn_protocol: socket.get_alpn_protocol(),
+                    socket: Box::pin(socket) as Pin<Box<dyn SocketStream>>,
+                    tls_sni,
+                    proxy_header,
+                })
+            });
+            (HttpScheme::Https, socket)
+        }
+        ServerTlsOption::TlsSni(acceptors) => {
+            let socket = Box::pin(async move {
+                let (socket, proxy_header) = socket.await?;
+                let (socket, tls_sni) = peek_sni(socket).await?;
+                let acceptor = match acceptors.select(tls_sni.as_deref()) {
+                    Some(acceptor) => acceptor.clone(),
+                    None => return Err(error::Error::UnknownTlsServerName(tls_sni)),
+                };
+                let socket = acceptor.accept(socket).await?;
+                Ok(ServerSocketAccepted {
+                    alpn_protocol: socket.get_alpn_protocol(),
+                    socket: Box::pin(socket) as Pin<Box<dyn SocketStream>>,
+                    tls_sni,
+                    proxy_header,
+                })
+            });
+            (HttpScheme::Https, socket)
+        }
+    }
+}
+
 impl ServerConn {
-    fn connected<F, I>(
+    fn connected<I>(
         lh: &Handle,
-        socket: HttpFutureSend<I>,
+        socket: HttpFutureSend<ServerSocketAccepted<I>>,
         peer_addr: AnySocketAddr,
+        scheme: HttpScheme,
         conf: ServerConf,
-        service: Arc<F>,
+        service: Arc<dyn ServerHandler>,
+        loop_load: Option<Arc<ConnLoopLoad>>,
     ) -> (ServerConn, HttpFutureSend<()>)
     where
-        F: ServerHandler,
         I: SocketStream,
     {
         let lh = lh.clone();
 
+        let id = next_conn_id();
+
         let conn_died_error_holder = SomethingDiedErrorHolder::new();
 
         let (write_tx, write_rx) = conn_command_channel(conn_died_error_holder.clone());
 
-        let settings_frame = SettingsFrame::from_settings(vec![HttpSetting::EnablePush(false)]);
+        let mut settings = vec![HttpSetting::EnablePush(false)];
+        if let Some(max_concurrent_streams) = conf.max_concurrent_streams {
+            settings.push(HttpSetting::MaxConcurrentStreams(max_concurrent_streams));
+        }
+        settings.push(HttpSetting::MaxHeaderListSize(
+            conf.common.max_header_list_size(),
+        ));
+        let settings_frame = SettingsFrame::from_settings(settings);
         let mut settings = DEFAULT_SETTINGS;
         settings.apply_from_frame(&settings_frame);
 
         let write_tx_copy = write_tx.clone();
 
-        let run = socket.and_then(move |mut conn| async move {
-            server_handshake(&mut conn, settings_frame).await?;
+        let run = async move {
+            let serve_http_1 = conf.http1.unwrap_or(false);
+            let mut handshake_deadline = conf.handshake_timeout.map(|t| Instant::now() + t);
+            let handshake = async {
+                let mut accepted = socket.await?;
+                let handshake =
+                    server_handshake(&mut accepted.socket, settings_frame.clone(), serve_http_1)
+                        .await?;
+                Ok((accepted, handshake))
+            };
+            let (accepted, handshake) =
+                with_handshake_deadline(handshake_deadline, handshake).await?;
+            let conn = accepted.socket;
+            let peer_addr = match accepted.proxy_header.as_ref().and_then(|h| h.source) {
+                Some(source) => AnySocketAddr::Inet(source),
+                None => peer_addr,
+            };
+            let conn_info = Arc::new(ServerConnInfo {
+                id,
+                peer_addr: peer_addr.clone(),
+                tls: scheme == HttpScheme::Https,
+                tls_sni: accepted.tls_sni,
+                alpn_protocol: accepted.alpn_protocol,
+                proxy_header: accepted.proxy_header,
+            });
+            let (conn, write_tx, write_rx, upgrade) = match handshake {
+                ServerHandshake::Http2 => (
+                    PrefixedSocket::new(BytesMut::new(), conn),
+                    write_tx_copy,
+                    write_rx,
+                    None,
+                ),
+                ServerHandshake::Http1(read) => {
+                    let upgrade = match ServerConnHttp1::new(
+                        lh.clone(),
+                        service.clone(),
+                        conn_info.clone(),
+                        conn,
+                        read,
+                        (write_tx_copy, write_rx),
+                        &conf,
+                    )
+                    .run()
+                    .await?
+                    {
+                        Some(upgrade) => upgrade,
+                        None => return Ok(()),
+                    };
+
+                    let mut conn = PrefixedSocket::new(upgrade.read, upgrade.socket);
+                    handshake_deadline = conf.handshake_timeout.map(|t| Instant::now() + t);
+                    with_handshake_deadline(
+                        handshake_deadline,
+                        server_handshake_h2c(&mut conn, settings_frame),
+                    )
+                    .await?;
+                    (
+                        conn,
+                        upgrade.to_write_tx,
+                        upgrade.write_rx,
+                        Some((upgrade.headers, upgrade.settings)),
+                    )
+                }
+            };
 
-            let conn_data = Conn::<ServerTypes, I>::new(
+            let mut conn_data = Conn::<ServerTypes, PrefixedSocket<I>>::new(
                 lh,
-                ServerConnData { factory: service },
+                ServerConnData {
+                    factory: service,
+                    max_request_body_size: conf.max_request_body_size,
+                    conn_info,
+                    loop_load,
+                    frame_rate: FrameRateLimits::new(&conf),
+                    push: None,
+                },
                 conf.common,
                 settings,
-                write_tx_copy,
+                write_tx,
                 write_rx,
                 conn,
                 peer_addr,
                 conn_died_error_holder,
             );
 
+            conn_data.timers = ConnTimers::new(ConnTimeouts {
+                settings_deadline: handshake_deadline,
+                header_block: conf.header_block_timeout,
+                idle: conf.idle_timeout,
+                ping_interval: conf.ping_interval,
+                ping_timeout: conf.ping_timeout,
+            });
+
+            if let Some((headers, settings)) = upgrade {
+                conn_data.process_h2c_upgrade(headers, settings)?;
+            }
+
             conn_data.run().await
-        });
+        };
 
         let run = assert_send_future(run);
 
@@ -300,7 +842,7 @@ impl ServerConn {
             future::ready(x)
         }));
 
-        (ServerConn { write_tx }, future)
+        (ServerConn { id, write_tx }, future)
     }
 
     pub fn new<S, A>(
@@ -315,16 +857,50 @@ impl ServerConn {
         S: ServerHandler,
         A: TlsAcceptor,
     {
-        match tls {
-            ServerTlsOption::Plain => {
-                let socket = Box::pin(future::ok(socket));
-                ServerConn::connected(lh, socket, peer_addr, conf, service)
-            }
-            ServerTlsOption::Tls(acceptor) => {
-                let socket = Box::pin(async move { Ok(acceptor.accept(socket).await?) });
-                ServerConn::connected(lh, socket, peer_addr, conf, service)
-            }
-        }
+        ServerConn::new_dyn(lh, socket, peer_addr, tls, conf, service, None)
+    }
+
+    pub(crate) fn new_dyn<A>(
+        lh: &Handle,
+        socket: Pin<Box<dyn SocketStream>>,
+        peer_addr: AnySocketAddr,
+        tls: ServerTlsOption<A>,
+        conf: ServerConf,
+        service: Arc<dyn ServerHandler>,
+        loop_load: Option<Arc<ConnLoopLoad>>,
+    ) -> (ServerConn, HttpFutureSend<()>)
+    where
+        A: TlsAcceptor,
+    {
+        let (scheme, socket) = accept_socket(socket, tls, &conf);
+        ServerConn::connected(lh, socket, peer_addr, scheme, conf, service, loop_load)
+    }
+
+    /// Complete the handshake and close the connection
+    /// with `GOAWAY(ENHANCE_YOUR_CALM)`.
+    pub(crate) fn refuse_dyn<A>(
+        socket: Pin<Box<dyn SocketStream>>,
+        tls: ServerTlsOption<A>,
+        conf: ServerConf,
+    ) -> HttpFutureSend<()>
+    where
+        A: TlsAcceptor,
+    {
+        let (_, socket) = accept_socket(socket, tls, &conf);
+        // refused client must not hold the socket for long
+        let timeout = conf.handshake_timeout.unwrap_or(DEFAULT_REFUSE_TIMEOUT);
+        let refuse = async move {
+            let mut socket = socket.await?.socket;
+            let settings = SettingsFrame::from_settings(Vec::new());
+            server_handshake(&mut socket, settings, false).await?;
+            let goaway = GoawayFrame::new(0, ErrorCode::EnhanceYourCalm);
+            socket.write_all(&goaway.serialize_into_vec()).await?;
+            socket.shutdown().await?;
+            Ok(())
+        };
+        Box::pin(
+            async move { with_handshake_deadline(Some(Instant::now() + timeout), refuse).await },
+        )
     }
 
     pub fn new_plain_single_thread<S>(
@@ -383,6 +959,20 @@ impl ServerConn {
         ServerConn::new_plain_single_thread(lh, socket, peer_addr, conf, Arc::new(HttpServiceFn(f)))
     }
 
+    /// Send `GOAWAY` and close the connection when in-flight streams complete.
+    pub fn graceful_shutdown(&self) {
+        // ignore error, connection might be already dead
+        drop(
+            self.write_tx
+                .unbounded_send(ServerToWriteMessage::GracefulShutdown),
+        );
+    }
+
+    /// Connection id, same as `ServerConnInfo::id`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// For tests
     pub fn dump_state(&self) -> HttpFutureSend<ConnStateSnapshot> {
         let (tx, rx) = oneshot::channel();
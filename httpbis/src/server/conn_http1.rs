@@ -0,0 +1,898 @@
+//! HTTP/1.1 server connection.
+//!
+//! Requests are served one at a time with the same `ServerHandler` as HTTP/2.
+//! Each request gets a stream id as if it were an HTTP/2 client stream,
+//! and `ServerResponse` messages are processed from the same command channel
+//! HTTP/2 connection uses, so `ServerConn` operations work for both protocols.
+//!
+//! First request on a cleartext connection may ask for `h2c` upgrade,
+//! in that case connection is handed over to HTTP/2 code.
+
+use std::collections::HashMap;
+use std::io;
+use std::panic;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use bytes::BytesMut;
+use futures::future;
+use futures::future::Either;
+use futures::pin_mut;
+use futures::stream::StreamExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Handle;
+use tokio::time;
+use tokio::time::Instant;
+
+use crate::codec::http1::decode_h2c_settings;
+use crate::codec::http1::find_head_end;
+use crate::codec::http1::parse_request_head;
+use crate::codec::http1::write_chunk;
+use crate::codec::http1::write_last_chunk;
+use crate::codec::http1::write_response_head;
+use crate::codec::http1::ChunkedDecoder;
+use crate::codec::http1::ChunkedPart;
+use crate::codec::http1::Http1BodyLength;
+use crate::codec::http1::Http1RequestHead;
+use crate::codec::http1::Http1Version;
+use crate::codec::http1::MAX_HEAD_LEN;
+use crate::common::conn::ConnStateSnapshot;
+use crate::common::conn_command_channel::ConnCommandReceiver;
+use crate::common::conn_command_channel::ConnCommandSender;
+use crate::common::conn_write::CommonToWriteMessage;
+use crate::common::pump_stream_to_write_loop::PumpStreamToWrite;
+use crate::common::sender::CommonSender;
+use crate::common::stream_handler::StreamHandlerInternal;
+use crate::common::window_size::ConnOutWindowSender;
+use crate::common::window_size::StreamOutWindowSender;
+use crate::data_or_headers::DataOrHeaders;
+use crate::data_or_headers_with_flag::DataOrHeadersWithFlag;
+use crate::error;
+use crate::misc::any_to_string;
+use crate::net::socket::SocketStream;
+use crate::result;
+use crate::server::cancel::server_cancel;
+use crate::server::cancel::ServerCancelReason;
+use crate::server::cancel::ServerCancelSender;
+use crate::server::conf::ServerConf;
+use crate::server::conn::ServerToWriteMessage;
+use crate::server::conn_info::ServerConnInfo;
+use crate::server::extensions::Extensions;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::push::ServerResponsePush;
+use crate::server::req::ServerRequest;
+use crate::server::stream_handler::ServerRequestStreamHandlerHolder;
+use crate::server::types::ServerTypes;
+use crate::solicit::frame::SettingsFrame;
+use crate::solicit::stream_id::StreamId;
+use crate::solicit::HttpScheme;
+use crate::solicit::DEFAULT_SETTINGS;
+use crate::ErrorCode;
+use crate::Headers;
+use crate::ServerResponse;
+
+const HTTP_1_100_CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+const HTTP_1_101_H2C: &[u8] = b"\
+HTTP/1.1 101 Switching Protocols\r\n\
+connection: upgrade\r\n\
+upgrade: h2c\r\n\
+\r\n";
+
+/// Response to malformed request
+const HTTP_1_400_RESPONSE: &[u8] = b"\
+HTTP/1.1 400 Bad Request\r\n\
+connection: close\r\n\
+content-length: 0\r\n\
+\r\n";
+
+const HTTP_1_431_RESPONSE: &[u8] = b"\
+HTTP/1.1 431 Request Header Fields Too Large\r\n\
+connection: close\r\n\
+content-length: 0\r\n\
+\r\n";
+
+const HTTP_1_408_RESPONSE: &[u8] = b"\
+HTTP/1.1 408 Request Timeout\r\n\
+connection: close\r\n\
+content-length: 0\r\n\
+\r\n";
+
+const HTTP_1_413_RESPONSE: &[u8] = b"\
+HTTP/1.1 413 Payload Too Large\r\n\
+connection: close\r\n\
+content-length: 0\r\n\
+\r\n";
+
+/// Response when handler reset the stream before sending headers
+const HTTP_1_500_RESPONSE: &[u8] = b"\
+HTTP/1.1 500 Internal Server Error\r\n\
+connection: close\r\n\
+content-length: 0\r\n\
+\r\n";
+
+enum Event {
+    /// Number of bytes read, zero on EOF
+    Read(usize),
+    Message(ServerToWriteMessage),
+}
+
+/// Remaining part of request body
+enum InBody {
+    ContentLength(u64),
+    Chunked(ChunkedDecoder),
+    Done,
+}
+
+/// How response body is delimited
+#[derive(Eq, PartialEq)]
+enum OutBody {
+    /// Body must not be sent (`HEAD` request, 1xx, 204 and 304)
+    None,
+    ContentLength,
+    Chunked,
+    /// HTTP/1.0 client without content length
+    UntilClose,
+}
+
+/// Currently processed request
+struct Http1Stream {
+    stream_id: StreamId,
+    version: Http1Version,
+    is_head: bool,
+    keep_alive: bool,
+    in_body: InBody,
+    /// Remaining body size limit
+    in_rem_body_limit: Option<u64>,
+    /// Body bytes the handler is ready to accept, replenished by `IncreaseInWindow`
+    in_window_size: u64,
+    stream_handler: Option<ServerRequestStreamHandlerHolder>,
+    out_window: StreamOutWindowSender,
+    /// Set after headers are written
+    out_body: Option<OutBody>,
+    /// Remaining response body bytes declared with `content-length`
+    out_rem_content_length: Option<u64>,
+    out_done: bool,
+    /// Response was reset after headers were sent, connection must be closed
+    out_aborted: bool,
+    /// Taken when request and response are complete
+    cancel: Option<ServerCancelSender>,
+}
+
+impl Drop for Http1Stream {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            // connection is closed before the exchange completed
+            cancel.cancel(ServerCancelReason::ConnDied(Arc::new(
+                error::Error::ConnDied,
+            )));
+        }
+    }
+}
+
+impl Http1Stream {
+    fn in_done(&self) -> bool {
+        matches!(self.in_body, InBody::Done)
+    }
+
+    /// Body data is not read until the handler consumes what it got
+    fn in_blocked(&self) -> bool {
+        // without handler the body is discarded
+        self.stream_handler.is_some() && self.in_window_size == 0
+    }
+
+    /// Decode next part of request body from the buffer.
+    ///
+    /// Returns `None` if more data is needed.
+    fn decode_in(&mut self, buf: &mut BytesMut) -> result::Result<Option<ChunkedPart>> {
+        let max_data = match self.stream_handler {
+            Some(..) => self.in_window_size.min(usize::MAX as u64) as usize,
+            None => usize::MAX,
+        };
+        match self.in_body {
+            InBody::Done => Ok(None),
+            InBody::ContentLength(rem) => {
+                if buf.is_empty() || max_data == 0 {
+                    return Ok(None);
+                }
+                let len = if (buf.len() as u64) < rem {
+                    buf.len()
+                } else {
+                    rem as usize
+                };
+                let len = len.min(max_data);
+                let data = buf.split_to(len).freeze();
+                self.in_body = match rem - len as u64 {
+                    0 => InBody::Done,
+                    rem => InBody::ContentLength(rem),
+                };
+                Ok(Some(ChunkedPart::Data(data)))
+            }
+            InBody::Chunked(ref mut decoder) => {
+                let part = decoder.decode(buf, max_data)?;
+                match part {
+                    Some(ChunkedPart::Data(..)) | None => {}
+                    Some(ChunkedPart::Trailers(..)) | Some(ChunkedPart::End) => {
+                        self.in_body = InBody::Done;
+                    }
+                }
+                Ok(part)
+            }
+        }
+    }
+
+    /// Account request body part, return `false` if body size limit is exceeded.
+    fn in_limit(&mut self, part: &ChunkedPart) -> bool {
+        match (self.in_rem_body_limit, part) {
+            (Some(rem), ChunkedPart::Data(data)) => match rem.checked_sub(data.len() as u64) {
+                Some(rem) => {
+                    self.in_rem_body_limit = Some(rem);
+                    true
+                }
+                None => false,
+            },
+            _ => true,
+        }
+    }
+
+    /// Account response body part, return `false` if it exceeds `content-length`.
+    fn out_content_length(&mut self, len: usize) -> bool {
+        match self.out_rem_content_length {
+            Some(rem) => match rem.checked_sub(len as u64) {
+                Some(rem) => {
+                    self.out_rem_content_length = Some(rem);
+                    true
+                }
+                None => false,
+            },
+            None => true,
+        }
+    }
+
+    /// Pass request body part to the handler
+    fn in_part(&mut self, part: ChunkedPart) {
+        let end = self.in_done();
+        if let Some(ref mut stream_handler) = self.stream_handler {
+            let r = match part {
+                ChunkedPart::Data(data) => {
+                    self.in_window_size -= data.len() as u64;
+                    stream_handler.data_frame(data, end)
+                }
+                ChunkedPart::End => stream_handler.data_frame(Bytes::new(), true),
+                ChunkedPart::Trailers(trailers) => stream_handler.trailers(trailers),
+            };
+            if let Err(e) = r {
+                // handler is not interested in the rest of the body
+                debug!("stream handler error: {:?}", e);
+                self.stream_handler = None;
+            }
+        }
+        if end {
+            self.stream_handler = None;
+        }
+    }
+}
+
+/// Connection state after `101 Switching Protocols` is sent.
+pub(crate) struct H2cUpgrade<I: SocketStream> {
+    pub socket: I,
+    /// Read bytes following upgrade request
+    pub read: BytesMut,
+    pub to_write_tx: ConnCommandSender<ServerTypes>,
+    pub write_rx: ConnCommandReceiver<ServerTypes>,
+    /// Upgrade request, becomes stream 1
+    pub headers: Headers,
+    /// Decoded `HTTP2-Settings`
+    pub settings: SettingsFrame,
+}
+
+pub(crate) struct ServerConnHttp1<I: SocketStream> {
+    loop_handle: Handle,
+    factory: Arc<dyn ServerHandler>,
+    scheme: HttpScheme,
+    conn_info: Arc<ServerConnInfo>,
+    socket: I,
+    read_buf: BytesMut,
+    to_write_tx: ConnCommandSender<ServerTypes>,
+    write_rx: ConnCommandReceiver<ServerTypes>,
+    out_window: ConnOutWindowSender,
+    last_stream_id: StreamId,
+    graceful_shutdown: bool,
+    max_request_body_size: Option<u64>,
+    /// Max time to receive request head after its first byte
+    header_block_timeout: Option<Duration>,
+    /// Max time to wait for the next request
+    idle_timeout: Option<Duration>,
+}
+
+impl<I: SocketStream> ServerConnHttp1<I> {
+    /// `read` is data read from `socket` during handshake.
+    pub fn new(
+        loop_handle: Handle,
+        factory: Arc<dyn ServerHandler>,
+        conn_info: Arc<ServerConnInfo>,
+        socket: I,
+        read: Vec<u8>,
+        (to_write_tx, write_rx): (
+            ConnCommandSender<ServerTypes>,
+            ConnCommandReceiver<ServerTypes>,
+        ),
+        conf: &ServerConf,
+    ) -> Self {
+        let scheme = match conn_info.tls {
+            true => HttpScheme::Https,
+            false => HttpScheme::Http,
+        };
+        ServerConnHttp1 {
+            loop_handle,
+            factory,
+            scheme,
+            conn_info,
+            socket,
+            read_buf: BytesMut::from(&read[..]),
+            to_write_tx,
+            write_rx,
+            out_window: ConnOutWindowSender::new(DEFAULT_SETTINGS.initial_window_size),
+            last_stream_id: 0,
+            graceful_shutdown: false,
+            max_request_body_size: conf.max_request_body_size,
+            header_block_timeout: conf.header_block_timeout,
+            idle_timeout: conf.idle_timeout,
+        }
+    }
+
+    /// Serve requests, return `Some` if connection is upgraded to HTTP/2.
+    pub async fn run(mut self) -> result::Result<Option<H2cUpgrade<I>>> {
+        loop {
+            let head = match self.read_request_head().await? {
+                Some(head) => head,
+                None => break,
+            };
+
+            // 3.2
+            // Requests that contain a payload body MUST be sent in their entirety
+            // before the client can send HTTP/2 frames.
+            // Upgrade of requests with body is not implemented, these are
+            // served over HTTP/1.1 as if there were no upgrade header.
+            // Only first request can be upgraded, because it becomes stream 1.
+            if head.h2c_settings.is_some()
+                && head.body == Http1BodyLength::Empty
+                && self.last_stream_id == 0
+                && !self.graceful_shutdown
+            {
+                return self.upgrade_h2c(head).await.map(Some);
+            }
+
+            let keep_alive = self.process_request(head).await?;
+            if !keep_alive || self.graceful_shutdown {
+                break;
+            }
+        }
+
+        self.socket.shutdown().await?;
+        Ok(None)
+    }
+
+    async fn upgrade_h2c(mut self, head: Http1RequestHead) -> result::Result<H2cUpgrade<I>> {
+        let settings = match decode_h2c_settings(head.h2c_settings.as_ref().unwrap()) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("failed to decode HTTP2-Settings: {}", e);
+                self.write_all(HTTP_1_400_RESPONSE).await?;
+                return Err(e);
+            }
+        };
+
+        debug!("upgrading to h2c");
+        self.write_all(HTTP_1_101_H2C).await?;
+
+        Ok(H2cUpgrade {
+            socket: self.socket,
+            read: self.read_buf,
+            to_write_tx: self.to_write_tx,
+            write_rx: self.write_rx,
+            headers: head.headers,
+            settings,
+        })
+    }
+
+    fn dump_state(&self) -> ConnStateSnapshot {
+        ConnStateSnapshot {
+            peer_addr: self.conn_info.peer_addr.clone(),
+            in_window_size: 0,
+            out_window_size: 0,
+            pump_out_window_size: self.out_window.get(),
+            out_buf_bytes: 0,
+            streams: HashMap::new(),
+            queued_streams: 0,
+        }
+    }
+
+    async fn next_event(&mut self, read: bool) -> result::Result<Event> {
+        self.read_buf.reserve(8192);
+
+        let socket = &mut self.socket;
+        let read_buf = &mut self.read_buf;
+        let read_future = async move {
+            if read {
+                socket.read_buf(read_buf).await
+            } else {
+                future::pending().await
+            }
+        };
+        pin_mut!(read_future);
+
+        match future::select(read_future, self.write_rx.next()).await {
+            Either::Left((r, _)) => Ok(Event::Read(r?)),
+            // Channel cannot be closed because we hold the sender
+            Either::Right((m, _)) => Ok(Event::Message(m.expect("write_rx closed"))),
+        }
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> result::Result<()> {
+        self.socket.write_all(buf).await?;
+        self.socket.flush().await?;
+        Ok(())
+    }
+
+    /// Messages which do not belong to current request
+    fn process_conn_message(&mut self, message: ServerToWriteMessage) {
+        match message {
+            ServerToWriteMessage::Common(CommonToWriteMessage::DumpState(sender)) => {
+                // ignore send error, client might be already dead
+                sender.send(self.dump_state()).ok();
+            }
+            ServerToWriteMessage::Common(CommonToWriteMessage::StreamEnqueue(stream_id, part)) => {
+                debug!("ignoring data for finished stream {}", stream_id);
+                if let DataOrHeaders::Data(data) = part.content {
+                    self.out_window.increase(data.len());
+                }
+            }
+            ServerToWriteMessage::Common(CommonToWriteMessage::Pull(
+                stream_id,
+                stream,
+                out_window,
+            )) => {
+                self.loop_handle.spawn(
+                    PumpStreamToWrite::<ServerTypes> {
+                        to_write_tx: self.to_write_tx.clone(),
+                        stream_id,
+                        out_window,
+                        stream,
+                    }
+                    .run(),
+                );
+            }
+            ServerToWriteMessage::Common(CommonToWriteMessage::StreamEnd(..)) => {}
+            // request body of finished stream is already read
+            ServerToWriteMessage::Common(CommonToWriteMessage::IncreaseInWindow(..)) => {}
+            ServerToWriteMessage::GracefulShutdown => self.graceful_shutdown = true,
+            // push is not available in HTTP/1
+            ServerToWriteMessage::PushPromise(..) => {}
+        }
+    }
+
+    /// Read request line with headers, return `None` on EOF, idle timeout
+    /// or shutdown between requests.
+    async fn read_request_head(&mut self) -> result::Result<Option<Http1RequestHead>> {
+        let idle_deadline = self.idle_timeout.map(|t| Instant::now() + t);
+        let mut header_block_deadline = None;
+        loop {
+            if let Some(len) = find_head_end(&self.read_buf) {
+                let head = self.read_buf.split_to(len);
+                return match parse_request_head(&head, self.scheme) {
+                    Ok(head) => Ok(Some(head)),
+                    Err(e) => {
+                        warn!("failed to parse HTTP/1 request: {}", e);
+                        self.write_all(HTTP_1_400_RESPONSE).await?;
+                        Err(e)
+                    }
+                };
+            }
+
+            if self.read_buf.len() > MAX_HEAD_LEN {
+                self.write_all(HTTP_1_431_RESPONSE).await?;
+                return Err(error::Error::InvalidHttp1Request(format!(
+                    "request head is larger than {}",
+                    MAX_HEAD_LEN
+                )));
+            }
+
+            if self.graceful_shutdown && self.read_buf.is_empty() {
+                return Ok(None);
+            }
+
+            let deadline = if self.read_buf.is_empty() {
+                idle_deadline
+            } else {
+                if header_block_deadline.is_none() {
+                    header_block_deadline = self.header_block_timeout.map(|t| Instant::now() + t);
+                }
+                header_block_deadline
+            };
+
+            let event = match deadline {
+                Some(deadline) => match time::timeout_at(deadline, self.next_event(true)).await {
+                    Ok(event) => event?,
+                    Err(_) if self.read_buf.is_empty() => {
+                        debug!("HTTP/1 connection idle timeout");
+                        return Ok(None);
+                    }
+                    Err(_) => {
+                        warn!("timeout reading HTTP/1 request head");
+                        self.write_all(HTTP_1_408_RESPONSE).await?;
+                        return Err(error::Error::HeaderBlockTimeout);
+                    }
+                },
+                None => self.next_event(true).await?,
+            };
+
+            match event {
+                Event::Read(0) => {
+                    if !self.read_buf.is_empty() {
+                        debug!("EOF in the middle of HTTP/1 request head");
+                    }
+                    return Ok(None);
+                }
+                Event::Read(_) => {}
+                Event::Message(m) => self.process_conn_message(m),
+            }
+        }
+    }
+
+    /// Serve a request, return `false` if connection must be closed after it.
+    async fn process_request(&mut self, head: Http1RequestHead) -> result::Result<bool> {
+        let stream_id = match self.last_stream_id {
+            0 => 1,
+            n => n + 2,
+        };
+        self.last_stream_id = stream_id;
+
+        debug!("new HTTP/1 request: {}", stream_id);
+
+        let in_body = match head.body {
+            Http1BodyLength::Empty => InBody::Done,
+            Http1BodyLength::ContentLength(len) => InBody::ContentLength(len),
+            Http1BodyLength::Chunked => InBody::Chunked(ChunkedDecoder::new()),
+        };
+
+        let end_stream = head.body == Http1BodyLength::Empty;
+
+        let max_request_body_size = self
+            .factory
+            .max_request_body_size(&head.headers)
+            .or(self.max_request_body_size);
+
+        if let (Some(limit), Http1BodyLength::ContentLength(len)) =
+            (max_request_body_size, &head.body)
+        {
+            if *len > limit {
+                warn!("content-length {} exceeds limit {}", len, limit);
+                self.write_all(HTTP_1_413_RESPONSE).await?;
+                return Ok(false);
+            }
+        }
+
+        if head.expect_continue && !end_stream {
+            self.write_all(HTTP_1_100_CONTINUE).await?;
+        }
+
+        let is_head = head.headers.method() == "HEAD";
+
+        let (out_window_sender, out_window_receiver) = self
+            .out_window
+            .new_stream(DEFAULT_SETTINGS.initial_window_size);
+
+        let (cancel, cancelled) = server_cancel();
+
+        let sender = ServerResponse {
+            common: CommonSender::new(
+                stream_id,
+                self.to_write_tx.clone(),
+                out_window_receiver,
+                false,
+            ),
+            drop_callback: None,
+            push: ServerResponsePush::NotSupported,
+            headers_hooks: Vec::new(),
+            cancelled: cancelled.clone(),
+            body_encoder_factory: None,
+            body_encoder: None,
+        };
+
+        let context = ServerHandlerContext {
+            loop_handle: self.loop_handle.clone(),
+            conn_info: self.conn_info.clone(),
+            stream_id,
+            cancelled,
+        };
+
+        let mut stream_handler = None;
+        let invoke_result = {
+            let req = ServerRequest {
+                headers: head.headers,
+                end_stream,
+                extensions: Extensions::new(),
+                stream_id,
+                in_window_size: DEFAULT_SETTINGS.initial_window_size,
+                stream_handler: &mut stream_handler,
+                to_write_tx: &self.to_write_tx,
+            };
+
+            let factory = &self.factory;
+            panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                factory.start_request(context, req, sender)
+            }))
+        };
+
+        match invoke_result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                warn!("handler returned error: {:?}", e);
+            }
+            Err(e) => {
+                let e = any_to_string(e);
+                warn!("handler panicked: {}", e);
+            }
+        }
+
+        let mut stream = Http1Stream {
+            stream_id,
+            version: head.version,
+            is_head,
+            keep_alive: head.keep_alive,
+            in_body,
+            in_rem_body_limit: max_request_body_size,
+            in_window_size: DEFAULT_SETTINGS.initial_window_size as u64,
+            stream_handler,
+            out_window: out_window_sender,
+            out_body: None,
+            out_rem_content_length: None,
+            out_done: false,
+            out_aborted: false,
+            cancel: Some(cancel),
+        };
+
+        loop {
+            if let Some(part) = stream.decode_in(&mut self.read_buf)? {
+                if !stream.in_limit(&part) {
+                    warn!("request body exceeds limit");
+                    if let Some(stream_handler) = stream.stream_handler.take() {
+                        stream_handler.error(error::Error::RequestBodyTooLarge).ok();
+                    }
+                    // connection is closed, because the rest of the body is not read
+                    if stream.out_body.is_none() {
+                        self.write_all(HTTP_1_413_RESPONSE).await?;
+                    }
+                    return Ok(false);
+                }
+                stream.in_part(part);
+                continue;
+            }
+
+            if stream.out_aborted {
+                return Ok(false);
+            }
+
+            if stream.out_done && stream.in_done() {
+                stream.cancel.take();
+                return Ok(stream.keep_alive);
+            }
+
+            // after the request body keep reading while response is pending
+            // to notice client disconnect, pipelined requests are buffered
+            // up to the head size limit
+            let read = if stream.in_done() {
+                self.read_buf.len() <= MAX_HEAD_LEN
+            } else {
+                !stream.in_blocked()
+            };
+
+            match self.next_event(read).await? {
+                Event::Read(0) if stream.in_done() => {
+                    // client half-close is treated as disconnect,
+                    // dropped stream cancels the handler
+                    debug!("EOF while HTTP/1 response is pending");
+                    return Ok(false);
+                }
+                Event::Read(0) => {
+                    let e = io::Error::new(io::ErrorKind::UnexpectedEof, "EOF in request body");
+                    if let Some(stream_handler) = stream.stream_handler.take() {
+                        stream_handler.error(error::Error::from(e)).ok();
+                    }
+                    return Ok(false);
+                }
+                Event::Read(_) => {}
+                Event::Message(m) => self.process_message(&mut stream, m).await?,
+            }
+        }
+    }
+
+    async fn process_message(
+        &mut self,
+        stream: &mut Http1Stream,
+        message: ServerToWriteMessage,
+    ) -> result::Result<()> {
+        match message {
+            ServerToWriteMessage::Common(CommonToWriteMessage::StreamEnqueue(stream_id, part))
+                if stream_id == stream.stream_id =>
+            {
+                self.write_part(stream, part).await
+            }
+            ServerToWriteMessage::Common(CommonToWriteMessage::StreamEnd(
+                stream_id,
+                error_code,
+            )) if stream_id == stream.stream_id => self.stream_end(stream, error_code).await,
+            ServerToWriteMessage::Common(CommonToWriteMessage::IncreaseInWindow(
+                stream_id,
+                increase,
+            )) if stream_id == stream.stream_id => {
+                stream.in_window_size += increase as u64;
+                Ok(())
+            }
+            message => {
+                self.process_conn_message(message);
+                Ok(())
+            }
+        }
+    }
+
+    async fn write_part(
+        &mut self,
+        stream: &mut Http1Stream,
+        part: DataOrHeadersWithFlag,
+    ) -> result::Result<()> {
+        if stream.out_done {
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        match part.content {
+            DataOrHeaders::Headers(headers) if stream.out_body.is_none() => {
+                self.encode_head(stream, &headers, part.last, &mut buf);
+                if part.last {
+                    self.finish_body(stream);
+                }
+            }
+            DataOrHeaders::Headers(trailers) => {
+                if stream.out_body == Some(OutBody::Chunked) {
+                    write_last_chunk(&mut buf, Some(&trailers));
+                }
+                self.finish_body(stream);
+            }
+            DataOrHeaders::Data(data) => {
+                match stream.out_body {
+                    Some(OutBody::None) => {}
+                    Some(OutBody::Chunked) => write_chunk(&mut buf, &data),
+                    Some(OutBody::ContentLength) => {
+                        if !stream.out_content_length(data.len()) {
+                            // the rest would be read as the next response
+                            warn!(
+                                "stream {} body is larger than content-length",
+                                stream.stream_id
+                            );
+                            stream.out_aborted = true;
+                            return Ok(());
+                        }
+                        buf.extend_from_slice(&data)
+                    }
+                    Some(OutBody::UntilClose) => buf.extend_from_slice(&data),
+                    None => {
+                        warn!("data before headers in stream {}", stream.stream_id);
+                        stream.out_aborted = true;
+                        return self.write_all(HTTP_1_500_RESPONSE).await;
+                    }
+                }
+                if part.last {
+                    if stream.out_body == Some(OutBody::Chunked) {
+                        write_last_chunk(&mut buf, None);
+                    }
+                    self.finish_body(stream);
+                }
+
+                // Data is written synchronously, so return the window immediately
+                stream.out_window.increase(data.len() as isize);
+                self.out_window.increase(data.len());
+            }
+        }
+
+        self.write_all(&buf).await
+    }
+
+    fn encode_head(
+        &mut self,
+        stream: &mut Http1Stream,
+        headers: &Headers,
+        last: bool,
+        buf: &mut Vec<u8>,
+    ) {
+        let status = headers.get_opt_parse::<u32>(":status").unwrap_or(500);
+
+        let mut extra = Vec::new();
+
+        let out_body = if stream.is_head || status < 200 || status == 204 || status == 304 {
+            OutBody::None
+        } else if last {
+            if headers.get_opt("content-length").is_none() {
+                extra.push(("content-length", "0"));
+            }
+            OutBody::ContentLength
+        } else if headers.get_opt("content-length").is_some() {
+            OutBody::ContentLength
+        } else if stream.version == Http1Version::Http11 {
+            extra.push(("transfer-encoding", "chunked"));
+            OutBody::Chunked
+        } else {
+            stream.keep_alive = false;
+            OutBody::UntilClose
+        };
+
+        if self.graceful_shutdown {
+            stream.keep_alive = false;
+        }
+
+        if !stream.keep_alive {
+            extra.push(("connection", "close"));
+        } else if stream.version == Http1Version::Http10 {
+            extra.push(("connection", "keep-alive"));
+        }
+
+        if out_body == OutBody::ContentLength {
+            stream.out_rem_content_length = Some(headers.content_length().unwrap_or(0));
+        }
+
+        write_response_head(buf, status, headers, &extra);
+        stream.out_body = Some(out_body);
+    }
+
+    fn finish_body(&mut self, stream: &mut Http1Stream) {
+        if stream.out_body == Some(OutBody::UntilClose) {
+            stream.keep_alive = false;
+        }
+        if let Some(rem) = stream.out_rem_content_length.filter(|rem| *rem != 0) {
+            // closing connection is the only way to tell the client
+            warn!(
+                "stream {} body is {} bytes shorter than content-length",
+                stream.stream_id, rem
+            );
+            stream.out_aborted = true;
+        }
+        stream.out_done = true;
+    }
+
+    async fn stream_end(
+        &mut self,
+        stream: &mut Http1Stream,
+        error_code: ErrorCode,
+    ) -> result::Result<()> {
+        if stream.out_done {
+            return Ok(());
+        }
+
+        if stream.out_body.is_none() {
+            warn!(
+                "stream {} reset before headers: {:?}",
+                stream.stream_id, error_code
+            );
+            stream.out_aborted = true;
+            return self.write_all(HTTP_1_500_RESPONSE).await;
+        }
+
+        if error_code != ErrorCode::NoError {
+            // The only way to tell the client response is incomplete
+            warn!("stream {} reset: {:?}", stream.stream_id, error_code);
+            stream.out_aborted = true;
+            return Ok(());
+        }
+
+        let mut buf = Vec::new();
+        if stream.out_body == Some(OutBody::Chunked) {
+            write_last_chunk(&mut buf, None);
+        }
+        self.finish_body(stream);
+        self.write_all(&buf).await
+    }
+}
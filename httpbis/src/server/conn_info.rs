@@ -0,0 +1,69 @@
+//! Information about server connection available to handlers.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use crate::net::addr::AnySocketAddr;
+use crate::server::proxy_protocol::ProxyHeader;
+
+/// Last connection id allocated in this process.
+static LAST_CONN_ID: AtomicU64 = AtomicU64::new(0);
+
+/// New connection id, unique in this process.
+pub(crate) fn next_conn_id() -> u64 {
+    LAST_CONN_ID.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Connection a request was received on.
+///
+/// Client certificate is not available: `tls_api` 0.4 provides
+/// no access to the peer certificate of an accepted TLS stream.
+#[derive(Debug)]
+pub struct ServerConnInfo {
+    pub(crate) id: u64,
+    pub(crate) peer_addr: AnySocketAddr,
+    pub(crate) tls: bool,
+    pub(crate) tls_sni: Option<String>,
+    pub(crate) alpn_protocol: Option<Vec<u8>>,
+    pub(crate) proxy_header: Option<ProxyHeader>,
+}
+
+impl ServerConnInfo {
+    /// Connection id, unique in the process.
+    ///
+    /// Same as connection key in `ServerStateSnapshot::conns`.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Client address.
+    ///
+    /// Source address from PROXY header if `ServerConf::proxy_protocol` is enabled.
+    pub fn peer_addr(&self) -> &AnySocketAddr {
+        &self.peer_addr
+    }
+
+    /// Connection is TLS.
+    pub fn is_tls(&self) -> bool {
+        self.tls
+    }
+
+    /// Server name sent by client in TLS handshake, in lower case.
+    ///
+    /// `None` for plain connections or when client sent no server name.
+    pub fn tls_sni(&self) -> Option<&str> {
+        self.tls_sni.as_deref()
+    }
+
+    /// Protocol negotiated with ALPN, `None` for plain connections
+    /// or if client did not use ALPN.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// PROXY protocol header of the connection,
+    /// `None` unless `ServerConf::proxy_protocol` is enabled.
+    pub fn proxy_header(&self) -> Option<&ProxyHeader> {
+        self.proxy_header.as_ref()
+    }
+}
@@ -0,0 +1,176 @@
+//! Assignment of accepted connections to event loops.
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use rand::thread_rng;
+use rand::Rng;
+use tokio::runtime::Handle;
+
+use crate::server::conf::ServerConnLoopSelection;
+
+/// Live connections and streams of an event loop.
+#[derive(Default)]
+pub(crate) struct ConnLoopLoad {
+    conns: AtomicUsize,
+    streams: AtomicUsize,
+}
+
+impl ConnLoopLoad {
+    /// Count a stream until the guard is dropped.
+    pub fn stream_guard(self: &Arc<Self>) -> ConnLoopStreamGuard {
+        self.streams.fetch_add(1, Ordering::SeqCst);
+        ConnLoopStreamGuard { load: self.clone() }
+    }
+
+    fn snapshot(&self) -> ConnLoopStateSnapshot {
+        ConnLoopStateSnapshot {
+            conns: self.conns.load(Ordering::SeqCst),
+            streams: self.streams.load(Ordering::SeqCst),
+        }
+    }
+}
+
+pub(crate) struct ConnLoopConnGuard {
+    load: Arc<ConnLoopLoad>,
+}
+
+impl Drop for ConnLoopConnGuard {
+    fn drop(&mut self) {
+        self.load.conns.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub(crate) struct ConnLoopStreamGuard {
+    load: Arc<ConnLoopLoad>,
+}
+
+impl Drop for ConnLoopStreamGuard {
+    fn drop(&mut self) {
+        self.load.streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Event loops running connections of a server.
+pub(crate) struct ConnLoops {
+    handles: Vec<Handle>,
+    loads: Vec<Arc<ConnLoopLoad>>,
+    selection: ServerConnLoopSelection,
+    next: usize,
+}
+
+impl ConnLoops {
+    /// Empty `handles` means connections are run by the listener loop,
+    /// which is set later with `set_default_handle`.
+    pub fn new(handles: Vec<Handle>, selection: ServerConnLoopSelection) -> ConnLoops {
+        let loads = (0..handles.len().max(1))
+            .map(|_| Arc::new(ConnLoopLoad::default()))
+            .collect();
+        ConnLoops {
+            handles,
+            loads,
+            selection,
+            next: 0,
+        }
+    }
+
+    pub fn set_default_handle(&mut self, handle: Handle) {
+        if self.handles.is_empty() {
+            self.handles.push(handle);
+        }
+    }
+
+    /// Loads shared with server state to build snapshots.
+    pub fn loads(&self) -> Vec<Arc<ConnLoopLoad>> {
+        self.loads.clone()
+    }
+
+    fn select_index(&mut self) -> usize {
+        let len = self.loads.len();
+        let start = self.next;
+        self.next = (self.next + 1) % len;
+        match self.selection {
+            ServerConnLoopSelection::RoundRobin => start,
+            ServerConnLoopSelection::LeastConnections => {
+                // ties are resolved in turn
+                (0..len)
+                    .map(|i| (start + i) % len)
+                    .min_by_key(|&i| {
+                        let load = self.loads[i].snapshot();
+                        (load.conns, load.streams)
+                    })
+                    .unwrap()
+            }
+            ServerConnLoopSelection::Random => thread_rng().gen_range(0, len),
+        }
+    }
+
+    /// Select a loop for a new connection.
+    ///
+    /// Connection is counted until the guard is dropped.
+    pub fn select(&mut self) -> (Handle, Arc<ConnLoopLoad>, ConnLoopConnGuard) {
+        let i = self.select_index();
+        let load = self.loads[i].clone();
+        load.conns.fetch_add(1, Ordering::SeqCst);
+        let guard = ConnLoopConnGuard { load: load.clone() };
+        (self.handles[i].clone(), load, guard)
+    }
+}
+
+pub(crate) fn snapshot(loads: &[Arc<ConnLoopLoad>]) -> Vec<ConnLoopStateSnapshot> {
+    loads.iter().map(|load| load.snapshot()).collect()
+}
+
+/// Connection and stream counts of a connection event loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnLoopStateSnapshot {
+    /// Open connections
+    pub conns: usize,
+    /// Open HTTP/2 streams, including pushed streams
+    pub streams: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn select_n(loops: &mut ConnLoops, n: usize) -> Vec<ConnLoopConnGuard> {
+        (0..n).map(|_| loops.select().2).collect()
+    }
+
+    fn conns(loops: &ConnLoops) -> Vec<usize> {
+        snapshot(&loops.loads)
+            .into_iter()
+            .map(|s| s.conns)
+            .collect()
+    }
+
+    #[test]
+    fn selection() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let handles = vec![rt.handle().clone(); 3];
+
+        let mut loops = ConnLoops::new(handles.clone(), ServerConnLoopSelection::RoundRobin);
+        let guards = select_n(&mut loops, 4);
+        assert_eq!(vec![2, 1, 1], conns(&loops));
+        drop(guards);
+        assert_eq!(vec![0, 0, 0], conns(&loops));
+
+        let mut loops = ConnLoops::new(handles.clone(), ServerConnLoopSelection::LeastConnections);
+        let mut guards = select_n(&mut loops, 3);
+        assert_eq!(vec![1, 1, 1], conns(&loops));
+        // loop with a busy connection gets new connections last
+        let _stream = loops.loads[1].stream_guard();
+        guards.remove(0);
+        guards.remove(0);
+        guards.extend(select_n(&mut loops, 2));
+        assert_eq!(vec![1, 1, 1], conns(&loops));
+        guards.extend(select_n(&mut loops, 2));
+        assert_eq!(vec![2, 1, 2], conns(&loops));
+
+        let mut loops = ConnLoops::new(handles, ServerConnLoopSelection::Random);
+        let _guards = select_n(&mut loops, 10);
+        assert_eq!(10, conns(&loops).iter().sum::<usize>());
+    }
+}
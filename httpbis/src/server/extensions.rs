@@ -0,0 +1,80 @@
+//! Typed values attached to a request.
+
+use std::any::Any;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Map of values keyed by their type.
+///
+/// Used to pass data (e. g. captured path parameters) from
+/// wrapping handlers to inner handlers.
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Default::default()
+    }
+
+    /// Insert a value, returning previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.map
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|prev| prev.downcast().ok())
+            .map(|prev| *prev)
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_ref())
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_mut())
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|v| v.downcast().ok())
+            .map(|v| *v)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut e = Extensions::new();
+        assert!(e.is_empty());
+        assert_eq!(None, e.insert(10u32));
+        assert_eq!(Some(10), e.insert(20u32));
+        e.insert("str");
+        assert_eq!(Some(&20u32), e.get::<u32>());
+        assert_eq!(Some(&"str"), e.get::<&str>());
+        assert_eq!(None, e.get::<u64>());
+        *e.get_mut::<u32>().unwrap() += 1;
+        assert_eq!(Some(21u32), e.remove::<u32>());
+        assert_eq!(None, e.get::<u32>());
+    }
+}
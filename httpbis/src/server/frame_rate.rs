@@ -0,0 +1,122 @@
+//! Per-connection rate limits of frames received from client.
+
+use tokio::time::Instant;
+
+use crate::server::conf::ServerConf;
+use crate::server::conf::ServerFrameRateLimit;
+use crate::solicit::frame::HttpFrameDecoded;
+use crate::solicit::frame::HttpFrameType;
+
+/// Frames counted in the current period.
+struct FrameRate {
+    limit: ServerFrameRateLimit,
+    period_start: Instant,
+    count: u32,
+}
+
+impl FrameRate {
+    fn new(limit: ServerFrameRateLimit, now: Instant) -> FrameRate {
+        FrameRate {
+            limit,
+            period_start: now,
+            count: 0,
+        }
+    }
+
+    /// Count a frame, return `true` if limit is exceeded.
+    fn hit(&mut self, now: Instant) -> bool {
+        if now >= self.period_start + self.limit.period {
+            self.period_start = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        self.count > self.limit.count
+    }
+}
+
+/// Rate limits configured in `ServerConf`.
+pub(crate) struct FrameRateLimits {
+    resets: Option<FrameRate>,
+    control: Option<FrameRate>,
+    empty: Option<FrameRate>,
+    /// Frame type which exceeded the limit
+    exceeded: Option<HttpFrameType>,
+}
+
+impl FrameRateLimits {
+    pub fn new(conf: &ServerConf) -> FrameRateLimits {
+        let now = Instant::now();
+        FrameRateLimits {
+            resets: conf.max_reset_rate.map(|l| FrameRate::new(l, now)),
+            control: conf.max_control_frame_rate.map(|l| FrameRate::new(l, now)),
+            empty: conf.max_empty_frame_rate.map(|l| FrameRate::new(l, now)),
+            exceeded: None,
+        }
+    }
+
+    /// Count received frame.
+    ///
+    /// Return type of the frame which exceeded a limit,
+    /// once exceeded it is returned for all subsequent frames.
+    pub fn check(&mut self, frame: &HttpFrameDecoded, now: Instant) -> Option<HttpFrameType> {
+        if self.exceeded.is_some() {
+            return self.exceeded;
+        }
+
+        let (rate, frame_type) = match frame {
+            HttpFrameDecoded::RstStream(_) => (&mut self.resets, HttpFrameType::RstStream),
+            HttpFrameDecoded::Ping(f) if !f.is_ack() => (&mut self.control, HttpFrameType::Ping),
+            HttpFrameDecoded::Settings(f) if !f.is_ack() => {
+                (&mut self.control, HttpFrameType::Settings)
+            }
+            HttpFrameDecoded::Priority(_) => (&mut self.control, HttpFrameType::Priority),
+            HttpFrameDecoded::Data(f) if f.data.is_empty() && !f.is_end_of_stream() => {
+                (&mut self.empty, HttpFrameType::Data)
+            }
+            _ => return None,
+        };
+
+        if let Some(rate) = rate {
+            if rate.hit(now) {
+                self.exceeded = Some(frame_type);
+            }
+        }
+        self.exceeded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::solicit::frame::PingFrame;
+    use crate::solicit::frame::RstStreamFrame;
+    use crate::ErrorCode;
+
+    #[test]
+    fn check() {
+        let mut conf = ServerConf::new();
+        conf.max_control_frame_rate = Some(ServerFrameRateLimit {
+            count: 2,
+            period: Duration::from_secs(1),
+        });
+
+        let mut limits = FrameRateLimits::new(&conf);
+        let start = Instant::now();
+
+        let ping = HttpFrameDecoded::Ping(PingFrame::new());
+        let rst = HttpFrameDecoded::RstStream(RstStreamFrame::new(1, ErrorCode::Cancel));
+
+        assert_eq!(None, limits.check(&ping, start));
+        assert_eq!(None, limits.check(&ping, start));
+        // resets are not limited
+        assert_eq!(None, limits.check(&rst, start));
+        // next period
+        let next = start + Duration::from_secs(1);
+        assert_eq!(None, limits.check(&ping, next));
+        assert_eq!(None, limits.check(&ping, next));
+        assert_eq!(Some(HttpFrameType::Ping), limits.check(&ping, next));
+        assert_eq!(Some(HttpFrameType::Ping), limits.check(&rst, next));
+    }
+}
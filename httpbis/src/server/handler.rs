@@ -1,10 +1,18 @@
 use crate::result;
+use crate::server::cancel::ServerCancelled;
+use crate::server::conn_info::ServerConnInfo;
 use crate::server::req::ServerRequest;
+use crate::solicit::stream_id::StreamId;
+use crate::Headers;
 use crate::ServerResponse;
+use std::sync::Arc;
 use tokio::runtime::Handle;
 
 pub struct ServerHandlerContext {
     pub(crate) loop_handle: Handle,
+    pub(crate) conn_info: Arc<ServerConnInfo>,
+    pub(crate) stream_id: StreamId,
+    pub(crate) cancelled: ServerCancelled,
 }
 
 impl ServerHandlerContext {
@@ -12,6 +20,24 @@ impl ServerHandlerContext {
     pub fn loop_remote(&self) -> Handle {
         self.loop_handle.clone()
     }
+
+    /// Connection the request was received on.
+    ///
+    /// Peer address, TLS server name, ALPN protocol and PROXY header
+    /// are accessed through `ServerConnInfo`.
+    pub fn conn_info(&self) -> &ServerConnInfo {
+        &self.conn_info
+    }
+
+    /// Stream id of the request, HTTP/1 requests are numbered like HTTP/2 streams.
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    /// Same as `ServerResponse::cancelled`, usable after response is moved.
+    pub fn cancelled(&self) -> ServerCancelled {
+        self.cancelled.clone()
+    }
 }
 
 /// Central HTTP/2 service interface.
@@ -30,4 +56,11 @@ pub trait ServerHandler: Send + Sync + 'static {
         req: ServerRequest,
         resp: ServerResponse,
     ) -> result::Result<()>;
+
+    /// Override `ServerConf::max_request_body_size` for the request.
+    ///
+    /// Called before `start_request`. `None` means use server configuration.
+    fn max_request_body_size(&self, _headers: &Headers) -> Option<u64> {
+        None
+    }
 }
@@ -0,0 +1,146 @@
+//! Adapter for handlers written as async functions.
+
+use std::future::Future;
+use std::mem;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use futures::future;
+use futures::future::Either;
+use futures::future::FutureExt;
+use futures::pin_mut;
+
+use crate::error;
+use crate::misc::any_to_string;
+use crate::result;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::req::ServerRequest;
+use crate::ErrorCode;
+use crate::Headers;
+use crate::HttpStreamAfterHeaders;
+use crate::Response;
+use crate::SenderState;
+use crate::ServerResponse;
+
+/// Server handler which returns response asynchronously.
+///
+/// Unlike `ServerHandler` it does not deal with `ServerResponse`,
+/// returned `Response` future resolves to response headers and body stream
+/// with optional trailers. Use `AsyncServerHandlerAdapter` to serve it.
+///
+/// Closures of the same signature implement this trait.
+pub trait AsyncServerHandler: Send + Sync + 'static {
+    /// Handle request with given headers and request body stream.
+    fn handle(
+        &self,
+        context: ServerHandlerContext,
+        headers: Headers,
+        body: HttpStreamAfterHeaders,
+    ) -> Response;
+}
+
+impl<F> AsyncServerHandler for F
+where
+    F: Fn(ServerHandlerContext, Headers, HttpStreamAfterHeaders) -> Response
+        + Send
+        + Sync
+        + 'static,
+{
+    fn handle(
+        &self,
+        context: ServerHandlerContext,
+        headers: Headers,
+        body: HttpStreamAfterHeaders,
+    ) -> Response {
+        self(context, headers, body)
+    }
+}
+
+/// `ServerHandler` which serves `AsyncServerHandler`.
+///
+/// Response future is spawned on connection event loop and dropped
+/// if the stream is reset by client. Handler panic is replied with 500
+/// if headers were not sent yet, error is replied with `RST_STREAM`.
+pub struct AsyncServerHandlerAdapter<H: AsyncServerHandler> {
+    handler: Arc<H>,
+}
+
+impl<H: AsyncServerHandler> AsyncServerHandlerAdapter<H> {
+    pub fn new(handler: H) -> AsyncServerHandlerAdapter<H> {
+        AsyncServerHandlerAdapter {
+            handler: Arc::new(handler),
+        }
+    }
+}
+
+/// `RST_STREAM` error code for handler error
+fn error_to_rst_code(e: &error::Error) -> ErrorCode {
+    match *e {
+        error::Error::CodeError(code) => code,
+        _ => ErrorCode::InternalError,
+    }
+}
+
+async fn send_response<F>(response: F, mut resp: ServerResponse)
+where
+    F: Future<Output = result::Result<(Headers, HttpStreamAfterHeaders)>>,
+{
+    let response = AssertUnwindSafe(response).catch_unwind();
+    pin_mut!(response);
+    // also resolved if the stream is reset by the server, e.g. by timeout
+    let finished = resp.cancelled().finished();
+    pin_mut!(finished);
+
+    let r = match future::select(response, finished).await {
+        Either::Left((r, _)) => r,
+        Either::Right(((), _)) => {
+            debug!("stream is closed, dropping handler future");
+            return;
+        }
+    };
+
+    let r = match r {
+        Ok(Ok((headers, body))) => resp
+            .send_headers(headers)
+            .and_then(|()| resp.pull_from_stream(body.catch_unwind())),
+        Ok(Err(e)) => {
+            warn!("handler returned error: {:?}", e);
+            resp.reset(error_to_rst_code(&e))
+        }
+        Err(e) => {
+            let e = any_to_string(e);
+            warn!("handler panicked: {}", e);
+            match resp.state() {
+                SenderState::ExpectingHeaders => resp.send_internal_error_500("handler panicked"),
+                _ => resp.reset(ErrorCode::InternalError),
+            }
+        }
+    };
+
+    if let Err(e) = r {
+        // stream or connection is dead
+        debug!("failed to send response: {:?}", e);
+    }
+}
+
+impl<H: AsyncServerHandler> ServerHandler for AsyncServerHandlerAdapter<H> {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        mut req: ServerRequest,
+        resp: ServerResponse,
+    ) -> result::Result<()> {
+        let loop_handle = context.loop_remote();
+
+        let headers = mem::replace(&mut req.headers, Headers::new());
+        let body = req.make_stream();
+
+        // `handle` is invoked inside the future, so its panic is handled too
+        let handler = self.handler.clone();
+        let response = async move { handler.handle(context, headers, body).0.await };
+
+        loop_handle.spawn(send_response(response, resp));
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+//! Response compression middleware.
+
+use std::sync::Arc;
+
+use crate::content_coding::BodyEncoder;
+use crate::content_coding::ContentCoding;
+use crate::result;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::handler_layer::ServerHandlerLayer;
+use crate::server::req::ServerRequest;
+use crate::solicit::end_stream::EndStream;
+use crate::Headers;
+use crate::ServerResponse;
+
+/// Layer which compresses response body with `gzip` or `deflate`
+/// if the request `accept-encoding` allows it.
+///
+/// Body is compressed chunk by chunk as it is sent, trailers are sent as is.
+/// Compressed response gets `content-encoding` header, `content-length`
+/// is removed and strong `etag` is made weak. Responses which could be
+/// compressed get `vary: accept-encoding` whether compressed or not.
+/// Response to `HEAD` gets the headers response to `GET` would get.
+///
+/// Responses are not compressed if they already have `content-encoding`,
+/// `cache-control: no-transform`, content type which is usually already
+/// compressed (images, audio, video, archives), or `content-length`
+/// smaller than the configured minimum size.
+pub struct ServerHandlerCompressionLayer {
+    min_size: u64,
+}
+
+impl Default for ServerHandlerCompressionLayer {
+    fn default() -> Self {
+        ServerHandlerCompressionLayer::new()
+    }
+}
+
+impl ServerHandlerCompressionLayer {
+    /// Default minimum size of the response body to compress.
+    pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+    pub fn new() -> ServerHandlerCompressionLayer {
+        ServerHandlerCompressionLayer {
+            min_size: ServerHandlerCompressionLayer::DEFAULT_MIN_SIZE,
+        }
+    }
+
+    /// Do not compress responses with `content-length` smaller than this.
+    ///
+    /// Responses without `content-length` are always compressed.
+    pub fn min_size(mut self, min_size: u64) -> ServerHandlerCompressionLayer {
+        self.min_size = min_size;
+        self
+    }
+}
+
+/// Content types which are usually already compressed.
+fn is_compressed_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap()
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "image/svg+xml" => false,
+        "application/zip"
+        | "application/gzip"
+        | "application/x-gzip"
+        | "application/x-bzip2"
+        | "application/x-xz"
+        | "application/x-7z-compressed"
+        | "application/x-rar-compressed"
+        | "application/zstd"
+        | "font/woff"
+        | "font/woff2" => true,
+        _ => mime.starts_with("image/") || mime.starts_with("audio/") || mime.starts_with("video/"),
+    }
+}
+
+/// Add `vary: accept-encoding` unless already present.
+fn add_vary(headers: &mut Headers) {
+    let has_vary = headers.iter().filter(|h| h.name() == "vary").any(|h| {
+        String::from_utf8_lossy(h.value()).split(',').any(|v| {
+            let v = v.trim();
+            v == "*" || v.eq_ignore_ascii_case("accept-encoding")
+        })
+    });
+    if !has_vary {
+        headers.add("vary", "accept-encoding");
+    }
+}
+
+/// Update headers for negotiated coding, return coding if the response should be compressed.
+///
+/// `vary` is added if compression depends on `accept-encoding`,
+/// even if no coding is acceptable.
+fn prepare_headers(
+    headers: &mut Headers,
+    coding: Option<ContentCoding>,
+    min_size: u64,
+) -> Option<ContentCoding> {
+    if headers.get_opt("content-encoding").is_some() {
+        return None;
+    }
+    if let Some(cache_control) = headers.get_opt("cache-control") {
+        if cache_control.to_ascii_lowercase().contains("no-transform") {
+            return None;
+        }
+    }
+    if let Some(content_type) = headers.get_opt("content-type") {
+        if is_compressed_content_type(content_type) {
+            return None;
+        }
+    }
+    match headers.get_opt_parse::<u32>(":status") {
+        // representation would be compressed in 200 response
+        Some(204) | Some(206) | Some(304) => {
+            add_vary(headers);
+            return None;
+        }
+        _ => {}
+    }
+    if let Some(content_length) = headers.content_length() {
+        if content_length < min_size {
+            return None;
+        }
+    }
+
+    add_vary(headers);
+    let coding = coding?;
+
+    headers.remove("content-length");
+    headers.add("content-encoding", coding.name());
+    // compressed body is not byte-for-byte identical to the original,
+    // so it must not share a strong validator with it
+    if let Some(etag) = headers.get_opt("etag") {
+        if !etag.starts_with("W/") {
+            let etag = format!("W/{}", etag);
+            headers.remove("etag");
+            headers.add("etag", etag);
+        }
+    }
+    Some(coding)
+}
+
+struct CompressionHandler {
+    inner: Arc<dyn ServerHandler>,
+    min_size: u64,
+}
+
+impl ServerHandler for CompressionHandler {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> result::Result<()> {
+        let coding = req
+            .headers
+            .get_opt("accept-encoding")
+            .and_then(ContentCoding::negotiate);
+        let head = req.headers.method() == "HEAD";
+
+        let min_size = self.min_size;
+        resp.set_body_encoder_factory(move |headers, end_stream| {
+            // empty body is sent as is,
+            // but headers of response to `HEAD` describe the body of `GET`
+            let coding = match end_stream {
+                EndStream::Yes if !head => None,
+                _ => coding,
+            };
+            match prepare_headers(headers, coding, min_size) {
+                Some(coding) if !head => Some(BodyEncoder::new(coding)),
+                _ => None,
+            }
+        });
+
+        self.inner.start_request(context, req, resp)
+    }
+
+    fn max_request_body_size(&self, headers: &Headers) -> Option<u64> {
+        self.inner.max_request_body_size(headers)
+    }
+}
+
+impl ServerHandlerLayer for ServerHandlerCompressionLayer {
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> {
+        Arc::new(CompressionHandler {
+            inner,
+            min_size: self.min_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prepare_headers_skip() {
+        let gzip = Some(ContentCoding::Gzip);
+
+        let mut headers = Headers::ok_200();
+        headers.add("content-type", "image/png");
+        assert_eq!(None, prepare_headers(&mut headers, gzip, 0));
+        assert_eq!(None, headers.get_opt("vary"));
+
+        let mut headers = Headers::ok_200();
+        headers.add("content-length", "10");
+        assert_eq!(None, prepare_headers(&mut headers, gzip, 100));
+        assert_eq!(Some("10"), headers.get_opt("content-length"));
+
+        let mut headers = Headers::ok_200();
+        headers.add("content-encoding", "br");
+        assert_eq!(None, prepare_headers(&mut headers, gzip, 0));
+
+        let mut headers = Headers::new_status(304);
+        assert_eq!(None, prepare_headers(&mut headers, gzip, 0));
+        assert_eq!(Some("accept-encoding"), headers.get_opt("vary"));
+
+        // no acceptable coding
+        let mut headers = Headers::ok_200();
+        headers.add("content-length", "1000");
+        assert_eq!(None, prepare_headers(&mut headers, None, 100));
+        assert_eq!(Some("1000"), headers.get_opt("content-length"));
+        assert_eq!(Some("accept-encoding"), headers.get_opt("vary"));
+    }
+
+    #[test]
+    fn prepare_headers_compress() {
+        let mut headers = Headers::ok_200();
+        headers.add("content-type", "application/json");
+        headers.add("content-length", "1000");
+        headers.add("vary", "Origin, Accept-Encoding");
+        headers.add("etag", "\"abc\"");
+        assert_eq!(
+            Some(ContentCoding::Deflate),
+            prepare_headers(&mut headers, Some(ContentCoding::Deflate), 100)
+        );
+        assert_eq!(None, headers.get_opt("content-length"));
+        assert_eq!(Some("deflate"), headers.get_opt("content-encoding"));
+        assert_eq!(1, headers.iter().filter(|h| h.name() == "vary").count());
+        assert_eq!(Some("W/\"abc\""), headers.get_opt("etag"));
+
+        let mut headers = Headers::ok_200();
+        headers.add("etag", "W/\"abc\"");
+        assert_eq!(
+            Some(ContentCoding::Gzip),
+            prepare_headers(&mut headers, Some(ContentCoding::Gzip), 100)
+        );
+        assert_eq!(Some("accept-encoding"), headers.get_opt("vary"));
+        assert_eq!(Some("W/\"abc\""), headers.get_opt("etag"));
+    }
+}
@@ -0,0 +1,713 @@
+//! Serve files from a directory.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::fs::Metadata;
+use std::io;
+use std::ops::Range;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use bytes::Bytes;
+use futures::stream;
+use futures::stream::Stream;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use crate::common::sender::SendError;
+use crate::result;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::handler_paths::PathParams;
+use crate::server::req::ServerRequest;
+use crate::Headers;
+use crate::ServerResponse;
+
+/// Max number of ranges in `range` header, request with more ranges
+/// is replied with the whole file.
+const MAX_RANGES: usize = 16;
+
+/// `ServerHandler` serving files from a directory.
+///
+/// Request path is resolved relative to the root directory, paths leaving
+/// the root (with `..` segments or symlinks) are replied with 404.
+/// Directory is served with its `index.html`.
+///
+/// Only `GET` and `HEAD` are allowed. Handler supports `range` requests
+/// (including multipart), `if-none-match` and `if-modified-since`
+/// conditional requests, and optionally serves precompressed `.br`
+/// and `.gz` siblings of files when client accepts them.
+///
+/// File is read in chunks only when stream window is available.
+pub struct StaticFilesHandler {
+    root: PathBuf,
+    path_param: Option<String>,
+    precompressed: bool,
+    chunk_size: usize,
+}
+
+impl StaticFilesHandler {
+    pub fn new<P: Into<PathBuf>>(root: P) -> StaticFilesHandler {
+        StaticFilesHandler {
+            root: root.into(),
+            path_param: None,
+            precompressed: false,
+            chunk_size: 0x4000,
+        }
+    }
+
+    /// Take file path from the path parameter captured by `ServerHandlerPaths`
+    /// (e. g. `path` for handler registered as `/static/{*path}`)
+    /// instead of the whole request path.
+    pub fn set_path_param(&mut self, name: &str) {
+        self.path_param = Some(name.to_owned());
+    }
+
+    /// Serve `<file>.br` or `<file>.gz` if it exists and client accepts the encoding.
+    pub fn set_precompressed(&mut self, precompressed: bool) {
+        self.precompressed = precompressed;
+    }
+
+    /// Max size of `DATA` chunk read from file.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        assert!(chunk_size > 0);
+        self.chunk_size = chunk_size;
+    }
+
+    fn request_path(&self, req: &ServerRequest) -> Option<PathBuf> {
+        let path = match &self.path_param {
+            Some(name) => req
+                .extensions
+                .get::<PathParams>()
+                .and_then(|params| params.get(name))
+                .unwrap_or(""),
+            None => req.headers.path(),
+        };
+        let path = path.split(['?', '#']).next().unwrap();
+        safe_relative_path(&percent_decode(path)?)
+    }
+}
+
+/// Decode `%XX` sequences, `None` if path is malformed or not UTF-8.
+fn percent_decode(path: &str) -> Option<String> {
+    fn hex(b: u8) -> Option<u8> {
+        (b as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let mut r = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let h = hex(bytes.next()?)?;
+            let l = hex(bytes.next()?)?;
+            r.push(h << 4 | l);
+        } else {
+            r.push(b);
+        }
+    }
+    String::from_utf8(r).ok()
+}
+
+/// Relative path of normal components, `None` if path tries to escape the root.
+fn safe_relative_path(path: &str) -> Option<PathBuf> {
+    let mut r = PathBuf::new();
+    for segment in path.split('/') {
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        if segment.contains('\0') {
+            return None;
+        }
+        // rejects `..`, and also drive prefixes and backslashes on Windows
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => r.push(segment),
+            _ => return None,
+        }
+    }
+    Some(r)
+}
+
+fn guess_content_type(path: &Path) -> &'static str {
+    let ext = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => ext.to_ascii_lowercase(),
+        None => return "application/octet-stream",
+    };
+    match &ext[..] {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "text/xml; charset=utf-8",
+        "json" | "map" => "application/json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Days since epoch to (year, month, day), proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Inverse of `civil_from_days`.
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if m > 2 { m - 3 } else { m + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format time as IMF-fixdate, e. g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs() as i64;
+    let days = secs / 86400;
+    let rem = secs % 86400;
+    let (y, m, d) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        d,
+        MONTHS[m as usize - 1],
+        y,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Parse IMF-fixdate, obsolete date formats are not supported.
+fn parse_http_date(s: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return None;
+    }
+    let d: u32 = parts[1].parse().ok()?;
+    let m = MONTHS.iter().position(|&m| m == parts[2])? as u32 + 1;
+    let y: i64 = parts[3].parse().ok()?;
+    let hms: Vec<u64> = parts[4]
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    if hms.len() != 3
+        || hms[0] > 23
+        || hms[1] > 59
+        || hms[2] > 60
+        || !(1..=31).contains(&d)
+        || y < 1970
+    {
+        return None;
+    }
+    let secs = days_from_civil(y, m, d) as u64 * 86400 + hms[0] * 3600 + hms[1] * 60 + hms[2];
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Seconds since epoch, HTTP dates have one second precision.
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// True if `accept-encoding` header value accepts the encoding.
+fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut params = item.split(';');
+        let name = params.next().unwrap().trim();
+        if !name.eq_ignore_ascii_case(encoding) && name != "*" {
+            return false;
+        }
+        params.all(|p| match p.trim().strip_prefix("q=") {
+            Some(q) => q.trim().parse::<f32>().map(|q| q > 0.0).unwrap_or(false),
+            None => true,
+        })
+    })
+}
+
+/// True if `if-none-match` header value matches the entity tag (weak comparison).
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let weak = |t: &str| t.trim().trim_start_matches("W/").to_owned();
+    let etag = weak(etag);
+    if_none_match
+        .split(',')
+        .any(|t| t.trim() == "*" || weak(t) == etag)
+}
+
+/// Parse `range` header value.
+///
+/// `None` if range is malformed or not in bytes, and must be ignored.
+/// Empty vec if no range is satisfiable.
+fn parse_range(value: &str, len: u64) -> Option<Vec<Range<u64>>> {
+    let specs = value.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        let dash = spec.find('-')?;
+        let (first, last) = (&spec[..dash], &spec[dash + 1..]);
+        if first.is_empty() {
+            let suffix: u64 = last.parse().ok()?;
+            if suffix > 0 && len > 0 {
+                ranges.push(len.saturating_sub(suffix)..len);
+            }
+            continue;
+        }
+        let first: u64 = first.parse().ok()?;
+        let end = if last.is_empty() {
+            len
+        } else {
+            let last: u64 = last.parse().ok()?;
+            if last < first {
+                return None;
+            }
+            cmp::min(last.saturating_add(1), len)
+        };
+        if first < len {
+            ranges.push(first..end);
+        }
+    }
+    if ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(ranges)
+}
+
+/// Part of response body.
+enum BodyPart {
+    Bytes(Bytes),
+    File(Range<u64>),
+}
+
+impl BodyPart {
+    fn len(&self) -> u64 {
+        match self {
+            BodyPart::Bytes(b) => b.len() as u64,
+            BodyPart::File(r) => r.end - r.start,
+        }
+    }
+}
+
+/// Read body parts from the file in chunks of at most `chunk_size`.
+fn body_stream(
+    file: fs::File,
+    parts: Vec<BodyPart>,
+    chunk_size: usize,
+) -> impl Stream<Item = result::Result<Bytes>> + Send {
+    let parts: VecDeque<_> = parts.into_iter().collect();
+    stream::try_unfold(
+        (file, None, parts),
+        move |(mut file, mut pos, mut parts)| async move {
+            let chunk = match parts.pop_front() {
+                None => return Ok(None),
+                Some(BodyPart::Bytes(bytes)) => bytes,
+                Some(BodyPart::File(range)) => {
+                    if pos != Some(range.start) {
+                        file.seek(io::SeekFrom::Start(range.start)).await?;
+                    }
+                    let len = cmp::min(range.end - range.start, chunk_size as u64);
+                    let mut buf = vec![0; len as usize];
+                    let n = file.read(&mut buf).await?;
+                    if n == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "file truncated while serving",
+                        )
+                        .into());
+                    }
+                    buf.truncate(n);
+                    let start = range.start + n as u64;
+                    if start < range.end {
+                        parts.push_front(BodyPart::File(start..range.end));
+                    }
+                    pos = Some(start);
+                    Bytes::from(buf)
+                }
+            };
+            Ok(Some((chunk, (file, pos, parts))))
+        },
+    )
+}
+
+/// File selected to serve the request.
+struct ResolvedFile {
+    path: PathBuf,
+    meta: Metadata,
+    /// `content-encoding` of precompressed sibling
+    encoding: Option<&'static str>,
+}
+
+impl ResolvedFile {
+    fn etag(&self) -> String {
+        let modified = self
+            .meta
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let suffix = match self.encoding {
+            Some("br") => "-br",
+            Some(_) => "-gz",
+            None => "",
+        };
+        format!("\"{:x}-{:x}{}\"", self.meta.len(), modified, suffix)
+    }
+}
+
+/// Canonical path and metadata of a regular file within the root.
+async fn resolve_file(root: &Path, path: &Path) -> Option<(PathBuf, Metadata)> {
+    let path = fs::canonicalize(path).await.ok()?;
+    if !path.starts_with(root) {
+        debug!("path {} is outside of root", path.display());
+        return None;
+    }
+    let meta = fs::metadata(&path).await.ok()?;
+    if meta.is_file() {
+        Some((path, meta))
+    } else {
+        None
+    }
+}
+
+struct FilesRequest {
+    root: PathBuf,
+    path: PathBuf,
+    head: bool,
+    headers: Headers,
+    precompressed: bool,
+    chunk_size: usize,
+}
+
+impl FilesRequest {
+    async fn resolve(&self) -> io::Result<Option<ResolvedFile>> {
+        let root = fs::canonicalize(&self.root).await?;
+        let mut path = root.join(&self.path);
+        if fs::metadata(&path)
+            .await
+            .map(|m| m.is_dir())
+            .unwrap_or(false)
+        {
+            path.push("index.html");
+        }
+
+        let (path, meta) = match resolve_file(&root, &path).await {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        if self.precompressed {
+            let accept_encoding = self.headers.get_opt("accept-encoding").unwrap_or("");
+            for &(encoding, ext) in &[("br", "br"), ("gzip", "gz")] {
+                if !accepts_encoding(accept_encoding, encoding) {
+                    continue;
+                }
+                let mut sibling = path.clone().into_os_string();
+                sibling.push(".");
+                sibling.push(ext);
+                if let Some((_, meta)) = resolve_file(&root, Path::new(&sibling)).await {
+                    return Ok(Some(ResolvedFile {
+                        path: sibling.into(),
+                        meta,
+                        encoding: Some(encoding),
+                    }));
+                }
+            }
+        }
+
+        Ok(Some(ResolvedFile {
+            path,
+            meta,
+            encoding: None,
+        }))
+    }
+
+    fn not_modified(&self, file: &ResolvedFile, etag: &str) -> bool {
+        if let Some(if_none_match) = self.headers.get_opt("if-none-match") {
+            return etag_matches(if_none_match, etag);
+        }
+        let since = self
+            .headers
+            .get_opt("if-modified-since")
+            .and_then(parse_http_date);
+        match (since, file.meta.modified()) {
+            (Some(since), Ok(modified)) => unix_secs(modified) <= unix_secs(since),
+            _ => false,
+        }
+    }
+
+    /// Ranges requested, `None` if the whole file must be served.
+    fn ranges(&self, file: &ResolvedFile, etag: &str) -> Option<Vec<Range<u64>>> {
+        if self.head {
+            return None;
+        }
+        let range = self.headers.get_opt("range")?;
+        if let Some(if_range) = self.headers.get_opt("if-range") {
+            let fresh = match parse_http_date(if_range) {
+                Some(date) => file.meta.modified().map(unix_secs).ok() == Some(unix_secs(date)),
+                None => if_range.trim() == etag,
+            };
+            if !fresh {
+                return None;
+            }
+        }
+        parse_range(range, file.meta.len())
+    }
+
+    async fn serve(self, resp: &mut ServerResponse) -> Result<(), SendError> {
+        let file = match self.resolve().await {
+            Ok(Some(file)) => file,
+            Ok(None) => {
+                info!("file not found: {}", self.path.display());
+                return resp.send_headers_end_of_stream(Headers::not_found_404());
+            }
+            Err(e) => {
+                warn!("failed to resolve file {}: {:?}", self.path.display(), e);
+                return resp.send_headers_end_of_stream(Headers::internal_error_500());
+            }
+        };
+
+        let len = file.meta.len();
+        let etag = file.etag();
+        let content_type = match file.encoding {
+            // content type of the original file
+            Some(_) => guess_content_type(&file.path.with_extension("")),
+            None => guess_content_type(&file.path),
+        };
+
+        let add_validators = |headers: &mut Headers| {
+            headers.add("etag", etag.clone());
+            if let Ok(modified) = file.meta.modified() {
+                headers.add("last-modified", format_http_date(modified));
+            }
+            if self.precompressed {
+                headers.add("vary", "accept-encoding");
+            }
+        };
+
+        if self.not_modified(&file, &etag) {
+            let mut headers = Headers::new_status(304);
+            add_validators(&mut headers);
+            return resp.send_headers_end_of_stream(headers);
+        }
+
+        let (mut headers, parts) = match self.ranges(&file, &etag) {
+            None => (Headers::ok_200(), vec![BodyPart::File(0..len)]),
+            Some(ranges) if ranges.is_empty() => {
+                let mut headers = Headers::new_status(416);
+                headers.add("content-range", format!("bytes */{}", len));
+                return resp.send_headers_end_of_stream(headers);
+            }
+            Some(mut ranges) if ranges.len() == 1 => {
+                let range = ranges.pop().unwrap();
+                let mut headers = Headers::new_status(206);
+                headers.add(
+                    "content-range",
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                );
+                (headers, vec![BodyPart::File(range)])
+            }
+            Some(ranges) => {
+                let boundary = format!("{:016x}", rand::random::<u64>());
+                let mut parts = Vec::new();
+                for range in ranges {
+                    let part_headers = format!(
+                        "\r\n--{}\r\ncontent-type: {}\r\ncontent-range: bytes {}-{}/{}\r\n\r\n",
+                        boundary,
+                        content_type,
+                        range.start,
+                        range.end - 1,
+                        len
+                    );
+                    parts.push(BodyPart::Bytes(Bytes::from(part_headers)));
+                    parts.push(BodyPart::File(range));
+                }
+                parts.push(BodyPart::Bytes(Bytes::from(format!(
+                    "\r\n--{}--\r\n",
+                    boundary
+                ))));
+                let mut headers = Headers::new_status(206);
+                headers.add(
+                    "content-type",
+                    format!("multipart/byteranges; boundary={}", boundary),
+                );
+                (headers, parts)
+            }
+        };
+
+        if headers.get_opt("content-type").is_none() {
+            headers.add("content-type", content_type);
+        }
+        if let Some(encoding) = file.encoding {
+            headers.add("content-encoding", encoding);
+        }
+        headers.add("accept-ranges", "bytes");
+        let content_length: u64 = parts.iter().map(BodyPart::len).sum();
+        headers.add("content-length", content_length.to_string());
+        add_validators(&mut headers);
+
+        if self.head {
+            return resp.send_headers_end_of_stream(headers);
+        }
+
+        let f = match fs::File::open(&file.path).await {
+            Ok(f) => f,
+            Err(e) => {
+                warn!("failed to open file {}: {:?}", file.path.display(), e);
+                return resp.send_headers_end_of_stream(Headers::internal_error_500());
+            }
+        };
+
+        resp.send_headers(headers)?;
+        resp.pull_bytes_from_stream(body_stream(f, parts, self.chunk_size))
+    }
+}
+
+impl ServerHandler for StaticFilesHandler {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> result::Result<()> {
+        let head = match req.headers.method() {
+            "GET" => false,
+            "HEAD" => true,
+            _ => {
+                let mut headers = Headers::new_status(405);
+                headers.add("allow", "GET, HEAD");
+                resp.send_headers_end_of_stream(headers)?;
+                return Ok(());
+            }
+        };
+
+        let path = match self.request_path(&req) {
+            Some(path) => path,
+            None => {
+                info!("rejecting path {}", req.headers.path());
+                resp.send_headers_end_of_stream(Headers::not_found_404())?;
+                return Ok(());
+            }
+        };
+
+        let request = FilesRequest {
+            root: self.root.clone(),
+            path,
+            head,
+            headers: req.headers,
+            precompressed: self.precompressed,
+            chunk_size: self.chunk_size,
+        };
+
+        context.loop_remote().spawn(async move {
+            if let Err(e) = request.serve(&mut resp).await {
+                // stream or connection is dead
+                debug!("failed to send response: {:?}", e);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn http_date() {
+        let t = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", format_http_date(t));
+        assert_eq!(Some(t), parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(
+            "Thu, 01 Jan 1970 00:00:00 GMT",
+            format_http_date(UNIX_EPOCH)
+        );
+        let t = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!("Tue, 29 Feb 2000 00:00:00 GMT", format_http_date(t));
+        assert_eq!(Some(t), parse_http_date(&format_http_date(t)));
+        assert_eq!(None, parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"));
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(Some(vec![0..10]), parse_range("bytes=0-9", 100));
+        assert_eq!(Some(vec![90..100]), parse_range("bytes=-10", 100));
+        assert_eq!(Some(vec![90..100]), parse_range("bytes=90-", 100));
+        assert_eq!(Some(vec![95..100]), parse_range("bytes=95-200", 100));
+        assert_eq!(
+            Some(vec![0..1, 5..7]),
+            parse_range("bytes=0-0, 5-6, 100-", 100)
+        );
+        assert_eq!(Some(vec![]), parse_range("bytes=100-", 100));
+        assert_eq!(None, parse_range("bytes=9-0", 100));
+        assert_eq!(None, parse_range("items=0-1", 100));
+        assert_eq!(None, parse_range("bytes=x-1", 100));
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            Some(PathBuf::from("a/b.txt")),
+            safe_relative_path("/a//./b.txt")
+        );
+        assert_eq!(Some(PathBuf::new()), safe_relative_path("/"));
+        assert_eq!(None, safe_relative_path("/a/../../etc/passwd"));
+        assert_eq!(
+            None,
+            safe_relative_path(&percent_decode("/%2e%2e/x").unwrap())
+        );
+        assert_eq!(Some("/a b".to_owned()), percent_decode("/a%20b"));
+        assert_eq!(None, percent_decode("/a%2"));
+    }
+
+    #[test]
+    fn encodings() {
+        assert!(accepts_encoding("gzip, br;q=0.5", "br"));
+        assert!(!accepts_encoding("gzip, br;q=0", "br"));
+        assert!(accepts_encoding("*", "gzip"));
+        assert!(!accepts_encoding("identity", "gzip"));
+        assert!(etag_matches("\"x\", W/\"y\"", "\"y\""));
+        assert!(!etag_matches("\"x\"", "\"y\""));
+    }
+}
@@ -0,0 +1,173 @@
+//! Dispatch requests by `:authority`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::result;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::req::ServerRequest;
+use crate::Headers;
+use crate::ServerResponse;
+
+/// Host name without port, in lower case and without trailing dot.
+pub(crate) fn normalize_host(authority: &str) -> String {
+    // userinfo is deprecated, but allowed in authority
+    let authority = authority.rsplit('@').next().unwrap();
+    let host = if authority.starts_with('[') {
+        // IPv6 literal
+        match authority.find(']') {
+            Some(end) => &authority[..=end],
+            None => authority,
+        }
+    } else {
+        authority.split(':').next().unwrap()
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Values by host names, with `*.example.com` wildcard names
+/// matching exactly one label before `example.com`.
+pub(crate) struct HostMap<T> {
+    exact: HashMap<String, T>,
+    wildcard: HashMap<String, T>,
+}
+
+impl<T> Default for HostMap<T> {
+    fn default() -> Self {
+        HostMap {
+            exact: HashMap::new(),
+            wildcard: HashMap::new(),
+        }
+    }
+}
+
+impl<T> HostMap<T> {
+    pub fn insert(&mut self, name: &str, value: T) -> Option<T> {
+        let name = normalize_host(name);
+        match name.strip_prefix("*.") {
+            Some(suffix) => self.wildcard.insert(suffix.to_owned(), value),
+            None => self.exact.insert(name, value),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<T> {
+        let name = normalize_host(name);
+        match name.strip_prefix("*.") {
+            Some(suffix) => self.wildcard.remove(suffix),
+            None => self.exact.remove(&name),
+        }
+    }
+
+    /// Find value by normalized host, exact match is preferred.
+    pub fn get(&self, host: &str) -> Option<&T> {
+        if let Some(value) = self.exact.get(host) {
+            return Some(value);
+        }
+        let dot = host.find('.')?;
+        self.wildcard.get(&host[dot + 1..])
+    }
+}
+
+/// Sibling of `ServerHandlerPaths` which delegates requests
+/// to handlers registered for hosts of `:authority` pseudo-header.
+///
+/// Host names may be exact (`example.com`) or wildcard (`*.example.com`,
+/// matching `www.example.com` but not `example.com` or `a.b.example.com`).
+/// Requests to unknown hosts are served by default handler or replied with 404.
+///
+/// Requests over TLS with SNI different from request host are replied
+/// with `421 Misdirected Request`, so client may retry them on another connection.
+#[derive(Default)]
+pub struct ServerHandlerHosts {
+    hosts: HostMap<Arc<dyn ServerHandler>>,
+    default: Option<Arc<dyn ServerHandler>>,
+}
+
+impl ServerHandlerHosts {
+    pub fn new() -> ServerHandlerHosts {
+        Default::default()
+    }
+
+    /// Register a handler for given host name or wildcard.
+    pub fn set_host(&mut self, host: &str, handler: Arc<dyn ServerHandler>) {
+        self.hosts.insert(host, handler);
+    }
+
+    /// Remove a handler registered with `set_host`.
+    pub fn remove_host(&mut self, host: &str) -> Option<Arc<dyn ServerHandler>> {
+        self.hosts.remove(host)
+    }
+
+    /// Set handler for requests to unknown hosts or without `:authority`.
+    pub fn set_default(&mut self, handler: Arc<dyn ServerHandler>) {
+        self.default = Some(handler);
+    }
+
+    fn find(&self, host: Option<&str>) -> Option<&Arc<dyn ServerHandler>> {
+        host.and_then(|host| self.hosts.get(host))
+            .or(self.default.as_ref())
+    }
+}
+
+impl ServerHandler for ServerHandlerHosts {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> result::Result<()> {
+        let host = req.headers.get_opt(":authority").map(normalize_host);
+
+        if let (Some(host), Some(sni)) = (&host, context.conn_info().tls_sni()) {
+            if host != sni {
+                info!("serving 421 for host {} on connection to {}", host, sni);
+                drop(resp.send_headers_end_of_stream(Headers::new_status(421)));
+                return Ok(());
+            }
+        }
+
+        match self.find(host.as_deref()) {
+            Some(handler) => handler.start_request(context, req, resp),
+            None => {
+                info!("serving 404 for host {:?}", host);
+                drop(resp.send_headers_end_of_stream(Headers::not_found_404()));
+                Ok(())
+            }
+        }
+    }
+
+    fn max_request_body_size(&self, headers: &Headers) -> Option<u64> {
+        let host = headers.get_opt(":authority").map(normalize_host);
+        self.find(host.as_deref())
+            .and_then(|handler| handler.max_request_body_size(headers))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!("example.com", normalize_host("Example.COM:8443"));
+        assert_eq!("example.com", normalize_host("example.com."));
+        assert_eq!("example.com", normalize_host("user@example.com"));
+        assert_eq!("[::1]", normalize_host("[::1]:80"));
+    }
+
+    #[test]
+    fn host_map() {
+        let mut map = HostMap::default();
+        map.insert("example.com", 1);
+        map.insert("*.example.com", 2);
+        map.insert("WWW.example.com", 3);
+        assert_eq!(Some(&1), map.get("example.com"));
+        assert_eq!(Some(&2), map.get("api.example.com"));
+        assert_eq!(Some(&3), map.get("www.example.com"));
+        assert_eq!(None, map.get("a.b.example.com"));
+        assert_eq!(None, map.get("example.org"));
+        assert_eq!(Some(2), map.remove("*.example.com"));
+        assert_eq!(None, map.get("api.example.com"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Middleware wrapping `ServerHandler`.
+
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::time;
+
+use crate::common::conn_write::CommonToWriteMessage;
+use crate::result;
+use crate::server::conn::ServerToWriteMessage;
+use crate::server::handler::ServerHandler;
+use crate::server::handler::ServerHandlerContext;
+use crate::server::req::ServerRequest;
+use crate::ErrorCode;
+use crate::Headers;
+use crate::ServerResponse;
+
+/// Wraps a handler into another handler.
+///
+/// Wrapping handler may inspect or rewrite `ServerRequest::headers`
+/// and register callbacks on `ServerResponse` (e. g. `on_send_headers`)
+/// before passing them to the inner handler. Wrapping handler should
+/// also forward `ServerHandler::max_request_body_size` to the inner handler.
+///
+/// Functions of the same signature implement this trait.
+pub trait ServerHandlerLayer: Send + Sync + 'static {
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler>;
+}
+
+impl<F> ServerHandlerLayer for F
+where
+    F: Fn(Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> + Send + Sync + 'static,
+{
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> {
+        self(inner)
+    }
+}
+
+/// Wrap handler with layers, first layer is outermost.
+pub(crate) fn apply_layers(
+    handler: Arc<dyn ServerHandler>,
+    layers: &[Arc<dyn ServerHandlerLayer>],
+) -> Arc<dyn ServerHandler> {
+    layers
+        .iter()
+        .rev()
+        .fold(handler, |handler, layer| layer.layer(handler))
+}
+
+/// Layer which logs request method and path with response status
+/// and time to response headers.
+pub struct ServerHandlerLoggingLayer;
+
+struct LoggingHandler {
+    inner: Arc<dyn ServerHandler>,
+}
+
+impl ServerHandler for LoggingHandler {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> result::Result<()> {
+        let method = req.headers.method().to_owned();
+        let path = req.headers.path().to_owned();
+        let start = Instant::now();
+
+        debug!("request: {} {}", method, path);
+
+        resp.on_send_headers(move |headers| {
+            info!(
+                "{} {} -> {} in {:?}",
+                method,
+                path,
+                headers.get_opt(":status").unwrap_or("-"),
+                start.elapsed()
+            );
+        });
+
+        self.inner.start_request(context, req, resp)
+    }
+
+    fn max_request_body_size(&self, headers: &Headers) -> Option<u64> {
+        self.inner.max_request_body_size(headers)
+    }
+}
+
+impl ServerHandlerLayer for ServerHandlerLoggingLayer {
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> {
+        Arc::new(LoggingHandler { inner })
+    }
+}
+
+/// Layer which resets streams not closed within given time after request start.
+///
+/// Stream is reset with `CANCEL`, HTTP/1 requests are replied with 500
+/// if response headers were not sent yet.
+pub struct ServerHandlerTimeoutLayer {
+    timeout: Duration,
+}
+
+impl ServerHandlerTimeoutLayer {
+    pub fn new(timeout: Duration) -> ServerHandlerTimeoutLayer {
+        ServerHandlerTimeoutLayer { timeout }
+    }
+}
+
+struct TimeoutHandler {
+    inner: Arc<dyn ServerHandler>,
+    timeout: Duration,
+}
+
+impl ServerHandler for TimeoutHandler {
+    fn start_request(
+        &self,
+        context: ServerHandlerContext,
+        req: ServerRequest,
+        mut resp: ServerResponse,
+    ) -> result::Result<()> {
+        let stream_id = resp.common.stream_id();
+        let write_tx = resp.common.write_tx()?.clone();
+        let finished = resp.cancelled().finished();
+        let timeout = self.timeout;
+
+        context.loop_remote().spawn(async move {
+            if time::timeout(timeout, finished).await.is_err() {
+                warn!("request timeout, resetting stream {}", stream_id);
+                // ignore error, connection might be already dead
+                write_tx
+                    .unbounded_send(ServerToWriteMessage::Common(
+                        CommonToWriteMessage::StreamEnd(stream_id, ErrorCode::Cancel),
+                    ))
+                    .ok();
+            }
+        });
+
+        self.inner.start_request(context, req, resp)
+    }
+
+    fn max_request_body_size(&self, headers: &Headers) -> Option<u64> {
+        self.inner.max_request_body_size(headers)
+    }
+}
+
+impl ServerHandlerLayer for ServerHandlerTimeoutLayer {
+    fn layer(&self, inner: Arc<dyn ServerHandler>) -> Arc<dyn ServerHandler> {
+        Arc::new(TimeoutHandler {
+            inner,
+            timeout: self.timeout,
+        })
+    }
+}
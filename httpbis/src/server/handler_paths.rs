@@ -1,4 +1,5 @@
 use std::collections::hash_map;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -9,51 +10,186 @@ use crate::server::req::ServerRequest;
 use crate::solicit::header::Headers;
 use crate::ServerResponse;
 
+/// Services registered for a path.
+#[derive(Default)]
+struct Routes {
+    /// Serves any method
+    any: Option<Arc<dyn ServerHandler>>,
+    by_method: BTreeMap<String, Arc<dyn ServerHandler>>,
+    max_request_body_size: Option<u64>,
+}
+
+impl Routes {
+    fn is_empty(&self) -> bool {
+        self.any.is_none() && self.by_method.is_empty()
+    }
+
+    fn find(&self, method: &str) -> Option<&dyn ServerHandler> {
+        self.by_method
+            .get(method)
+            .or(self.any.as_ref())
+            .map(|a| a.as_ref())
+    }
+
+    /// Value of `allow` header
+    fn allow(&self) -> String {
+        self.by_method
+            .keys()
+            .map(|m| m.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    /// `{name}`, matches single segment
+    Param(&'a str),
+    /// `{*name}`, matches the rest of the path
+    Rest(&'a str),
+}
+
+impl<'a> Segment<'a> {
+    fn parse(segment: &'a str) -> Segment<'a> {
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) => match name.strip_prefix('*') {
+                Some(name) => Segment::Rest(name),
+                None => Segment::Param(name),
+            },
+            None => Segment::Literal(segment),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Node {
-    service: Option<Arc<dyn ServerHandler>>,
+    routes: Routes,
     children: HashMap<String, Node>,
+    param: Option<(String, Box<Node>)>,
+    rest: Option<(String, Routes)>,
 }
 
 impl Node {
-    fn add_service(&mut self, path: &str, service: Arc<dyn ServerHandler>) {
+    fn routes_mut(&mut self, path: &str) -> &mut Routes {
         match split_path(path) {
-            None => {
-                self.service = Some(service);
-            }
-            Some((first, rem)) => {
-                let node = match self.children.entry(first.to_owned()) {
-                    hash_map::Entry::Occupied(e) => e.into_mut(),
-                    hash_map::Entry::Vacant(e) => e.insert(Node {
-                        service: None,
-                        children: HashMap::new(),
-                    }),
-                };
-                node.add_service(rem, service);
-            }
+            None => &mut self.routes,
+            Some((first, rem)) => match Segment::parse(first) {
+                Segment::Literal(first) => {
+                    let node = match self.children.entry(first.to_owned()) {
+                        hash_map::Entry::Occupied(e) => e.into_mut(),
+                        hash_map::Entry::Vacant(e) => e.insert(Node::default()),
+                    };
+                    node.routes_mut(rem)
+                }
+                Segment::Param(name) => {
+                    let (param, node) = self
+                        .param
+                        .get_or_insert_with(|| (name.to_owned(), Box::new(Node::default())));
+                    assert_eq!(param, name, "conflicting parameter names");
+                    node.routes_mut(rem)
+                }
+                Segment::Rest(name) => {
+                    assert!(
+                        split_path(rem).is_none(),
+                        "rest parameter must be the last segment"
+                    );
+                    let (param, routes) = self
+                        .rest
+                        .get_or_insert_with(|| (name.to_owned(), Routes::default()));
+                    assert_eq!(param, name, "conflicting parameter names");
+                    routes
+                }
+            },
         }
     }
 
     fn remove_service(&mut self, path: &str) -> Option<Arc<dyn ServerHandler>> {
         match split_path(path) {
-            None => self.service.take(),
-            Some((first, rem)) => match self.children.get_mut(first) {
-                Some(child) => child.remove_service(rem),
-                None => None,
+            None => self.routes.any.take(),
+            Some((first, rem)) => match Segment::parse(first) {
+                Segment::Literal(first) => match self.children.get_mut(first) {
+                    Some(child) => child.remove_service(rem),
+                    None => None,
+                },
+                Segment::Param(name) => match &mut self.param {
+                    Some((param, child)) if param == name => child.remove_service(rem),
+                    _ => None,
+                },
+                Segment::Rest(name) => match &mut self.rest {
+                    Some((param, routes)) if param == name => routes.any.take(),
+                    _ => None,
+                },
             },
         }
     }
 
-    fn find_service(&self, path: &str) -> Option<&dyn ServerHandler> {
-        if let Some((first, rem)) = split_path(path) {
-            if let Some(node) = self.children.get(first) {
-                if let Some(service) = node.find_service(rem) {
-                    return Some(service);
+    /// Find routes for the longest matching path prefix.
+    ///
+    /// Literal segments are preferred over `{param}`,
+    /// and `{param}` is preferred over `{*rest}`.
+    fn find_routes(&self, path: &str, params: &mut Vec<(String, String)>) -> Option<&Routes> {
+        match split_path(path) {
+            Some((first, rem)) => {
+                if let Some(node) = self.children.get(first) {
+                    if let Some(routes) = node.find_routes(rem, params) {
+                        return Some(routes);
+                    }
+                }
+                if let Some((name, node)) = &self.param {
+                    params.push((name.clone(), first.to_owned()));
+                    if let Some(routes) = node.find_routes(rem, params) {
+                        return Some(routes);
+                    }
+                    params.pop();
+                }
+            }
+            None => {
+                if !self.routes.is_empty() {
+                    return Some(&self.routes);
                 }
             }
         }
 
-        self.service.as_ref().map(|a| a.as_ref())
+        if let Some((name, routes)) = &self.rest {
+            if !routes.is_empty() {
+                params.push((name.clone(), path.trim_start_matches('/').to_owned()));
+                return Some(routes);
+            }
+        }
+
+        if !self.routes.is_empty() {
+            Some(&self.routes)
+        } else {
+            None
+        }
+    }
+}
+
+impl Node {
+    /// Request body size limit set for the longest matching path prefix.
+    ///
+    /// Unlike `find_routes`, paths without services are matched,
+    /// so a limit applies to requests served by a service of a parent path.
+    fn find_max_request_body_size(&self, path: &str) -> Option<u64> {
+        let nested = match split_path(path) {
+            Some((first, rem)) => self
+                .children
+                .get(first)
+                .and_then(|node| node.find_max_request_body_size(rem))
+                .or_else(|| {
+                    self.param
+                        .as_ref()
+                        .and_then(|(_, node)| node.find_max_request_body_size(rem))
+                }),
+            None => None,
+        };
+        nested
+            .or_else(|| {
+                self.rest
+                    .as_ref()
+                    .and_then(|(_, routes)| routes.max_request_body_size)
+            })
+            .or(self.routes.max_request_body_size)
     }
 }
 
@@ -91,8 +227,36 @@ fn test_split_path() {
     );
 }
 
+/// Path parameters captured by `ServerHandlerPaths`.
+///
+/// Available to handlers in `ServerRequest::extensions`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathParams {
+    params: Vec<(String, String)>,
+}
+
+impl PathParams {
+    /// Get parameter value by name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Iterate over parameter names and values in path order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
+
 /// Convient implementation of `ServerHandler` which allows delegation to
 /// multiple `ServerHandler` implementations provided by user.
+///
+/// Path patterns may contain `{name}` segments matching a single path segment
+/// and a trailing `{*name}` segment matching the rest of the path.
+/// Service registered for a path also serves requests to nested paths
+/// unless more specific service is registered. Query string is ignored.
 #[derive(Default)]
 pub struct ServerHandlerPaths {
     root: Node,
@@ -105,7 +269,7 @@ impl ServerHandlerPaths {
         Default::default()
     }
 
-    /// Register a service for given path.
+    /// Register a service for given path pattern and any method.
     ///
     /// ```
     /// # use std::sync::Arc;
@@ -132,10 +296,28 @@ impl ServerHandlerPaths {
     /// let mut server = ServerBuilder::new_plain();
     /// server.service.set_service("/", Arc::new(Root{}));
     /// server.service.set_service("/files", Arc::new(Files{}));
+    /// server.service.set_service("/users/{id}/files/{*rest}", Arc::new(Files{}));
     /// ```
     pub fn set_service(&mut self, path: &str, service: Arc<dyn ServerHandler>) {
         assert!(path.starts_with("/"));
-        self.root.add_service(path, service);
+        self.root.routes_mut(path).any = Some(service);
+    }
+
+    /// Register a service for given method and path pattern.
+    ///
+    /// Requests to the path with other methods are replied with
+    /// `405 Method Not Allowed` unless there's a service for any method.
+    pub fn set_method_service(
+        &mut self,
+        method: &str,
+        path: &str,
+        service: Arc<dyn ServerHandler>,
+    ) {
+        assert!(path.starts_with("/"));
+        self.root
+            .routes_mut(path)
+            .by_method
+            .insert(method.to_owned(), service);
     }
 
     pub fn set_service_fn<F>(&mut self, path: &str, service: F)
@@ -165,13 +347,37 @@ impl ServerHandlerPaths {
         self.set_service(path, Arc::new(service))
     }
 
+    pub fn set_method_service_fn<F>(&mut self, method: &str, path: &str, service: F)
+    where
+        F: Fn(ServerHandlerContext, ServerRequest, ServerResponse) -> result::Result<()>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.set_method_service(method, path, Arc::new(service))
+    }
+
+    /// Override `ServerConf::max_request_body_size` for given path pattern.
+    ///
+    /// Applies to all methods and to nested paths, including requests served
+    /// by a service of a parent path. Without an override the limit is taken from
+    /// `ServerHandler::max_request_body_size` of the matched service.
+    pub fn set_max_request_body_size(&mut self, path: &str, limit: u64) {
+        assert!(path.starts_with("/"));
+        self.root.routes_mut(path).max_request_body_size = Some(limit);
+    }
+
+    /// Remove a service registered with `set_service`.
     pub fn remove_service(&mut self, path: &str) -> Option<Arc<dyn ServerHandler>> {
         assert!(path.starts_with("/"));
         self.root.remove_service(path)
     }
 
-    fn find_service(&self, path: &str) -> Option<&dyn ServerHandler> {
-        self.root.find_service(path)
+    fn find_routes(&self, path: &str) -> Option<(&Routes, PathParams)> {
+        let path = path.split('?').next().unwrap();
+        let mut params = Vec::new();
+        let routes = self.root.find_routes(path, &mut params)?;
+        Some((routes, PathParams { params }))
     }
 }
 
@@ -179,17 +385,163 @@ impl ServerHandler for ServerHandlerPaths {
     fn start_request(
         &self,
         context: ServerHandlerContext,
-        req: ServerRequest,
+        mut req: ServerRequest,
         mut resp: ServerResponse,
     ) -> result::Result<()> {
-        if let Some(service) = self.find_service(req.headers.path()) {
-            info!("invoking user callback for path {}", req.headers.path());
-            service.start_request(context, req, resp)
-        } else {
-            info!("serving 404 for path {}", req.headers.path());
-            drop(resp.send_headers(Headers::not_found_404()));
-            drop(resp.close());
+        match self.find_routes(req.headers.path()) {
+            Some((routes, params)) => match routes.find(req.headers.method()) {
+                Some(service) => {
+                    info!("invoking user callback for path {}", req.headers.path());
+                    req.extensions.insert(params);
+                    service.start_request(context, req, resp)
+                }
+                None => {
+                    info!(
+                        "serving 405 for {} {}",
+                        req.headers.method(),
+                        req.headers.path()
+                    );
+                    let mut headers = Headers::new_status(405);
+                    headers.add("allow", routes.allow());
+                    drop(resp.send_headers(headers));
+                    drop(resp.close());
+                    Ok(())
+                }
+            },
+            None => {
+                info!("serving 404 for path {}", req.headers.path());
+                drop(resp.send_headers(Headers::not_found_404()));
+                drop(resp.close());
+                Ok(())
+            }
+        }
+    }
+
+    fn max_request_body_size(&self, headers: &Headers) -> Option<u64> {
+        let path = headers.path().split('?').next().unwrap();
+        if let Some(limit) = self.root.find_max_request_body_size(path) {
+            return Some(limit);
+        }
+        self.find_routes(path)
+            .and_then(|(routes, _)| routes.find(headers.method()))
+            .and_then(|service| service.max_request_body_size(headers))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn handler() -> Arc<dyn ServerHandler> {
+        Arc::new(|_: ServerHandlerContext, _: ServerRequest, _: ServerResponse| Ok(()))
+    }
+
+    fn same(a: &dyn ServerHandler, b: &Arc<dyn ServerHandler>) -> bool {
+        a as *const dyn ServerHandler as *const u8
+            == b.as_ref() as *const dyn ServerHandler as *const u8
+    }
+
+    fn find<'a>(
+        paths: &'a ServerHandlerPaths,
+        method: &str,
+        path: &str,
+    ) -> Option<(&'a dyn ServerHandler, PathParams)> {
+        let (routes, params) = paths.find_routes(path)?;
+        Some((routes.find(method)?, params))
+    }
+
+    #[test]
+    fn patterns() {
+        let root = handler();
+        let user = handler();
+        let me = handler();
+        let files = handler();
+
+        let mut paths = ServerHandlerPaths::new();
+        paths.set_service("/", root.clone());
+        paths.set_service("/users/{id}", user.clone());
+        paths.set_service("/users/me", me.clone());
+        paths.set_service("/users/{id}/files/{*rest}", files.clone());
+
+        let (h, params) = find(&paths, "GET", "/users/10?x=y").unwrap();
+        assert!(same(h, &user));
+        assert_eq!(Some("10"), params.get("id"));
+
+        let (h, params) = find(&paths, "GET", "/users/me").unwrap();
+        assert!(same(h, &me));
+        assert_eq!(None, params.get("id"));
+
+        let (h, params) = find(&paths, "GET", "/users/10/files/a/b.txt").unwrap();
+        assert!(same(h, &files));
+        assert_eq!(
+            vec![("id", "10"), ("rest", "a/b.txt")],
+            params.iter().collect::<Vec<_>>()
+        );
+
+        // nested path falls back to the longest registered prefix
+        let (h, params) = find(&paths, "GET", "/users/10/other").unwrap();
+        assert!(same(h, &user));
+        assert_eq!(Some("10"), params.get("id"));
+
+        let (h, _) = find(&paths, "GET", "/other").unwrap();
+        assert!(same(h, &root));
+
+        assert!(same(
+            paths.remove_service("/users/{id}").unwrap().as_ref(),
+            &user
+        ));
+        let (h, params) = find(&paths, "GET", "/users/10").unwrap();
+        assert!(same(h, &root));
+        assert_eq!(PathParams::default(), params);
+    }
+
+    #[test]
+    fn methods() {
+        let get = handler();
+        let post = handler();
+
+        let mut paths = ServerHandlerPaths::new();
+        paths.set_method_service("GET", "/items", get.clone());
+        paths.set_method_service("POST", "/items", post.clone());
+
+        assert!(same(find(&paths, "GET", "/items").unwrap().0, &get));
+        assert!(same(find(&paths, "POST", "/items").unwrap().0, &post));
+        assert!(find(&paths, "DELETE", "/items").is_none());
+        assert_eq!("GET, POST", paths.find_routes("/items").unwrap().0.allow());
+        assert!(paths.find_routes("/other").is_none());
+    }
+
+    struct Limited(u64);
+
+    impl ServerHandler for Limited {
+        fn start_request(
+            &self,
+            _context: ServerHandlerContext,
+            _req: ServerRequest,
+            _resp: ServerResponse,
+        ) -> result::Result<()> {
             Ok(())
         }
+
+        fn max_request_body_size(&self, _headers: &Headers) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn max_request_body_size() {
+        let mut paths = ServerHandlerPaths::new();
+        paths.set_service("/", handler());
+        paths.set_service("/limited", Arc::new(Limited(10)));
+        paths.set_max_request_body_size("/limited/large", 20);
+        // no service at this path
+        paths.set_max_request_body_size("/upload", 30);
+
+        let limit = |path| paths.max_request_body_size(&Headers::new_post(path));
+        assert_eq!(None, limit("/"));
+        assert_eq!(Some(10), limit("/limited/a"));
+        assert_eq!(Some(20), limit("/limited/large?x=y"));
+        assert_eq!(Some(30), limit("/upload/a"));
+        assert_eq!(None, limit("/uploads"));
     }
 }
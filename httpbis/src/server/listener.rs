@@ -0,0 +1,251 @@
+//! Sockets server listens on.
+
+#[cfg(unix)]
+use std::env;
+use std::io;
+#[cfg(unix)]
+use std::mem;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::io::FromRawFd;
+#[cfg(unix)]
+use std::os::unix::io::IntoRawFd;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use crate::net::addr::AnySocketAddr;
+use crate::net::listen::ToSocketListener;
+use crate::net::listen::ToTokioListener;
+use crate::net::unix::SocketAddrUnix;
+use crate::ServerConf;
+use crate::ServerTlsOption;
+
+/// Socket server listens on with its TLS option.
+pub struct ServerListener<A: tls_api::TlsAcceptor> {
+    pub socket: ServerSocket,
+    pub tls: ServerTlsOption<A>,
+}
+
+/// Socket to accept connections from.
+#[derive(Debug)]
+pub enum ServerSocket {
+    /// Bind to the address when server is built
+    Addr(AnySocketAddr),
+    /// Already bound listener, e. g. inherited from parent process
+    Tcp(TcpListener),
+    /// Already bound unix listener, socket file is not removed on server shutdown
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl ServerSocket {
+    pub(crate) fn listen(self, conf: &ServerConf) -> io::Result<Box<dyn ToTokioListener + Send>> {
+        match self {
+            ServerSocket::Addr(addr) => addr.listen(conf),
+            ServerSocket::Tcp(listener) => Ok(Box::new(listener)),
+            #[cfg(unix)]
+            ServerSocket::Unix(listener) => Ok(Box::new(listener)),
+        }
+    }
+
+    /// Address bound by the server, which needs cleanup on shutdown.
+    pub(crate) fn bound_addr(&self) -> Option<&AnySocketAddr> {
+        match self {
+            ServerSocket::Addr(addr) => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+impl From<AnySocketAddr> for ServerSocket {
+    fn from(addr: AnySocketAddr) -> Self {
+        ServerSocket::Addr(addr)
+    }
+}
+
+impl From<std::net::SocketAddr> for ServerSocket {
+    fn from(addr: std::net::SocketAddr) -> Self {
+        ServerSocket::Addr(addr.into())
+    }
+}
+
+impl From<SocketAddrUnix> for ServerSocket {
+    fn from(addr: SocketAddrUnix) -> Self {
+        ServerSocket::Addr(addr.into())
+    }
+}
+
+impl From<TcpListener> for ServerSocket {
+    fn from(listener: TcpListener) -> Self {
+        ServerSocket::Tcp(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for ServerSocket {
+    fn from(listener: UnixListener) -> Self {
+        ServerSocket::Unix(listener)
+    }
+}
+
+/// First file descriptor passed by systemd.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Read integer `SOL_SOCKET` option.
+#[cfg(unix)]
+fn get_socket_option(fd: RawFd, option: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let r = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if r < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+/// Take ownership of a listening socket file descriptor.
+///
+/// Descriptor must be a listening stream socket, otherwise it is left open.
+/// Socket family is detected by querying the local address.
+#[cfg(unix)]
+unsafe fn socket_from_raw_fd(fd: RawFd) -> io::Result<ServerSocket> {
+    let so_type = get_socket_option(fd, libc::SO_TYPE).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("fd {} is not a socket: {}", fd, e),
+        )
+    })?;
+    if so_type != libc::SOCK_STREAM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("fd {} is not a stream socket", fd),
+        ));
+    }
+    if get_socket_option(fd, libc::SO_ACCEPTCONN)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("fd {} is not a listening socket", fd),
+        ));
+    }
+
+    let tcp = TcpListener::from_raw_fd(fd);
+    if tcp.local_addr().is_ok() {
+        return Ok(ServerSocket::Tcp(tcp));
+    }
+    let unix = UnixListener::from_raw_fd(tcp.into_raw_fd());
+    if unix.local_addr().is_ok() {
+        return Ok(ServerSocket::Unix(unix));
+    }
+    // do not close the descriptor we do not understand
+    let _ = unix.into_raw_fd();
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("fd {} is neither TCP nor unix socket", fd),
+    ))
+}
+
+#[cfg(unix)]
+fn listen_fds_from_env(start_fd: RawFd) -> io::Result<Vec<ServerSocket>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    // variables are for this process only, must not be inherited by children
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let (pid, fds) = match (pid, fds) {
+        (Some(pid), Some(fds)) => (pid, fds),
+        _ => return Ok(Vec::new()),
+    };
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        debug!("LISTEN_PID {} is not our pid, ignoring LISTEN_FDS", pid);
+        return Ok(Vec::new());
+    }
+    let fds: RawFd = fds
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "malformed LISTEN_FDS"))?;
+
+    (start_fd..start_fd + fds)
+        .map(|fd| unsafe { socket_from_raw_fd(fd) })
+        .collect()
+}
+
+/// Take listeners passed by systemd socket activation
+/// in `LISTEN_PID` and `LISTEN_FDS` environment variables.
+///
+/// Returns empty list if the process is not socket activated.
+/// Variables are removed from the environment, so the function
+/// returns sockets only on the first call.
+#[cfg(unix)]
+pub fn systemd_listeners() -> io::Result<Vec<ServerSocket>> {
+    listen_fds_from_env(SD_LISTEN_FDS_START)
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn listen_fds() {
+        let tempdir = tempdir::TempDir::new("rust_http2_test").unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_addr = tcp.local_addr().unwrap();
+        let unix = UnixListener::bind(tempdir.path().join("socket")).unwrap();
+        let tcp_fd = tcp.into_raw_fd();
+        let unix_fd = unix.into_raw_fd();
+
+        env::set_var("LISTEN_PID", "1");
+        env::set_var("LISTEN_FDS", "1");
+        assert!(listen_fds_from_env(tcp_fd).unwrap().is_empty());
+        assert!(env::var("LISTEN_FDS").is_err());
+
+        let set_env = || {
+            env::set_var("LISTEN_PID", std::process::id().to_string());
+            env::set_var("LISTEN_FDS", "1");
+        };
+
+        set_env();
+        match &listen_fds_from_env(tcp_fd).unwrap()[..] {
+            [ServerSocket::Tcp(tcp)] => assert_eq!(tcp_addr, tcp.local_addr().unwrap()),
+            sockets => panic!("{:?}", sockets),
+        }
+
+        set_env();
+        match &listen_fds_from_env(unix_fd).unwrap()[..] {
+            [ServerSocket::Unix(unix)] => assert_eq!(
+                Some(tempdir.path().join("socket").as_path()),
+                unix.local_addr().unwrap().as_pathname()
+            ),
+            sockets => panic!("{:?}", sockets),
+        }
+    }
+
+    #[test]
+    fn not_listening_fd() {
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(tcp.local_addr().unwrap()).unwrap();
+        let file = std::fs::File::open("/dev/null").unwrap();
+
+        for fd in &[udp.as_raw_fd(), stream.as_raw_fd(), file.as_raw_fd()] {
+            let e = unsafe { socket_from_raw_fd(*fd) }.unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, e.kind(), "{}", e);
+        }
+
+        // descriptors are not taken
+        assert!(udp.local_addr().is_ok());
+        assert!(stream.peer_addr().is_ok());
+    }
+}
@@ -1,30 +1,51 @@
+pub mod cancel;
 pub mod conf;
 pub mod conn;
+pub(crate) mod conn_http1;
+pub mod conn_info;
+pub mod conn_loops;
+pub mod extensions;
+pub(crate) mod frame_rate;
 pub mod handler;
+pub mod handler_async;
+pub mod handler_compression;
+pub mod handler_files;
+pub mod handler_hosts;
+pub mod handler_layer;
 pub mod handler_paths;
 pub(crate) mod increase_in_window;
+pub mod listener;
+pub mod proxy_protocol;
+pub(crate) mod push;
 pub mod req;
 pub mod resp;
+pub(crate) mod sni;
 pub(crate) mod stream_handler;
 pub mod tls;
 pub(crate) mod types;
 
 use futures::future::try_join_all;
 use std::collections::HashMap;
+use std::io;
 
+use std::net::IpAddr;
 use std::net::ToSocketAddrs;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::thread;
+use std::time::Duration;
 
 use tls_api;
 
 use futures::channel::oneshot;
 use futures::future;
 use futures::future::try_join;
+use futures::future::AbortHandle;
 use futures::future::FutureExt;
 use futures::future::TryFutureExt;
+use futures::stream;
+use futures::stream::StreamExt;
 
 use crate::error::Error;
 use crate::result::Result;
@@ -40,34 +61,56 @@ use crate::net::addr::AnySocketAddr;
 use crate::net::listen::ToSocketListener;
 use crate::net::listen::ToTokioListener;
 
+use self::tls::ServerTlsAcceptors;
+use self::tls::ServerTlsHandle;
 pub use self::tls::ServerTlsOption;
+use self::tls::ServerTlsSniMap;
 use crate::assert_types::assert_send_future;
 use crate::common::conn::ConnStateSnapshot;
 use crate::net::unix::SocketAddrUnix;
 use crate::result;
 pub use crate::server::conf::ServerConf;
+use crate::server::conf::ServerConnLoopSelection;
 pub use crate::server::conn::ServerConn;
+use crate::server::conn_loops::ConnLoopLoad;
+use crate::server::conn_loops::ConnLoopStateSnapshot;
+use crate::server::conn_loops::ConnLoops;
 use crate::server::handler::ServerHandler;
+use crate::server::handler_layer::apply_layers;
+use crate::server::handler_layer::ServerHandlerLayer;
 use crate::server::handler_paths::ServerHandlerPaths;
-use rand::thread_rng;
-use rand::Rng;
+#[cfg(unix)]
+use crate::server::listener::systemd_listeners;
+use crate::server::listener::ServerListener;
+use crate::server::listener::ServerSocket;
 use std::fmt;
 use tokio::runtime::{Handle, Runtime};
+use tokio::time;
 
 pub struct ServerBuilder<A: tls_api::TlsAcceptor = tls_api_stub::TlsAcceptor> {
     pub conf: ServerConf,
+    /// TLS option of `addr`
     pub tls: ServerTlsOption<A>,
     pub addr: Option<AnySocketAddr>,
+    /// Listeners in addition to `addr`, all served by the same `service`
+    pub listeners: Vec<ServerListener<A>>,
     /// Event loop to spawn server.
     /// If not specified, builder will create new event loop in a new thread.
     pub event_loop: Option<Handle>,
     /// Event loops used to run incoming connections.
     /// If empty, listener event loop will be used.
-    // TODO: test it
+    /// Loop for a connection is chosen by `ServerConf::conn_loop_selection`.
     pub conn_event_loops: Vec<Handle>,
     pub service: ServerHandlerPaths,
+    /// Layers wrapping `service`, first is outermost
+    pub layers: Vec<Arc<dyn ServerHandlerLayer>>,
+    /// Called with peer address of accepted socket, socket is closed when it returns `false`
+    pub accept_filter: Option<Arc<ServerAcceptFilter>>,
 }
 
+/// Connection filter called before TLS and HTTP/2 handshake.
+pub type ServerAcceptFilter = dyn Fn(&AnySocketAddr) -> bool + Send + Sync;
+
 impl ServerBuilder<tls_api_stub::TlsAcceptor> {
     /// New server builder with defaults.
     ///
@@ -114,6 +157,41 @@ impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
         self.addr = Some(AnySocketAddr::Unix(addr.into()));
         Ok(())
     }
+
+    /// Listen on an additional address or already bound listener
+    /// with its own TLS option.
+    pub fn add_listener<S: Into<ServerSocket>>(&mut self, socket: S, tls: ServerTlsOption<A>) {
+        self.listeners.push(ServerListener {
+            socket: socket.into(),
+            tls,
+        });
+    }
+
+    /// Listen on all addresses the name resolves to,
+    /// e. g. both IPv4 and IPv6 addresses of `localhost`.
+    pub fn add_addrs<S: ToSocketAddrs>(&mut self, addr: S, tls: ServerTlsOption<A>) -> Result<()> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() {
+            return Err(Error::AddrResolvedToEmptyList);
+        }
+        for addr in addrs {
+            self.add_listener(addr, tls.clone());
+        }
+        Ok(())
+    }
+
+    /// Listen on sockets passed by systemd socket activation.
+    ///
+    /// Returns number of sockets added, zero if the process is not socket activated.
+    #[cfg(unix)]
+    pub fn add_systemd_listeners(&mut self, tls: ServerTlsOption<A>) -> Result<usize> {
+        let sockets = systemd_listeners()?;
+        let count = sockets.len();
+        for socket in sockets {
+            self.add_listener(socket, tls.clone());
+        }
+        Ok(count)
+    }
 }
 
 impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
@@ -128,20 +206,56 @@ impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
             conf: ServerConf::new(),
             tls: ServerTlsOption::Plain,
             addr: None,
+            listeners: Vec::new(),
             event_loop: None,
             conn_event_loops: Vec::new(),
             service: ServerHandlerPaths::new(),
+            layers: Vec::new(),
+            accept_filter: None,
         }
     }
 
+    /// Wrap `service` with a layer.
+    ///
+    /// Layer added first is outermost, i. e. it sees requests before other layers.
+    pub fn layer<L: ServerHandlerLayer>(&mut self, layer: L) -> &mut Self {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Accept only connections for which the filter returns `true`.
+    ///
+    /// Filter is called in the accept loop, so it must not block.
+    pub fn set_accept_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(&AnySocketAddr) -> bool + Send + Sync + 'static,
+    {
+        self.accept_filter = Some(Arc::new(filter));
+    }
+
     pub fn set_tls(&mut self, acceptor: A) {
         self.tls = ServerTlsOption::Tls(Arc::new(acceptor));
     }
 
-    pub fn build(self) -> Result<Server> {
+    /// Select TLS acceptor of `addr` by server name sent by client.
+    pub fn set_tls_sni(&mut self, acceptors: ServerTlsSniMap<A>) {
+        self.tls = ServerTlsOption::TlsSni(Arc::new(acceptors));
+    }
+
+    pub fn build(self) -> Result<Server<A>> {
         let (alive_tx, alive_rx) = mpsc::channel();
 
-        let state: Arc<Mutex<ServerState>> = Default::default();
+        let conn_loops = ConnLoops::new(
+            self.conn_event_loops,
+            self.conf
+                .conn_loop_selection
+                .unwrap_or(ServerConnLoopSelection::LeastConnections),
+        );
+
+        let state = Arc::new(Mutex::new(ServerState {
+            conn_loops: conn_loops.loads(),
+            ..Default::default()
+        }));
 
         let state_copy = state.clone();
 
@@ -150,37 +264,61 @@ impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
         // TODO: why done_tx is unused?
         let (_done_tx, done_rx) = oneshot::channel();
 
-        let listen = match self.addr {
-            Some(addr) => addr.listen(&self.conf)?,
-            None => return Err(Error::ListenAddrNotSpecified),
-        };
+        let mut listeners = Vec::new();
+        if let Some(addr) = self.addr {
+            listeners.push(ServerListener {
+                socket: ServerSocket::Addr(addr),
+                tls: self.tls,
+            });
+        }
+        listeners.extend(self.listeners);
+        if listeners.is_empty() {
+            return Err(Error::ListenAddrNotSpecified);
+        }
+        if self.conf.max_conns_per_ip.is_some() && self.conf.proxy_protocol.unwrap_or(false) {
+            return Err(Error::MaxConnsPerIpWithProxyProtocol);
+        }
 
-        let local_addr = listen.local_addr().unwrap();
-        //let local_addr = local_addr.downcast_ref::<T>().expect("downcast socket_addr").clone();
+        let mut listens = Vec::new();
+        let mut tls_options = Vec::new();
+        let mut local_addrs = Vec::new();
+        let mut bound_addrs = Vec::new();
+        for listener in listeners {
+            if let Some(addr) = listener.socket.bound_addr() {
+                bound_addrs.push(addr.clone());
+            }
+            let listen = listener.socket.listen(&self.conf)?;
+            local_addrs.push(listen.local_addr()?);
+            listens.push(listen);
+            tls_options.push(listener.tls);
+        }
+        let tls = Arc::new(ServerTlsAcceptors::new(tls_options));
+        let tls_copy = tls.clone();
+
+        let accept_filter = self.accept_filter;
 
-        let join = if let Some(remote) = self.event_loop {
-            let tls = self.tls;
+        let (handle, join) = if let Some(remote) = self.event_loop {
             let conf = self.conf;
-            let service = self.service;
-            let conn_event_loops = self.conn_event_loops;
+            let service = apply_layers(Arc::new(self.service), &self.layers);
             let handle = remote.clone();
             remote.spawn(spawn_server_event_loop(
                 handle.clone(),
-                conn_event_loops,
+                conn_loops,
                 state_copy,
+                listens,
                 tls,
-                listen,
                 shutdown_future,
                 conf,
                 service,
+                accept_filter,
                 alive_tx,
             ));
-            Completion::Rx(done_rx)
+            (handle, Completion::Rx(done_rx))
         } else {
-            let tls = self.tls;
             let conf = self.conf;
-            let service = self.service;
-            let conn_event_loops = self.conn_event_loops;
+            let service = apply_layers(Arc::new(self.service), &self.layers);
+            let mut lp = Runtime::new()?;
+            let handle = lp.handle().clone();
             let join_handle = thread::Builder::new()
                 .name(
                     conf.thread_name
@@ -189,29 +327,45 @@ impl<A: tls_api::TlsAcceptor> ServerBuilder<A> {
                         .to_string(),
                 )
                 .spawn(move || {
-                    let mut lp = Runtime::new().expect("http2server");
-                    lp.block_on(
-                        spawn_server_event_loop(
-                            lp.handle().clone(),
-                            conn_event_loops,
-                            state_copy,
-                            tls,
-                            listen,
-                            shutdown_future,
-                            conf,
-                            service,
-                            alive_tx,
-                        )
-                        .map(|_| ()),
+                    let done_rx = spawn_server_event_loop(
+                        lp.handle().clone(),
+                        conn_loops,
+                        state_copy.clone(),
+                        listens,
+                        tls,
+                        shutdown_future,
+                        conf,
+                        service,
+                        accept_filter,
+                        alive_tx,
                     );
+                    lp.block_on(async move {
+                        done_rx.await.ok();
+
+                        // Runtime must not be dropped while connections are drained
+                        let conns_closed = {
+                            let mut g = state_copy.lock().expect("lock");
+                            if g.graceful_shutdown {
+                                Some(g.conns_closed())
+                            } else {
+                                None
+                            }
+                        };
+                        if let Some(conns_closed) = conns_closed {
+                            conns_closed.await.ok();
+                        }
+                    });
                 })?;
-            Completion::Thread(join_handle)
+            (handle, Completion::Thread(join_handle))
         };
 
         Ok(Server {
             state: state,
+            handle,
+            tls: tls_copy,
             shutdown: shutdown_signal,
-            local_addr: local_addr,
+            local_addrs,
+            bound_addrs,
             join: Some(join),
             alive_rx: alive_rx,
         })
@@ -223,36 +377,145 @@ enum Completion {
     Rx(oneshot::Receiver<()>),
 }
 
-pub struct Server {
+/// Running server, `A` is the TLS acceptor type of its listeners.
+pub struct Server<A: TlsAcceptor = tls_api_stub::TlsAcceptor> {
     state: Arc<Mutex<ServerState>>,
-    local_addr: AnySocketAddr,
+    handle: Handle,
+    /// TLS options of listeners shared with accept loop
+    tls: Arc<ServerTlsAcceptors<A>>,
+    local_addrs: Vec<AnySocketAddr>,
+    /// Addresses bound by server, cleaned up on shutdown
+    bound_addrs: Vec<AnySocketAddr>,
     shutdown: ShutdownSignal,
     alive_rx: mpsc::Receiver<()>,
     join: Option<Completion>,
 }
 
-impl fmt::Debug for Server {
+impl<A: TlsAcceptor> fmt::Debug for Server<A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Server")
-            .field("local_addr", &self.local_addr)
+            .field("local_addrs", &self.local_addrs)
             .finish()
     }
 }
 
+/// Delay before the next accept after an error other than aborted connection,
+/// e. g. when the process is out of file descriptors.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Connection aborted by peer before accept is retried immediately.
+fn accept_error_backoff(e: &io::Error) -> Option<Duration> {
+    match e.kind() {
+        io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::Interrupted => None,
+        _ => Some(ACCEPT_ERROR_BACKOFF),
+    }
+}
+
+/// Max number of connections being refused with `GOAWAY` at once,
+/// connections over limits are closed immediately when reached.
+const MAX_REFUSING_CONNS: usize = 64;
+
+struct ServerStateConn {
+    conn: ServerConn,
+    /// Forcibly close the connection
+    abort: AbortHandle,
+    /// Peer IP address counted in `conns_per_ip`
+    ip: Option<IpAddr>,
+}
+
 #[derive(Default)]
 struct ServerState {
-    last_conn_id: u64,
-    conns: HashMap<u64, ServerConn>,
+    conns: HashMap<u64, ServerStateConn>,
+    /// Open connections by peer IP address
+    conns_per_ip: HashMap<IpAddr, usize>,
+    refused: ServerRefusedConns,
+    /// Connections being refused with `GOAWAY`
+    refusing: usize,
+    /// Loads of connection event loops
+    conn_loops: Vec<Arc<ConnLoopLoad>>,
+    /// Graceful shutdown was requested
+    graceful_shutdown: bool,
+    /// Notified when the last connection is closed
+    conns_closed_waiters: Vec<oneshot::Sender<()>>,
 }
 
 impl ServerState {
+    /// Future resolved when there are no connections left
+    fn conns_closed(&mut self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        if self.conns.is_empty() {
+            tx.send(()).ok();
+        } else {
+            self.conns_closed_waiters.push(tx);
+        }
+        rx
+    }
+
+    /// Count refusal if connection is over `max_conns` or `max_conns_per_ip`.
+    fn over_limits(&mut self, ip: Option<IpAddr>, conf: &ServerConf) -> bool {
+        if let Some(max_conns) = conf.max_conns {
+            if self.conns.len() >= max_conns {
+                self.refused.max_conns += 1;
+                return true;
+            }
+        }
+        if let (Some(max_conns_per_ip), Some(ip)) = (conf.max_conns_per_ip, ip) {
+            if self.conns_per_ip.get(&ip).cloned().unwrap_or(0) >= max_conns_per_ip {
+                self.refused.max_conns_per_ip += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Count refusal with `GOAWAY`, `false` if too many refusals are in progress.
+    fn start_refusal(&mut self) -> bool {
+        if self.refusing >= MAX_REFUSING_CONNS {
+            return false;
+        }
+        self.refusing += 1;
+        true
+    }
+
+    fn add_conn(&mut self, conn_id: u64, conn: ServerStateConn) {
+        // connection accepted after shutdown was requested
+        // but before accept loop was stopped
+        if self.graceful_shutdown {
+            conn.conn.graceful_shutdown();
+        }
+        if let Some(ip) = conn.ip {
+            *self.conns_per_ip.entry(ip).or_insert(0) += 1;
+        }
+        let prev = self.conns.insert(conn_id, conn);
+        assert!(prev.is_none());
+    }
+
+    fn remove_conn(&mut self, conn_id: u64) {
+        let removed = self.conns.remove(&conn_id).expect("conn");
+        if let Some(ip) = removed.ip {
+            let count = self.conns_per_ip.get_mut(&ip).expect("ip");
+            *count -= 1;
+            if *count == 0 {
+                self.conns_per_ip.remove(&ip);
+            }
+        }
+        if self.conns.is_empty() {
+            for tx in self.conns_closed_waiters.drain(..) {
+                // ignore error, waiter might be gone
+                tx.send(()).ok();
+            }
+        }
+    }
+
     fn snapshot(&self) -> HttpFutureSend<ServerStateSnapshot> {
         let futures: Vec<_> = self
             .conns
             .iter()
-            .map(|(&id, conn)| {
+            .map(|(&id, c)| {
                 assert_send_future::<result::Result<_>, _>(
-                    conn.dump_state().map_ok(move |state| (id, state)),
+                    c.conn.dump_state().map_ok(move |state| (id, state)),
                 )
             })
             .collect();
@@ -260,8 +523,14 @@ impl ServerState {
         let j = try_join_all(futures);
         let j = assert_send_future::<result::Result<_>, _>(j);
 
-        Box::pin(j.map_ok(|states| ServerStateSnapshot {
+        let conn_loops = conn_loops::snapshot(&self.conn_loops);
+
+        let refused = self.refused;
+
+        Box::pin(j.map_ok(move |states| ServerStateSnapshot {
             conns: states.into_iter().collect(),
+            conn_loops,
+            refused,
         }))
     }
 }
@@ -269,6 +538,21 @@ impl ServerState {
 #[derive(Debug)]
 pub struct ServerStateSnapshot {
     pub conns: HashMap<u64, ConnStateSnapshot>,
+    /// Counts by `ServerBuilder::conn_event_loops`,
+    /// single element if connections run in listener loop
+    pub conn_loops: Vec<ConnLoopStateSnapshot>,
+    pub refused: ServerRefusedConns,
+}
+
+/// Connections refused since server start.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerRefusedConns {
+    /// By `ServerBuilder::accept_filter`
+    pub filter: u64,
+    /// By `ServerConf::max_conns`
+    pub max_conns: u64,
+    /// By `ServerConf::max_conns_per_ip`
+    pub max_conns_per_ip: u64,
 }
 
 impl ServerStateSnapshot {
@@ -280,28 +564,49 @@ impl ServerStateSnapshot {
     }
 }
 
-fn spawn_server_event_loop<S, A>(
+fn spawn_server_event_loop<A>(
     handle: Handle,
-    mut conn_handles: Vec<Handle>,
+    mut conn_loops: ConnLoops,
     state: Arc<Mutex<ServerState>>,
-    tls: ServerTlsOption<A>,
-    listen: Box<dyn ToTokioListener + Send>,
+    listens: Vec<Box<dyn ToTokioListener + Send>>,
+    tls: Arc<ServerTlsAcceptors<A>>,
     shutdown_future: ShutdownFuture,
     conf: ServerConf,
-    service: S,
+    service: Arc<dyn ServerHandler>,
+    accept_filter: Option<Arc<ServerAcceptFilter>>,
     _alive_tx: mpsc::Sender<()>,
 ) -> oneshot::Receiver<()>
 where
-    S: ServerHandler,
     A: TlsAcceptor,
 {
-    let service = Arc::new(service);
+    let accepts = listens.into_iter().enumerate().map(|(i, listen)| {
+        let tokio_listener = listen.into_tokio_listener(&handle);
+        Box::pin(stream::unfold(
+            tokio_listener,
+            move |mut tokio_listener| async move {
+                loop {
+                    match tokio_listener.as_mut().accept().await {
+                        Ok((socket, peer_addr)) => {
+                            return Some(((socket, peer_addr, i), tokio_listener))
+                        }
+                        // error must not stop this or other listeners
+                        Err(e) => {
+                            warn!("listener {} accept failed: {}", i, e);
+                            if let Some(backoff) = accept_error_backoff(&e) {
+                                time::delay_for(backoff).await;
+                            }
+                        }
+                    }
+                }
+            },
+        ))
+    });
+    // connections accepted by all listeners
+    let mut accepts = stream::select_all(accepts);
 
-    let mut tokio_listener = listen.into_tokio_listener(&handle);
+    conn_loops.set_default_handle(handle.clone());
 
-    if conn_handles.is_empty() {
-        conn_handles.push(handle.clone());
-    }
+    let listener_handle = handle.clone();
 
     let loop_run = async move {
         if false {
@@ -310,10 +615,40 @@ where
         }
 
         loop {
-            let (socket, peer_addr) = tokio_listener.as_mut().accept().await?;
+            let (socket, peer_addr, listener) = match accepts.next().await {
+                Some(r) => r,
+                None => unreachable!("listener streams are infinite"),
+            };
 
             info!("accepted connection from {}", peer_addr);
 
+            if let Some(accept_filter) = &accept_filter {
+                if !accept_filter(&peer_addr) {
+                    info!("connection from {} refused by filter", peer_addr);
+                    state.lock().expect("lock").refused.filter += 1;
+                    continue;
+                }
+            }
+
+            let ip = match &peer_addr {
+                AnySocketAddr::Inet(addr) => Some(addr.ip()),
+                _ => None,
+            };
+            if state.lock().expect("lock").over_limits(ip, &conf) {
+                info!("connection from {} is over limits", peer_addr);
+                if conf.refuse_with_goaway.unwrap_or(false)
+                    && state.lock().expect("lock").start_refusal()
+                {
+                    let refuse = ServerConn::refuse_dyn(socket, tls.get(listener), conf.clone());
+                    let state = state.clone();
+                    listener_handle.spawn(refuse.map(move |r| {
+                        debug!("refused connection end: {:?}", r);
+                        state.lock().expect("lock").refusing -= 1;
+                    }));
+                }
+                continue;
+            }
+
             if socket.is_tcp() {
                 let no_delay = conf.no_delay.unwrap_or(true);
                 socket
@@ -321,35 +656,40 @@ where
                     .expect("failed to set TCP_NODELAY");
             }
 
-            // TODO: implement smarter selection
-            let handle = conn_handles[thread_rng().gen_range(0, conn_handles.len())].clone();
+            let (handle, loop_load, loop_guard) = conn_loops.select();
             let handle_clone = handle.clone();
             let state_clone = state.clone();
             handle.spawn({
-                let (conn, future) = ServerConn::new(
+                let (conn, future) = ServerConn::new_dyn(
                     &handle_clone,
                     socket,
                     peer_addr,
-                    tls.clone(),
+                    // acceptor might be replaced while server is running
+                    tls.get(listener),
                     conf.clone(),
                     service.clone(),
+                    Some(loop_load),
                 );
 
-                let conn_id = {
-                    let mut g = state_clone.lock().expect("lock");
-                    g.last_conn_id += 1;
-                    let conn_id = g.last_conn_id;
-                    let prev = g.conns.insert(conn_id, conn);
-                    assert!(prev.is_none());
-                    conn_id
-                };
+                let (future, abort) = future::abortable(future);
+
+                let conn_id = conn.id();
+                state_clone
+                    .lock()
+                    .expect("lock")
+                    .add_conn(conn_id, ServerStateConn { conn, abort, ip });
+
+                let future = future.map(|r| match r {
+                    Ok(r) => r,
+                    Err(future::Aborted) => Err(Error::Shutdown),
+                });
 
                 let future = assert_send_future::<result::Result<()>, _>(future);
 
                 FutureExt::then(future, move |r| {
+                    drop(loop_guard);
                     let mut g = state_clone.lock().expect("lock");
-                    let removed = g.conns.remove(&conn_id);
-                    assert!(removed.is_some());
+                    g.remove_conn(conn_id);
                     future::ready(r)
                 })
                 .map_err(|e| {
@@ -384,15 +724,73 @@ where
     done_rx
 }
 
-impl Server {
+impl<A: TlsAcceptor> Server<A> {
+    /// Address of the first listener.
     pub fn local_addr(&self) -> &AnySocketAddr {
-        &self.local_addr
+        &self.local_addrs[0]
+    }
+
+    /// Addresses of all listeners, `ServerBuilder::addr` first.
+    pub fn local_addrs(&self) -> &[AnySocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Handle to replace TLS acceptors of listeners while server is running.
+    pub fn tls_handle(&self) -> ServerTlsHandle<A> {
+        ServerTlsHandle::new(self.tls.clone())
     }
 
     pub fn is_alive(&self) -> bool {
         self.alive_rx.try_recv() != Err(mpsc::TryRecvError::Disconnected)
     }
 
+    /// Shutdown server gracefully.
+    ///
+    /// Server stops accepting connections and sends `GOAWAY` to all
+    /// connections, which are closed after in-flight streams complete.
+    /// Connections still alive after `drain_timeout` are closed forcibly.
+    ///
+    /// Returned future resolves when all connections are closed.
+    pub fn shutdown_graceful(&self, drain_timeout: Duration) -> HttpFutureSend<()> {
+        let conns_closed = {
+            let mut g = self.state.lock().expect("lock");
+            // Must be set before accept loop is stopped
+            g.graceful_shutdown = true;
+            for c in g.conns.values() {
+                c.conn.graceful_shutdown();
+            }
+            g.conns_closed()
+        };
+
+        self.shutdown.shutdown();
+
+        let state = self.state.clone();
+        let (done_tx, done_rx) = oneshot::channel();
+
+        self.handle.spawn(async move {
+            if time::timeout(drain_timeout, conns_closed).await.is_err() {
+                let conns_closed = {
+                    let mut g = state.lock().expect("lock");
+                    warn!(
+                        "drain timeout expired, closing {} connections",
+                        g.conns.len()
+                    );
+                    for c in g.conns.values() {
+                        c.abort.abort();
+                    }
+                    g.conns_closed()
+                };
+                conns_closed.await.ok();
+            }
+            // ignore error, caller might not wait
+            done_tx.send(()).ok();
+        });
+
+        // Cancelled if server event loop is already stopped,
+        // which means connections are closed too
+        Box::pin(done_rx.then(|_| future::ok(())))
+    }
+
     // for tests
     pub fn dump_state(&self) -> HttpFutureSend<ServerStateSnapshot> {
         let g = self.state.lock().expect("lock");
@@ -401,7 +799,7 @@ impl Server {
 }
 
 // We shutdown the server in the destructor.
-impl Drop for Server {
+impl<A: TlsAcceptor> Drop for Server<A> {
     fn drop(&mut self) {
         self.shutdown.shutdown();
 
@@ -414,6 +812,8 @@ impl Drop for Server {
             }
         };
 
-        self.local_addr.cleanup();
+        for addr in &self.bound_addrs {
+            addr.cleanup();
+        }
     }
 }
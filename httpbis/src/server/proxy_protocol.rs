@@ -0,0 +1,335 @@
+//! PROXY protocol header sent by load balancers before connection data.
+//!
+//! See <https://www.haproxy.org/download/2.0/doc/proxy-protocol.txt>
+
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
+use std::str;
+
+use bytes::Bytes;
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+
+use crate::error::Error;
+use crate::result::Result;
+
+/// Version 2 header signature.
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+/// Version 1 header prefix.
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Max version 1 header length including CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Type-length-value field of version 2 header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyTlv {
+    pub kind: u8,
+    pub value: Bytes,
+}
+
+impl ProxyTlv {
+    /// Application-Layer Protocol Negotiation.
+    pub const ALPN: u8 = 0x01;
+    /// Host name sent by client.
+    pub const AUTHORITY: u8 = 0x02;
+    /// Checksum of the header.
+    pub const CRC32C: u8 = 0x03;
+    /// Padding.
+    pub const NOOP: u8 = 0x04;
+    /// Connection id.
+    pub const UNIQUE_ID: u8 = 0x05;
+    /// TLS information.
+    pub const SSL: u8 = 0x20;
+    /// Network namespace.
+    pub const NETNS: u8 = 0x30;
+}
+
+/// Addresses of the proxied connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// Client address.
+    ///
+    /// `None` for `UNKNOWN` (v1) and `LOCAL` (v2) headers, which are sent
+    /// by proxy health checks, and for non-inet address families.
+    pub source: Option<SocketAddr>,
+    /// Address client connected to.
+    pub destination: Option<SocketAddr>,
+    /// Version 2 TLVs, empty for version 1 headers.
+    pub tlvs: Vec<ProxyTlv>,
+}
+
+impl ProxyHeader {
+    /// Value of the first TLV of given type.
+    pub fn tlv(&self, kind: u8) -> Option<&Bytes> {
+        self.tlvs.iter().find(|t| t.kind == kind).map(|t| &t.value)
+    }
+}
+
+fn invalid<T>(message: &str) -> Result<T> {
+    Err(Error::InvalidProxyHeader(message.to_owned()))
+}
+
+/// `a` is a prefix of `b` or `b` is a prefix of `a`.
+fn prefix_match(a: &[u8], b: &[u8]) -> bool {
+    let len = a.len().min(b.len());
+    a[..len] == b[..len]
+}
+
+fn parse_v1(line: &str) -> Result<ProxyHeader> {
+    let mut parts = line.split(' ');
+    if parts.next() != Some("PROXY") {
+        return invalid("expecting PROXY");
+    }
+    match parts.next() {
+        Some("UNKNOWN") => return Ok(ProxyHeader::default()),
+        Some("TCP4") | Some("TCP6") => {}
+        _ => return invalid("unknown protocol"),
+    }
+    let parts: Vec<&str> = parts.collect();
+    let (source, destination, source_port, destination_port) = match &parts[..] {
+        [a, b, c, d] => (a, b, c, d),
+        _ => return invalid("expecting addresses and ports"),
+    };
+    let addr = |ip: &str, port: &str| -> Result<SocketAddr> {
+        match (ip.parse::<IpAddr>(), port.parse::<u16>()) {
+            (Ok(ip), Ok(port)) => Ok(SocketAddr::new(ip, port)),
+            _ => invalid("malformed address"),
+        }
+    };
+    Ok(ProxyHeader {
+        source: Some(addr(source, source_port)?),
+        destination: Some(addr(destination, destination_port)?),
+        tlvs: Vec::new(),
+    })
+}
+
+fn parse_tlvs(mut data: &[u8]) -> Result<Vec<ProxyTlv>> {
+    let mut tlvs = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 3 {
+            return invalid("truncated TLV");
+        }
+        let len = (data[1] as usize) << 8 | data[2] as usize;
+        if data.len() < 3 + len {
+            return invalid("truncated TLV");
+        }
+        tlvs.push(ProxyTlv {
+            kind: data[0],
+            value: Bytes::copy_from_slice(&data[3..3 + len]),
+        });
+        data = &data[3 + len..];
+    }
+    Ok(tlvs)
+}
+
+fn parse_v2(header: &[u8]) -> Result<ProxyHeader> {
+    let version_command = header[12];
+    if version_command >> 4 != 2 {
+        return invalid("unsupported version");
+    }
+    let local = match version_command & 0x0f {
+        0 => true,
+        1 => false,
+        _ => return invalid("unknown command"),
+    };
+
+    let data = &header[16..];
+    let addrs_len = match header[13] >> 4 {
+        // AF_UNSPEC
+        0 => 0,
+        // AF_INET
+        1 => 12,
+        // AF_INET6
+        2 => 36,
+        // AF_UNIX
+        3 => 216,
+        _ => return invalid("unknown address family"),
+    };
+    if data.len() < addrs_len {
+        return invalid("truncated addresses");
+    }
+    let (addrs, tlvs) = data.split_at(addrs_len);
+    let port = |b: &[u8]| (b[0] as u16) << 8 | b[1] as u16;
+
+    let (source, destination) = match addrs_len {
+        _ if local => (None, None),
+        12 => {
+            let ip = |b: &[u8]| IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3]));
+            (
+                Some(SocketAddr::new(ip(&addrs[0..4]), port(&addrs[8..10]))),
+                Some(SocketAddr::new(ip(&addrs[4..8]), port(&addrs[10..12]))),
+            )
+        }
+        36 => {
+            let ip = |b: &[u8]| {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(b);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            };
+            (
+                Some(SocketAddr::new(ip(&addrs[0..16]), port(&addrs[32..34]))),
+                Some(SocketAddr::new(ip(&addrs[16..32]), port(&addrs[34..36]))),
+            )
+        }
+        _ => (None, None),
+    };
+
+    Ok(ProxyHeader {
+        source,
+        destination,
+        tlvs: parse_tlvs(tlvs)?,
+    })
+}
+
+/// Parse header at the start of the buffer.
+///
+/// Returns `None` if more data is needed, otherwise header and its length.
+pub(crate) fn parse(buf: &[u8]) -> Result<Option<(ProxyHeader, usize)>> {
+    if prefix_match(buf, V2_SIGNATURE) {
+        if buf.len() < 16 {
+            return Ok(None);
+        }
+        let len = 16 + ((buf[14] as usize) << 8 | buf[15] as usize);
+        if buf.len() < len {
+            return Ok(None);
+        }
+        return Ok(Some((parse_v2(&buf[..len])?, len)));
+    }
+
+    if prefix_match(buf, V1_PREFIX) {
+        let search = &buf[..buf.len().min(V1_MAX_LEN)];
+        return match search.windows(2).position(|w| w == b"\r\n") {
+            Some(pos) => {
+                let line = match str::from_utf8(&buf[..pos]) {
+                    Ok(line) => line,
+                    Err(_) => return invalid("header is not ASCII"),
+                };
+                Ok(Some((parse_v1(line)?, pos + 2)))
+            }
+            None if buf.len() >= V1_MAX_LEN => invalid("header is too long"),
+            None => Ok(None),
+        };
+    }
+
+    invalid("missing header")
+}
+
+/// Read header from the socket.
+///
+/// Returns header and bytes read after the header.
+pub(crate) async fn read_proxy_header<S>(socket: &mut S) -> Result<(ProxyHeader, BytesMut)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = BytesMut::with_capacity(V1_MAX_LEN);
+    loop {
+        if let Some((header, len)) = parse(&buf)? {
+            let rem = buf.split_off(len);
+            debug!("PROXY header: {:?}", header);
+            return Ok((header, rem));
+        }
+        if socket.read_buf(&mut buf).await? == 0 {
+            return invalid("EOF before header end");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn v2(command: u8, family: u8, addrs: &[u8], tlvs: &[u8]) -> Vec<u8> {
+        let len = addrs.len() + tlvs.len();
+        let mut r = V2_SIGNATURE.to_vec();
+        r.extend_from_slice(&[0x20 | command, family, (len >> 8) as u8, len as u8]);
+        r.extend_from_slice(addrs);
+        r.extend_from_slice(tlvs);
+        r
+    }
+
+    #[test]
+    fn v1() {
+        let (header, len) = parse(b"PROXY TCP4 1.2.3.4 5.6.7.8 1111 443\r\nPRI")
+            .unwrap()
+            .unwrap();
+        assert_eq!(37, len);
+        assert_eq!(Some("1.2.3.4:1111".parse().unwrap()), header.source);
+        assert_eq!(Some("5.6.7.8:443".parse().unwrap()), header.destination);
+
+        let (header, _) = parse(b"PROXY TCP6 ::1 ::2 1 2\r\n").unwrap().unwrap();
+        assert_eq!(Some("[::1]:1".parse().unwrap()), header.source);
+
+        let (header, _) = parse(b"PROXY UNKNOWN ::1 ::2 1 2\r\n").unwrap().unwrap();
+        assert_eq!(ProxyHeader::default(), header);
+
+        assert!(parse(b"PROXY TCP4 1.2.3.4").unwrap().is_none());
+        assert!(parse(b"PRO").unwrap().is_none());
+        assert!(parse(b"PROXY TCP4 1.2.3.4 5.6.7.8 1111\r\n").is_err());
+        assert!(parse(b"PROXY TCP4 1.2.3.4 5.6.7.8 1111 99999\r\n").is_err());
+        assert!(parse(&[b'P'; 200][..]).is_err());
+        assert!(parse(b"PRI * HTTP/2.0\r\n").is_err());
+        assert!(parse(&[0x16, 0x03, 0x01]).is_err());
+    }
+
+    #[test]
+    fn v2_inet() {
+        let addrs = [1, 2, 3, 4, 5, 6, 7, 8, 0x04, 0x57, 0x01, 0xbb];
+        let tlvs = [ProxyTlv::AUTHORITY, 0, 3, b'a', b'.', b'b'];
+        let mut data = v2(1, 0x11, &addrs, &tlvs);
+        let len = data.len();
+        data.extend_from_slice(b"PRI");
+
+        let (header, header_len) = parse(&data).unwrap().unwrap();
+        assert_eq!(len, header_len);
+        assert_eq!(Some("1.2.3.4:1111".parse().unwrap()), header.source);
+        assert_eq!(Some("5.6.7.8:443".parse().unwrap()), header.destination);
+        assert_eq!(
+            Some(&Bytes::from_static(b"a.b")),
+            header.tlv(ProxyTlv::AUTHORITY)
+        );
+
+        assert!(parse(&data[..len - 1]).unwrap().is_none());
+        assert!(parse(&data[..5]).unwrap().is_none());
+    }
+
+    #[test]
+    fn v2_inet6_local_errors() {
+        let mut addrs = vec![0; 36];
+        addrs[15] = 1;
+        addrs[31] = 2;
+        addrs[35] = 80;
+        let (header, _) = parse(&v2(1, 0x21, &addrs, &[])).unwrap().unwrap();
+        assert_eq!(Some("[::1]:0".parse().unwrap()), header.source);
+        assert_eq!(Some("[::2]:80".parse().unwrap()), header.destination);
+
+        let (header, _) = parse(&v2(0, 0x21, &addrs, &[])).unwrap().unwrap();
+        assert_eq!(None, header.source);
+
+        let (header, _) = parse(&v2(0, 0x00, &[], &[])).unwrap().unwrap();
+        assert_eq!(ProxyHeader::default(), header);
+
+        // truncated TLV
+        assert!(parse(&v2(1, 0x11, &[0; 12], &[1, 0, 5, 0])).is_err());
+        // unknown command
+        assert!(parse(&v2(2, 0x11, &[0; 12], &[])).is_err());
+        // addresses longer than header
+        assert!(parse(&v2(1, 0x21, &[0; 12], &[])).is_err());
+    }
+
+    #[test]
+    fn read() {
+        let data = b"PROXY TCP4 1.2.3.4 5.6.7.8 1111 443\r\nPRI * HTTP/2.0";
+        let mut socket = &data[..];
+        let (header, rem) = futures::executor::block_on(read_proxy_header(&mut socket)).unwrap();
+        assert_eq!(Some("1.2.3.4:1111".parse().unwrap()), header.source);
+        let mut rest = rem.to_vec();
+        rest.extend_from_slice(socket);
+        assert_eq!(&b"PRI * HTTP/2.0"[..], &rest[..]);
+
+        let mut socket = &b"PROXY TCP4"[..];
+        assert!(futures::executor::block_on(read_proxy_header(&mut socket)).is_err());
+    }
+}
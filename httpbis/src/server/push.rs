@@ -0,0 +1,174 @@
+//! Server push request sent from `ServerResponse` to the connection loop.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::common::conn_command_channel::ConnCommandSender;
+use crate::common::sender::CommonSender;
+use crate::common::window_size::ConnOutWindowStreams;
+use crate::common::window_size::StreamOutWindowSender;
+use crate::error;
+use crate::headers_place::HeadersPlace;
+use crate::req_resp::RequestOrResponse;
+use crate::result;
+use crate::server::cancel::server_cancel;
+use crate::server::cancel::ServerCancelSender;
+use crate::server::conn::ServerToWriteMessage;
+use crate::server::types::ServerTypes;
+use crate::solicit::stream_id::StreamId;
+use crate::Headers;
+use crate::ServerResponse;
+
+/// `PUSH_PROMISE` with promised stream reserved by `ServerResponse::push_promise`.
+pub(crate) struct ServerPushPromise {
+    pub parent_stream_id: StreamId,
+    pub promised_stream_id: StreamId,
+    pub headers: Headers,
+    /// Window of pushed response, created empty,
+    /// increased by the connection loop when the stream is opened
+    pub out_window: StreamOutWindowSender,
+    /// Cancels pushed response if the connection refuses to push
+    pub cancel: ServerCancelSender,
+}
+
+/// Whether `ServerResponse::push_promise` is allowed.
+pub(crate) enum ServerResponsePush {
+    /// HTTP/1 response
+    NotSupported,
+    /// Response on pushed stream
+    Pushed,
+    /// Response to HTTP/2 request
+    Allowed(Arc<ServerPushState>),
+}
+
+/// Connection state needed to reserve promised streams outside of the connection loop.
+///
+/// Mirrored by the connection loop after each event,
+/// the loop checks the limits again when it sends `PUSH_PROMISE`.
+pub(crate) struct ServerPushState {
+    inner: Mutex<ServerPushStateInner>,
+    out_window: ConnOutWindowStreams,
+}
+
+struct ServerPushStateInner {
+    last_stream_id: StreamId,
+    enable_push: bool,
+    going_away: bool,
+    max_concurrent_streams: u32,
+    /// Pushed streams open in the connection loop
+    open: usize,
+    /// Promised streams not yet processed by the connection loop
+    reserved: usize,
+}
+
+impl ServerPushState {
+    pub fn new(out_window: ConnOutWindowStreams) -> ServerPushState {
+        ServerPushState {
+            inner: Mutex::new(ServerPushStateInner {
+                last_stream_id: 0,
+                enable_push: true,
+                going_away: false,
+                max_concurrent_streams: u32::max_value(),
+                open: 0,
+                reserved: 0,
+            }),
+            out_window,
+        }
+    }
+
+    /// Called by the connection loop after each event.
+    pub fn update(
+        &self,
+        enable_push: bool,
+        going_away: bool,
+        max_concurrent_streams: u32,
+        open: usize,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.enable_push = enable_push;
+        inner.going_away = going_away;
+        inner.max_concurrent_streams = max_concurrent_streams;
+        inner.open = open;
+    }
+
+    /// Called by the connection loop when reserved stream is opened or rejected.
+    pub fn processed(&self, opened: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.reserved -= 1;
+        if opened {
+            inner.open += 1;
+        }
+    }
+
+    /// Allocate promised stream id and send `PUSH_PROMISE` request to the connection loop.
+    pub fn reserve(
+        &self,
+        parent_stream_id: StreamId,
+        headers: Headers,
+        write_tx: &ConnCommandSender<ServerTypes>,
+    ) -> result::Result<ServerResponse> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.going_away {
+            return Err(error::Error::PushPromiseAfterGoaway);
+        }
+        if !inner.enable_push {
+            return Err(error::Error::PushDisabled);
+        }
+        if inner.open + inner.reserved >= inner.max_concurrent_streams as usize {
+            return Err(error::Error::PushConcurrencyLimit(
+                inner.max_concurrent_streams,
+            ));
+        }
+
+        let promised_stream_id = match inner.last_stream_id {
+            0 => 2,
+            n => n + 2,
+        };
+
+        let (out_window, out_window_receiver) = self.out_window.new_stream(0);
+        let (cancel, cancelled) = server_cancel();
+
+        // Message is sent under the lock, so the loop receives promised ids in order
+        write_tx.unbounded_send(ServerToWriteMessage::PushPromise(ServerPushPromise {
+            parent_stream_id,
+            promised_stream_id,
+            headers,
+            out_window,
+            cancel,
+        }))?;
+
+        inner.last_stream_id = promised_stream_id;
+        inner.reserved += 1;
+
+        Ok(ServerResponse {
+            common: CommonSender::new(
+                promised_stream_id,
+                write_tx.clone(),
+                out_window_receiver,
+                false,
+            ),
+            drop_callback: None,
+            push: ServerResponsePush::Pushed,
+            headers_hooks: Vec::new(),
+            cancelled,
+            body_encoder_factory: None,
+            body_encoder: None,
+        })
+    }
+}
+
+/// Check headers of promised request.
+pub(crate) fn validate_push_promise_headers(headers: &Headers) -> result::Result<()> {
+    // 8.2
+    // Promised requests MUST be cacheable, MUST be safe,
+    // and MUST NOT include a request body.
+    if let Err(e) = headers.validate(RequestOrResponse::Request, HeadersPlace::Initial) {
+        warn!("invalid push promise headers: {:?} {:?}", e, headers);
+        return Err(error::Error::InvalidPushPromiseHeaders);
+    }
+    match headers.get_opt(":method") {
+        Some("GET") | Some("HEAD") => Ok(()),
+        _ => Err(error::Error::InvalidPushPromiseHeaders),
+    }
+}
@@ -2,6 +2,7 @@ use crate::common::conn_command_channel::ConnCommandSender;
 use crate::common::increase_in_window::IncreaseInWindow;
 use crate::common::stream_from_network::StreamFromNetwork;
 use crate::common::stream_queue_sync::stream_queue_sync;
+use crate::server::extensions::Extensions;
 use crate::server::increase_in_window::ServerIncreaseInWindow;
 use crate::server::stream_handler::ServerRequestStreamHandler;
 use crate::server::stream_handler::ServerRequestStreamHandlerHolder;
@@ -15,6 +16,8 @@ pub struct ServerRequest<'a> {
     pub headers: Headers,
     /// True if requests ends with headers
     pub end_stream: bool,
+    /// Values attached to the request by wrapping handlers
+    pub extensions: Extensions,
     pub(crate) stream_id: StreamId,
     /// Stream in window size at the moment of request start
     pub(crate) in_window_size: u32,
@@ -23,6 +26,11 @@ pub struct ServerRequest<'a> {
 }
 
 impl<'a> ServerRequest<'a> {
+    /// Id of the stream the request was received on.
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
     pub fn make_stream(self) -> HttpStreamAfterHeaders {
         if self.end_stream {
             HttpStreamAfterHeaders::empty()
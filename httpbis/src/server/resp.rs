@@ -1,9 +1,16 @@
 use crate::assert_types::assert_send;
 use crate::common::sender::CommonSender;
 use crate::common::sender::SendError;
+use crate::content_coding::code_stream;
+use crate::content_coding::BodyEncoder;
 
+use crate::error;
 use crate::result;
+use crate::server::cancel::ServerCancelled;
+use crate::server::push::validate_push_promise_headers;
+use crate::server::push::ServerResponsePush;
 use crate::server::types::ServerTypes;
+use crate::solicit::end_stream::EndStream;
 use crate::ErrorCode;
 use crate::Headers;
 use crate::HttpStreamAfterHeaders;
@@ -16,12 +23,23 @@ use futures::task::Context;
 use std::mem;
 use std::task::Poll;
 
+type HeadersHook = Box<dyn FnMut(&mut Headers) + Send>;
+/// Called with final response headers and whether the body follows them.
+type BodyEncoderFactory = Box<dyn FnMut(&mut Headers, EndStream) -> Option<BodyEncoder> + Send>;
+
 // NOTE: Keep in sync with ClientRequest
 pub struct ServerResponse {
     pub(crate) common: CommonSender<ServerTypes>,
     // need to replace with FnOnce when rust allows it
     pub(crate) drop_callback:
         Option<Box<dyn FnMut(&mut ServerResponse) -> result::Result<()> + Send>>,
+    pub(crate) push: ServerResponsePush,
+    /// Invoked with response headers before they are sent
+    pub(crate) headers_hooks: Vec<HeadersHook>,
+    pub(crate) cancelled: ServerCancelled,
+    /// Invoked after headers hooks, returns encoder if the body is to be compressed
+    pub(crate) body_encoder_factory: Option<BodyEncoderFactory>,
+    pub(crate) body_encoder: Option<BodyEncoder>,
 }
 
 impl Drop for ServerResponse {
@@ -64,27 +82,112 @@ impl ServerResponse {
         self.common.poll(cx)
     }
 
-    pub fn send_headers(&mut self, headers: Headers) -> Result<(), SendError> {
-        self.common.send_headers(headers)
+    /// Register a callback invoked with response headers before they are sent.
+    ///
+    /// Callbacks are invoked in registration order. Useful to inspect
+    /// or modify headers of response produced by another handler.
+    pub fn on_send_headers<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Headers) + Send + 'static,
+    {
+        self.headers_hooks.push(Box::new(f));
+    }
+
+    fn run_headers_hooks(&mut self, headers: &mut Headers) {
+        if self.state() == SenderState::ExpectingHeaders {
+            for hook in &mut self.headers_hooks {
+                hook(headers);
+            }
+        }
+    }
+
+    pub(crate) fn set_body_encoder_factory<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Headers, EndStream) -> Option<BodyEncoder> + Send + 'static,
+    {
+        self.body_encoder_factory = Some(Box::new(f));
     }
 
-    pub fn send_headers_end_of_stream(&mut self, headers: Headers) -> Result<(), SendError> {
+    /// Future resolved when client resets the stream or the connection dies,
+    /// so handler can stop producing the response.
+    ///
+    /// Never resolves if the stream completes normally.
+    pub fn cancelled(&self) -> ServerCancelled {
+        self.cancelled.clone()
+    }
+
+    pub fn send_headers(&mut self, mut headers: Headers) -> Result<(), SendError> {
+        self.run_headers_hooks(&mut headers);
+        // informational responses are followed by final headers
+        let informational = match headers.get_opt_parse::<u32>(":status") {
+            Some(status) => status < 200,
+            None => false,
+        };
+        let body_encoder = match self.body_encoder_factory {
+            Some(ref mut factory)
+                if !informational && self.common.state() == SenderState::ExpectingHeaders =>
+            {
+                factory(&mut headers, EndStream::No)
+            }
+            _ => None,
+        };
+        self.common.send_headers(headers)?;
+        if !informational {
+            self.body_encoder = body_encoder;
+        }
+        Ok(())
+    }
+
+    pub fn send_headers_end_of_stream(&mut self, mut headers: Headers) -> Result<(), SendError> {
+        self.run_headers_hooks(&mut headers);
+        if let Some(ref mut factory) = self.body_encoder_factory {
+            if self.common.state() == SenderState::ExpectingHeaders {
+                // no body to encode, but factory may update headers
+                factory(&mut headers, EndStream::Yes);
+            }
+        }
         self.common.send_headers_end_of_stream(headers)
     }
 
     pub fn send_data(&mut self, data: Bytes) -> Result<(), SendError> {
+        let data = match self.body_encoder {
+            Some(ref mut encoder) => encoder.encode(&data),
+            None => data,
+        };
+        if data.is_empty() && self.body_encoder.is_some() {
+            // nothing to send yet
+            return match self.state() {
+                SenderState::ExpectingBodyOrTrailers => Ok(()),
+                state => Err(SendError::IncorrectState(state)),
+            };
+        }
         self.common.send_data(data)
     }
 
     pub fn send_data_end_of_stream(&mut self, data: Bytes) -> Result<(), SendError> {
+        let data = match self.body_encoder.take() {
+            Some(mut encoder) => {
+                let mut data = encoder.encode(&data).to_vec();
+                data.extend_from_slice(&encoder.finish());
+                Bytes::from(data)
+            }
+            None => data,
+        };
         self.common.send_data_end_of_stream(data)
     }
 
     pub fn send_trailers(&mut self, trailers: Headers) -> Result<(), SendError> {
+        if let Some(mut encoder) = self.body_encoder.take() {
+            self.common.send_data(encoder.finish())?;
+        }
         self.common.send_trailers(trailers)
     }
 
     pub fn pull_from_stream(&mut self, stream: HttpStreamAfterHeaders) -> Result<(), SendError> {
+        let stream = match self.body_encoder.take() {
+            Some(encoder) => code_stream(stream, encoder),
+            None => stream,
+        };
         self.common.pull_from_stream(stream)
     }
 
@@ -92,7 +195,7 @@ impl ServerResponse {
     where
         S: Stream<Item = result::Result<Bytes>> + Send + 'static,
     {
-        self.common.pull_bytes_from_stream(stream)
+        self.pull_from_stream(HttpStreamAfterHeaders::bytes(stream))
     }
 
     pub fn send_message(&mut self, message: SimpleHttpMessage) -> Result<(), SendError> {
@@ -117,6 +220,33 @@ impl ServerResponse {
         self.send_message(SimpleHttpMessage::internal_error_500(message))
     }
 
+    /// Promise a response to the request with given headers.
+    ///
+    /// Reserves the promised stream and returns the sender for the pushed response,
+    /// `PUSH_PROMISE` is sent on this stream by the connection loop.
+    /// Must be called before this response is finished. Fails if the peer disabled push,
+    /// too many pushed streams are open, or the connection is going away.
+    /// If the connection rejects the push after the stream is reserved,
+    /// the pushed response is cancelled with `ServerCancelReason::PushRejected`.
+    pub fn push_promise(&mut self, request_headers: Headers) -> result::Result<ServerResponse> {
+        let push = match self.push {
+            ServerResponsePush::NotSupported => return Err(error::Error::PushNotSupported),
+            ServerResponsePush::Pushed => return Err(error::Error::PushPromiseOnPushedStream),
+            ServerResponsePush::Allowed(ref push) => push.clone(),
+        };
+        validate_push_promise_headers(&request_headers)?;
+
+        let parent_stream_id = self.common.stream_id();
+        // 6.6
+        // PUSH_PROMISE frames MUST only be sent on a peer-initiated stream that
+        // is in either the "open" or "half-closed (remote)" state.
+        if self.common.is_closed() {
+            return Err(error::Error::PushPromiseOnClosedStream(parent_stream_id));
+        }
+        let write_tx = self.common.write_tx()?;
+        push.reserve(parent_stream_id, request_headers, write_tx)
+    }
+
     pub fn reset(&mut self, error_code: ErrorCode) -> Result<(), SendError> {
         self.common.reset(error_code)
     }
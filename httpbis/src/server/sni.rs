@@ -0,0 +1,208 @@
+//! Server name indication from TLS `ClientHello`.
+
+use std::io;
+
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+
+use crate::net::prefixed::PrefixedSocket;
+use crate::net::socket::SocketStream;
+
+/// TLS record content type of handshake messages.
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+/// Handshake message type of `ClientHello`.
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+/// `server_name` extension type.
+const EXTENSION_SERVER_NAME: u16 = 0;
+/// `host_name` server name type.
+const NAME_TYPE_HOST_NAME: u8 = 0;
+/// Max TLS record payload length with allowed expansion.
+const MAX_RECORD_LEN: usize = 0x4000 + 2048;
+
+/// Read the first TLS record from the socket without interpreting it.
+///
+/// Returned bytes must be fed to TLS acceptor before the rest of the socket.
+/// Read is stopped early if the record is not a handshake record.
+pub(crate) async fn read_first_record<S>(socket: &mut S) -> io::Result<BytesMut>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = BytesMut::new();
+    buf.resize(5, 0);
+    socket.read_exact(&mut buf).await?;
+    if buf[0] != CONTENT_TYPE_HANDSHAKE {
+        return Ok(buf);
+    }
+    let len = (buf[3] as usize) << 8 | buf[4] as usize;
+    if len > MAX_RECORD_LEN {
+        return Ok(buf);
+    }
+    buf.resize(5 + len, 0);
+    socket.read_exact(&mut buf[5..]).await?;
+    Ok(buf)
+}
+
+/// Read server name from the socket before TLS handshake.
+///
+/// Returned socket replays the read bytes.
+pub(crate) async fn peek_sni<S: SocketStream>(
+    mut socket: S,
+) -> io::Result<(PrefixedSocket<S>, Option<String>)> {
+    let record = read_first_record(&mut socket).await?;
+    let sni = parse_sni(&record);
+    debug!("client hello server name: {:?}", sni);
+    Ok((PrefixedSocket::new(record, socket), sni))
+}
+
+/// Cursor over `ClientHello` bytes, `None` when data is truncated.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (r, rem) = self.0.split_at(len);
+        self.0 = rem;
+        Some(r)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let b = self.take(2)?;
+        Some((b[0] as u16) << 8 | b[1] as u16)
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        let b = self.take(3)?;
+        Some((b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize)
+    }
+
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+/// Host name from `server_name` extension of `ClientHello` in the first TLS record.
+///
+/// Host name is converted to lower case.
+pub(crate) fn parse_sni(record: &[u8]) -> Option<String> {
+    let mut r = Reader(record);
+    if r.u8()? != CONTENT_TYPE_HANDSHAKE {
+        return None;
+    }
+    // legacy version
+    r.take(2)?;
+    let mut r = Reader(r.vec16()?);
+
+    if r.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let len = r.u24()?;
+    // `ClientHello` may span several records, but extensions usually fit in the first one
+    let mut r = Reader(r.take(len).unwrap_or(r.0));
+
+    // legacy version and random
+    r.take(2 + 32)?;
+    // session id
+    r.vec8()?;
+    // cipher suites
+    r.vec16()?;
+    // compression methods
+    r.vec8()?;
+
+    let mut extensions = Reader(r.vec16()?);
+    while !extensions.0.is_empty() {
+        let extension_type = extensions.u16()?;
+        let data = extensions.vec16()?;
+        if extension_type != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let mut names = Reader(Reader(data).vec16()?);
+        while !names.0.is_empty() {
+            let name_type = names.u8()?;
+            let name = names.vec16()?;
+            if name_type == NAME_TYPE_HOST_NAME {
+                let name = std::str::from_utf8(name).ok()?;
+                return Some(name.to_ascii_lowercase());
+            }
+        }
+        return None;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vec16(data: &[u8]) -> Vec<u8> {
+        let mut r = vec![(data.len() >> 8) as u8, data.len() as u8];
+        r.extend_from_slice(data);
+        r
+    }
+
+    fn client_hello(sni: Option<&str>) -> Vec<u8> {
+        let mut extensions = Vec::new();
+        // supported_versions
+        extensions.extend_from_slice(&[0x00, 0x2b]);
+        extensions.extend(vec16(&[0x02, 0x03, 0x04]));
+        if let Some(sni) = sni {
+            let mut name = vec![NAME_TYPE_HOST_NAME];
+            name.extend(vec16(sni.as_bytes()));
+            extensions.extend_from_slice(&[0x00, 0x00]);
+            extensions.extend(vec16(&vec16(&name)));
+        }
+
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[0; 32]);
+        // session id
+        hello.extend_from_slice(&[1, 0xaa]);
+        // cipher suites
+        hello.extend(vec16(&[0x13, 0x01]));
+        // compression methods
+        hello.extend_from_slice(&[1, 0]);
+        hello.extend(vec16(&extensions));
+
+        let len = hello.len();
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO, 0, (len >> 8) as u8, len as u8];
+        handshake.extend(hello);
+
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend(vec16(&handshake));
+        record
+    }
+
+    #[test]
+    fn sni() {
+        assert_eq!(
+            Some("example.com".to_owned()),
+            parse_sni(&client_hello(Some("Example.COM")))
+        );
+        assert_eq!(None, parse_sni(&client_hello(None)));
+        let hello = client_hello(Some("example.com"));
+        assert_eq!(None, parse_sni(&hello[..hello.len() - 3]));
+        assert_eq!(None, parse_sni(b"GET / HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn read_record() {
+        let hello = client_hello(Some("example.com"));
+        let mut data = hello.clone();
+        data.extend_from_slice(b"rest");
+        let mut socket = &data[..];
+        let record = futures::executor::block_on(read_first_record(&mut socket)).unwrap();
+        assert_eq!(&hello[..], &record[..]);
+        assert_eq!(b"rest", socket);
+    }
+}
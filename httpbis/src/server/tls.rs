@@ -1,10 +1,23 @@
+use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use tls_api::TlsAcceptor;
 
+use crate::error::Error;
+use crate::result::Result;
+use crate::server::handler_hosts::HostMap;
+
 pub enum ServerTlsOption<A: TlsAcceptor> {
     Plain,
     Tls(Arc<A>),
+    /// Acceptor is selected by server name sent by client
+    TlsSni(Arc<ServerTlsSniMap<A>>),
 }
 
 impl<A: TlsAcceptor> Clone for ServerTlsOption<A> {
@@ -12,6 +25,240 @@ impl<A: TlsAcceptor> Clone for ServerTlsOption<A> {
         match self {
             &ServerTlsOption::Plain => ServerTlsOption::Plain,
             &ServerTlsOption::Tls(ref a) => ServerTlsOption::Tls(a.clone()),
+            &ServerTlsOption::TlsSni(ref m) => ServerTlsOption::TlsSni(m.clone()),
+        }
+    }
+}
+
+/// TLS acceptors by server name indication (SNI).
+///
+/// Names may be exact or wildcard (`*.example.com`). Default acceptor is used
+/// when client sends no SNI or unknown name, without default acceptor such
+/// connections are closed.
+pub struct ServerTlsSniMap<A: TlsAcceptor> {
+    acceptors: HostMap<Arc<A>>,
+    default: Option<Arc<A>>,
+}
+
+impl<A: TlsAcceptor> Default for ServerTlsSniMap<A> {
+    fn default() -> Self {
+        ServerTlsSniMap {
+            acceptors: HostMap::default(),
+            default: None,
+        }
+    }
+}
+
+impl<A: TlsAcceptor> ServerTlsSniMap<A> {
+    pub fn new() -> ServerTlsSniMap<A> {
+        Default::default()
+    }
+
+    /// Use acceptor for connections to given server name or wildcard.
+    pub fn insert(&mut self, name: &str, acceptor: A) {
+        self.acceptors.insert(name, Arc::new(acceptor));
+    }
+
+    /// Use acceptor for connections without SNI or to unknown names.
+    pub fn set_default(&mut self, acceptor: A) {
+        self.default = Some(Arc::new(acceptor));
+    }
+
+    /// Acceptor for server name from client hello.
+    pub(crate) fn select(&self, sni: Option<&str>) -> Option<&Arc<A>> {
+        sni.and_then(|sni| self.acceptors.get(sni))
+            .or(self.default.as_ref())
+    }
+}
+
+/// TLS options of server listeners shared with accept loop,
+/// so acceptor can be replaced while server is running.
+pub(crate) struct ServerTlsAcceptors<A: TlsAcceptor> {
+    listeners: Mutex<Vec<ServerTlsOption<A>>>,
+}
+
+impl<A: TlsAcceptor> ServerTlsAcceptors<A> {
+    /// TLS options of all listeners, in listener order.
+    pub fn new(listeners: Vec<ServerTlsOption<A>>) -> ServerTlsAcceptors<A> {
+        ServerTlsAcceptors {
+            listeners: Mutex::new(listeners),
         }
     }
+
+    /// TLS option for a connection accepted by the listener.
+    pub fn get(&self, listener: usize) -> ServerTlsOption<A> {
+        self.listeners.lock().expect("lock")[listener].clone()
+    }
+
+    /// Check that listener exists and is a TLS listener.
+    fn check(&self, listener: usize) -> Result<()> {
+        match self.listeners.lock().expect("lock").get(listener) {
+            Some(ServerTlsOption::Tls(..)) | Some(ServerTlsOption::TlsSni(..)) => Ok(()),
+            _ => Err(Error::NotTlsListener(listener)),
+        }
+    }
+
+    /// Replace option of a TLS listener, `Tls` may be replaced with `TlsSni` and vice versa.
+    fn replace(&self, listener: usize, option: ServerTlsOption<A>) -> Result<()> {
+        match self.listeners.lock().expect("lock").get_mut(listener) {
+            Some(current @ ServerTlsOption::Tls(..))
+            | Some(current @ ServerTlsOption::TlsSni(..)) => {
+                *current = option;
+                Ok(())
+            }
+            _ => Err(Error::NotTlsListener(listener)),
+        }
+    }
+}
+
+/// Handle to replace TLS acceptors while server is running.
+///
+/// Obtained with `Server::tls_handle`. Listeners are identified by index
+/// in `Server::local_addrs` order, only TLS listeners can be modified.
+/// New acceptors are used for connections accepted after replacement,
+/// established connections are not affected.
+pub struct ServerTlsHandle<A: TlsAcceptor> {
+    acceptors: Arc<ServerTlsAcceptors<A>>,
+}
+
+impl<A: TlsAcceptor> Clone for ServerTlsHandle<A> {
+    fn clone(&self) -> Self {
+        ServerTlsHandle {
+            acceptors: self.acceptors.clone(),
+        }
+    }
+}
+
+impl<A: TlsAcceptor> ServerTlsHandle<A> {
+    pub(crate) fn new(acceptors: Arc<ServerTlsAcceptors<A>>) -> ServerTlsHandle<A> {
+        ServerTlsHandle { acceptors }
+    }
+
+    /// Replace acceptor of the listener.
+    ///
+    /// Returns `Error::NotTlsListener` if there's no such TLS listener.
+    pub fn set_tls(&self, listener: usize, acceptor: A) -> Result<()> {
+        self.acceptors
+            .replace(listener, ServerTlsOption::Tls(Arc::new(acceptor)))
+    }
+
+    /// Replace acceptors of the listener with SNI map.
+    ///
+    /// The whole map is replaced, names missing in the new map are no longer served.
+    /// Returns `Error::NotTlsListener` if there's no such TLS listener.
+    pub fn set_tls_sni(&self, listener: usize, acceptors: ServerTlsSniMap<A>) -> Result<()> {
+        self.acceptors
+            .replace(listener, ServerTlsOption::TlsSni(Arc::new(acceptors)))
+    }
+
+    /// Reload acceptor of the listener when certificate or key files change.
+    ///
+    /// Files are checked for modification every `interval` in a separate thread,
+    /// and `load` is called to create new acceptor after any change.
+    /// If `load` fails, `on_error` is called and previous acceptor is kept.
+    /// Watching stops when server and all handles are dropped.
+    pub fn watch_tls_files<L, E>(
+        &self,
+        listener: usize,
+        files: Vec<PathBuf>,
+        interval: Duration,
+        load: L,
+        on_error: E,
+    ) -> Result<()>
+    where
+        L: Fn() -> Result<A> + Send + 'static,
+        E: Fn(Error) + Send + 'static,
+    {
+        self.acceptors.check(listener)?;
+        spawn_tls_files_watcher(
+            Arc::downgrade(&self.acceptors),
+            listener,
+            files,
+            interval,
+            move || load().map(|a| ServerTlsOption::Tls(Arc::new(a))),
+            on_error,
+        )
+    }
+
+    /// Reload SNI map of the listener when certificate or key files change.
+    ///
+    /// Same as `watch_tls_files`, but `load` creates the whole SNI map.
+    pub fn watch_tls_sni_files<L, E>(
+        &self,
+        listener: usize,
+        files: Vec<PathBuf>,
+        interval: Duration,
+        load: L,
+        on_error: E,
+    ) -> Result<()>
+    where
+        L: Fn() -> Result<ServerTlsSniMap<A>> + Send + 'static,
+        E: Fn(Error) + Send + 'static,
+    {
+        self.acceptors.check(listener)?;
+        spawn_tls_files_watcher(
+            Arc::downgrade(&self.acceptors),
+            listener,
+            files,
+            interval,
+            move || load().map(|m| ServerTlsOption::TlsSni(Arc::new(m))),
+            on_error,
+        )
+    }
+}
+
+/// Modification time and size of files, `None` for missing files.
+fn files_stamp(files: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+    files
+        .iter()
+        .map(|f| {
+            let meta = fs::metadata(f).ok()?;
+            Some((meta.modified().ok()?, meta.len()))
+        })
+        .collect()
+}
+
+/// Poll files and replace listener TLS option when any of files changes.
+///
+/// Thread exits when acceptors are dropped.
+fn spawn_tls_files_watcher<A, L, E>(
+    acceptors: Weak<ServerTlsAcceptors<A>>,
+    listener: usize,
+    files: Vec<PathBuf>,
+    interval: Duration,
+    load: L,
+    on_error: E,
+) -> Result<()>
+where
+    A: TlsAcceptor,
+    L: Fn() -> Result<ServerTlsOption<A>> + Send + 'static,
+    E: Fn(Error) + Send + 'static,
+{
+    let mut stamp = files_stamp(&files);
+    thread::Builder::new()
+        .name("http2-tls-watcher".to_owned())
+        .spawn(move || loop {
+            thread::sleep(interval);
+
+            let acceptors = match acceptors.upgrade() {
+                Some(acceptors) => acceptors,
+                None => return,
+            };
+
+            let new_stamp = files_stamp(&files);
+            if new_stamp == stamp {
+                continue;
+            }
+            stamp = new_stamp;
+
+            // keep previous acceptor on error, files might be partially written
+            match load().and_then(|option| acceptors.replace(listener, option)) {
+                Ok(()) => info!("reloaded TLS acceptor from {:?}", files),
+                Err(e) => {
+                    warn!("failed to reload TLS acceptor from {:?}: {:?}", files, e);
+                    on_error(e);
+                }
+            }
+        })?;
+    Ok(())
 }
@@ -8,6 +8,7 @@ use crate::hpack::encoder::EncodeBuf;
 use crate::solicit::frame::continuation::ContinuationFlag;
 use crate::solicit::frame::flags::*;
 use crate::solicit::frame::pack_header;
+use crate::solicit::frame::push_promise::PushPromiseFlag;
 use crate::solicit::frame::HttpFrameType;
 use crate::solicit::frame::ParseFrameError;
 use crate::solicit::frame::ParseFrameResult;
@@ -16,6 +17,7 @@ use crate::solicit::frame::{
     parse_padded_payload, Frame, FrameBuilder, FrameHeader, FrameIR, RawFrame,
 };
 use crate::solicit::stream_id::StreamId;
+use crate::solicit::stream_id::MAX_STREAM_ID;
 use crate::Headers;
 use std::cmp;
 use std::fmt;
@@ -406,8 +408,35 @@ impl<'a> fmt::Debug for HeadersMultiFrame<'a> {
     }
 }
 
+/// Encoder of `PUSH_PROMISE` header block into multiple frames
+pub struct PushPromiseMultiFrame<'a> {
+    /// The ID of the stream with which this frame is associated
+    pub stream_id: StreamId,
+    /// Promised Stream ID
+    pub promised_stream_id: StreamId,
+    /// Request headers of promised stream.
+    pub headers: Headers,
+
+    /// Header encoder state.
+    pub encoder: &'a mut hpack::Encoder,
+    /// Current max frame size for encoding.
+    pub max_frame_size: u32,
+}
+
+impl<'a> fmt::Debug for PushPromiseMultiFrame<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PushPromiseMultiFrame")
+            .field("stream_id", &self.stream_id)
+            .field("promised_stream_id", &self.promised_stream_id)
+            .field("headers", &self.headers)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
 enum HeadersFrameType {
     Headers,
+    PushPromise,
     Continuation,
 }
 
@@ -415,6 +444,7 @@ impl HeadersFrameType {
     fn frame_type(&self) -> HttpFrameType {
         match self {
             HeadersFrameType::Headers => HttpFrameType::Headers,
+            HeadersFrameType::PushPromise => HttpFrameType::PushPromise,
             HeadersFrameType::Continuation => HttpFrameType::Continuation,
         }
     }
@@ -430,6 +460,10 @@ impl HeadersFrameType {
                 }
                 .0
             }
+            HeadersFrameType::PushPromise => match last {
+                true => PushPromiseFlag::EndHeaders.bitmask(),
+                false => 0,
+            },
             HeadersFrameType::Continuation => match last {
                 true => ContinuationFlag::EndHeaders.bitmask(),
                 false => 0,
@@ -537,6 +571,35 @@ impl<'a> FrameIR for HeadersMultiFrame<'a> {
     }
 }
 
+impl<'a> FrameIR for PushPromiseMultiFrame<'a> {
+    fn serialize_into(self, builder: &mut WriteBuffer) {
+        let tail_vec = builder.tail_vec();
+
+        let mut buf = EncodeBufForHeadersMultiFrame {
+            flags: Flags::new(0),
+            stream_id: self.stream_id,
+            current_frame_type: HeadersFrameType::PushPromise,
+            current_frame_offset: tail_vec.remaining(),
+            builder: tail_vec,
+            max_frame_size: self.max_frame_size,
+        };
+
+        buf.open_frame();
+
+        buf.builder
+            .extend_from_slice(&(self.promised_stream_id & MAX_STREAM_ID).to_be_bytes());
+
+        let headers = self
+            .headers
+            .iter()
+            .map(|h| (h.name().as_bytes(), h.value()));
+
+        self.encoder.encode_into(headers, &mut buf);
+
+        buf.finish_frame(true);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{HeadersFlag, HeadersFrame, StreamDependency};
@@ -544,6 +607,8 @@ mod tests {
     use crate::solicit::frame::continuation::ContinuationFlag;
     use crate::solicit::frame::flags::Flags;
     use crate::solicit::frame::headers::HeadersMultiFrame;
+    use crate::solicit::frame::headers::PushPromiseMultiFrame;
+    use crate::solicit::frame::push_promise::PushPromiseFlag;
     use crate::solicit::frame::tests::build_padded_frame_payload;
     use crate::solicit::frame::unpack_frames_for_test;
     use crate::solicit::frame::FrameHeader;
@@ -905,4 +970,67 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_push_promise_multi_frame() {
+        let mut encoder = hpack::Encoder::new();
+
+        let mut headers = Headers::new_get("/push");
+        for i in 0..1000 {
+            headers.add(format!("h-{}", i), format!("v-{}", i))
+        }
+
+        let max_frame_size = 1000;
+
+        let serialized = PushPromiseMultiFrame {
+            stream_id: 3,
+            promised_stream_id: 4,
+            headers,
+            encoder: &mut encoder,
+            max_frame_size,
+        }
+        .serialize_into_vec();
+
+        let frames = unpack_frames_for_test(&serialized);
+        assert!(frames.len() > 2);
+        for (i, f) in frames.iter().enumerate() {
+            match f {
+                HttpFrame::PushPromise(p) => {
+                    assert_eq!(0, i);
+                    assert_eq!(3, p.stream_id);
+                    assert_eq!(4, p.promised_stream_id);
+                    assert_eq!(max_frame_size as usize - 4, p.header_fragment.len());
+                    assert_eq!(Flags::new(0), p.flags);
+                }
+                HttpFrame::Continuation(h) => {
+                    assert_ne!(0, i);
+                    assert_eq!(3, h.stream_id);
+                    let last = i == frames.len() - 1;
+                    if !last {
+                        assert_eq!(Flags::new(0), h.flags);
+                    } else {
+                        assert_eq!(Flags::new(0).with(ContinuationFlag::EndHeaders), h.flags);
+                    }
+                }
+                _ => panic!("wrong frame type"),
+            }
+        }
+
+        let single = PushPromiseMultiFrame {
+            stream_id: 1,
+            promised_stream_id: 2,
+            headers: Headers::new_get("/"),
+            encoder: &mut encoder,
+            max_frame_size,
+        }
+        .serialize_into_vec();
+
+        match &unpack_frames_for_test(&single)[..] {
+            [HttpFrame::PushPromise(p)] => {
+                assert_eq!(2, p.promised_stream_id);
+                assert_eq!(Flags::new(0).with(PushPromiseFlag::EndHeaders), p.flags);
+            }
+            frames => panic!("wrong frames: {:?}", frames),
+        }
+    }
 }
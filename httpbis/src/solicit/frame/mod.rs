@@ -64,6 +64,7 @@ pub use self::headers::HeadersDecodedFrame;
 pub use self::headers::HeadersFlag;
 pub use self::headers::HeadersFrame;
 pub use self::headers::HeadersMultiFrame;
+pub use self::headers::PushPromiseMultiFrame;
 pub use self::ping::PingFrame;
 pub use self::priority::PriorityFrame;
 pub use self::push_promise::PushPromiseFlag;
@@ -149,6 +149,7 @@ impl FrameIR for PushPromiseFrame {
         if padded {
             b.extend_from_slice(&[self.padding_len]);
         }
+        b.extend_from_slice(&self.promised_stream_id.to_be_bytes());
         // Now the actual headers fragment
         b.extend_from_bytes(self.header_fragment);
         // Finally, add the trailing padding, if required
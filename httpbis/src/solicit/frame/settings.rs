@@ -284,7 +284,7 @@ impl SettingsFrame {
     ///
     /// If the frame is invalid (i.e. the length of the payload is not a
     /// multiple of 6) it returns `None`.
-    fn parse_payload(payload: &[u8]) -> ParseFrameResult<Vec<HttpSetting>> {
+    pub(crate) fn parse_payload(payload: &[u8]) -> ParseFrameResult<Vec<HttpSetting>> {
         if payload.len() % 6 != 0 {
             return Err(ParseFrameError::ProtocolError);
         }
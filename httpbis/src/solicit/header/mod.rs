@@ -419,6 +419,16 @@ impl Headers {
         }
     }
 
+    /// Remove all headers with given name
+    pub fn remove(&mut self, name: &str) {
+        self.headers.retain(|h| h.name() != name);
+        self.pseudo_count = self
+            .headers
+            .iter()
+            .take_while(|h| h.is_preudo_header())
+            .count();
+    }
+
     /// Add all headers
     pub fn extend(&mut self, headers: Headers) {
         self.headers.reserve(headers.headers.len());
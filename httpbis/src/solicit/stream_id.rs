@@ -1,2 +1,5 @@
 /// An alias for the type that represents the ID of an HTTP/2 stream
 pub type StreamId = u32;
+
+/// Max stream id, `2^31 - 1`
+pub const MAX_STREAM_ID: StreamId = 0x7fff_ffff;
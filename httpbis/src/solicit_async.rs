@@ -1,5 +1,6 @@
 use std::io;
 use std::io::Read;
+use std::mem;
 
 use bytes::Bytes;
 
@@ -9,6 +10,7 @@ use futures::stream::Stream;
 use std::future::Future;
 
 use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 
@@ -111,11 +113,32 @@ Request is made using HTTP/1, server only supports HTTP/2\r\n\
 
 /// Buf content looks like a start of HTTP/1 request
 fn looks_like_http_1(buf: &[u8]) -> bool {
-    buf.starts_with(b"GET ") || buf.starts_with(b"POST ") || buf.starts_with(b"HEAD ")
+    const METHODS: &[&[u8]] = &[
+        b"GET ",
+        b"HEAD ",
+        b"POST ",
+        b"PUT ",
+        b"DELETE ",
+        b"OPTIONS ",
+        b"PATCH ",
+        b"TRACE ",
+    ];
+    METHODS.iter().any(|m| buf.starts_with(m))
 }
 
-/// Recv HTTP/2 preface, or sent HTTP/1 500 and return error is input looks like HTTP/1 request
-async fn recv_preface_or_handle_http_1<I>(conn: &mut I) -> result::Result<()>
+/// Longest method in `looks_like_http_1` with trailing space
+const HTTP_1_METHOD_MAX_LEN: usize = 8;
+
+/// Server handshake outcome
+pub(crate) enum ServerHandshake {
+    /// HTTP/2 preface received and `SETTINGS` sent
+    Http2,
+    /// Connection is HTTP/1, contains already read bytes of the request
+    Http1(Vec<u8>),
+}
+
+/// Recv HTTP/2 preface, or return the start of HTTP/1 request
+async fn recv_preface_or_http_1<I>(conn: &mut I) -> result::Result<Option<Vec<u8>>>
 where
     I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 {
@@ -128,7 +151,7 @@ where
     where
         I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
     {
-        type Output = result::Result<bool>;
+        type Output = result::Result<Option<Vec<u8>>>;
 
         fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
             loop {
@@ -156,18 +179,20 @@ where
 
                 self.collected.push(c);
 
-                if self.collected == PREFACE {
-                    return Poll::Ready(Ok(false));
+                if PREFACE.starts_with(&self.collected) {
+                    if self.collected.len() == PREFACE.len() {
+                        return Poll::Ready(Ok(None));
+                    }
+                    continue;
                 }
 
-                // TODO: check only for first \n
-                if c == b'\n' {
-                    if looks_like_http_1(&self.collected) {
-                        return Poll::Ready(Ok(true));
-                    }
+                // Not a preface, wait for the space after HTTP/1 method
+                if c == b' ' && looks_like_http_1(&self.collected) {
+                    let collected = mem::take(&mut self.collected);
+                    return Poll::Ready(Ok(Some(collected)));
                 }
 
-                if self.collected.len() == PREFACE.len() {
+                if c == b' ' || self.collected.len() >= HTTP_1_METHOD_MAX_LEN {
                     return Poll::Ready(Err(error::Error::InvalidFrame(format!(
                         "wrong preface, likely TLS: {:?}",
                         BsDebug(&self.collected)
@@ -177,30 +202,66 @@ where
         }
     }
 
-    let need_500 = Intermediate {
+    Intermediate {
         conn,
         collected: Vec::new(),
     }
-    .await?;
+    .await
+}
+
+/// Receive client preface and send `SETTINGS`.
+///
+/// When `serve_http_1` is false HTTP/1 requests are replied with 500 and error is returned.
+pub(crate) async fn server_handshake<I>(
+    conn: &mut I,
+    settings: SettingsFrame,
+    serve_http_1: bool,
+) -> result::Result<ServerHandshake>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    if let Some(http_1) = recv_preface_or_http_1(conn).await? {
+        if serve_http_1 {
+            return Ok(ServerHandshake::Http1(http_1));
+        }
+
+        // Consume request line, otherwise close after the reply may reset the connection
+        let mut c = [0];
+        while conn.read(&mut c).await? == 1 && c[0] != b'\n' {}
 
-    if need_500 {
         conn.write_all(HTTP_1_500_RESPONSE).await?;
 
         return Err(error::Error::RequestIsMadeUsingHttp1);
     }
 
-    Ok(())
+    send_settings(conn, settings).await?;
+
+    Ok(ServerHandshake::Http2)
 }
 
-pub async fn server_handshake<I>(conn: &mut I, settings: SettingsFrame) -> result::Result<()>
+/// Send `SETTINGS` and receive client preface after `h2c` upgrade.
+pub(crate) async fn server_handshake_h2c<I>(
+    conn: &mut I,
+    settings: SettingsFrame,
+) -> result::Result<()>
 where
     I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 {
-    let mut preface_buf = Vec::with_capacity(PREFACE.len());
-    preface_buf.resize(PREFACE.len(), 0);
-
-    recv_preface_or_handle_http_1(conn).await?;
+    // 3.2
+    // The first HTTP/2 frame sent by the server MUST be a server connection
+    // preface (Section 3.5) consisting of a SETTINGS frame (Section 6.5).
+    // Upon receiving the 101 response, the client MUST send a connection
+    // preface (Section 3.5), which includes a SETTINGS frame.
     send_settings(conn, settings).await?;
 
+    let mut preface = vec![0; PREFACE.len()];
+    conn.read_exact(&mut preface).await?;
+    if preface != PREFACE {
+        return Err(error::Error::InvalidFrame(format!(
+            "wrong preface after h2c upgrade: {:?}",
+            BsDebug(&preface)
+        )));
+    }
+
     Ok(())
 }